@@ -0,0 +1,162 @@
+//! Benchmarks of representative state store workloads
+//!
+//! Every workload runs against an in-memory sqlite database and a disposable postgres instance.
+//! Run them with `cargo bench --features bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use matrix_sdk_base::{
+    media::{MediaFormat, MediaRequest},
+    RoomInfo, RoomType, StateChanges, StateStore as _,
+};
+use matrix_sdk_sql::{testing::TestPostgres, AnyStateStore};
+use ruma::{
+    events::room::{member::SyncRoomMemberEvent, MediaSource},
+    OwnedMxcUri, OwnedRoomId, OwnedUserId,
+};
+use tokio::runtime::Runtime;
+
+/// Number of members upserted by the member benchmark
+const MEMBERS: usize = 10_000;
+/// Number of rooms saved by the room info benchmark
+const ROOMS: usize = 1_000;
+/// Number of media files added, loaded and removed by the media benchmark
+const MEDIA_FILES: usize = 100;
+/// Size of every media file of the media benchmark in bytes
+const MEDIA_SIZE: usize = 64 * 1024;
+
+/// Opens a fresh store for every backend
+///
+/// The postgres instance has to be kept alive for as long as the store is used.
+#[allow(clippy::unwrap_used)]
+async fn open_stores(name: &str) -> (TestPostgres, Vec<(&'static str, AnyStateStore)>) {
+    let pg = TestPostgres::start().await.unwrap();
+    let pg_url = pg.database_url(name).await.unwrap();
+    let stores = vec![
+        (
+            "sqlite",
+            AnyStateStore::connect("sqlite://:memory:").await.unwrap(),
+        ),
+        ("postgres", AnyStateStore::connect(&pg_url).await.unwrap()),
+    ];
+    (pg, stores)
+}
+
+/// Returns state changes with membership events for `MEMBERS` users in a single room
+#[allow(clippy::unwrap_used)]
+fn member_changes() -> StateChanges {
+    let room_id = OwnedRoomId::try_from("!bench:localhost").unwrap();
+    let mut changes = StateChanges::default();
+    let members = changes.members.entry(room_id).or_default();
+    for user in 0..MEMBERS {
+        let user_id = OwnedUserId::try_from(format!("@user{user}:localhost")).unwrap();
+        let event: SyncRoomMemberEvent = serde_json::from_value(serde_json::json!({
+            "type": "m.room.member",
+            "event_id": format!("$member{user}:localhost"),
+            "sender": user_id,
+            "state_key": user_id,
+            "origin_server_ts": 1,
+            "content": {
+                "membership": "join",
+                "displayname": format!("User {user}"),
+            },
+        }))
+        .unwrap();
+        members.insert(user_id, event);
+    }
+    changes
+}
+
+/// Returns state changes with `ROOMS` joined rooms
+#[allow(clippy::unwrap_used)]
+fn room_info_changes() -> StateChanges {
+    let mut changes = StateChanges::default();
+    for room in 0..ROOMS {
+        let room_id = OwnedRoomId::try_from(format!("!room{room}:localhost")).unwrap();
+        changes.add_room(RoomInfo::new(&room_id, RoomType::Joined));
+    }
+    changes
+}
+
+/// Returns the requests of the media benchmark
+fn media_requests() -> Vec<MediaRequest> {
+    (0..MEDIA_FILES)
+        .map(|file| MediaRequest {
+            source: MediaSource::Plain(OwnedMxcUri::from(format!("mxc://localhost/media{file}"))),
+            format: MediaFormat::File,
+        })
+        .collect()
+}
+
+/// Benchmarks upserting the members of a large room
+#[allow(clippy::unwrap_used)]
+fn member_upserts(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (_pg, stores) = runtime.block_on(open_stores("member_upserts"));
+    let changes = member_changes();
+    let mut group = c.benchmark_group("member_upserts");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(MEMBERS as u64));
+    for (backend, store) in &stores {
+        group.bench_function(BenchmarkId::from_parameter(backend), |b| {
+            b.to_async(&runtime)
+                .iter(|| async { store.save_changes(&changes).await.unwrap() });
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks saving and loading the room infos of many rooms
+#[allow(clippy::unwrap_used)]
+fn room_infos(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (_pg, stores) = runtime.block_on(open_stores("room_infos"));
+    let changes = room_info_changes();
+    let mut group = c.benchmark_group("room_infos");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(ROOMS as u64));
+    for (backend, store) in &stores {
+        group.bench_function(BenchmarkId::new("save", backend), |b| {
+            b.to_async(&runtime)
+                .iter(|| async { store.save_changes(&changes).await.unwrap() });
+        });
+        group.bench_function(BenchmarkId::new("load", backend), |b| {
+            b.to_async(&runtime)
+                .iter(|| async { store.get_room_infos().await.unwrap() });
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks adding, loading and removing media files
+#[allow(clippy::unwrap_used)]
+fn media_churn(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (_pg, stores) = runtime.block_on(open_stores("media_churn"));
+    let requests = media_requests();
+    let content = vec![0x42; MEDIA_SIZE];
+    let mut group = c.benchmark_group("media_churn");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes((MEDIA_FILES * MEDIA_SIZE) as u64));
+    for (backend, store) in &stores {
+        group.bench_function(BenchmarkId::from_parameter(backend), |b| {
+            b.to_async(&runtime).iter(|| async {
+                for request in &requests {
+                    store
+                        .add_media_content(request, content.clone())
+                        .await
+                        .unwrap();
+                }
+                for request in &requests {
+                    store.get_media_content(request).await.unwrap();
+                }
+                for request in &requests {
+                    store.remove_media_content(request).await.unwrap();
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, member_upserts, room_infos, media_churn);
+criterion_main!(benches);
@@ -12,4 +12,289 @@ compile_error!("You must enable at least one database backend feature!");
 fn main() {
     // trigger recompilation when a new migration is added
     println!("cargo:rerun-if-changed=migrations");
+    #[cfg(feature = "checked-queries")]
+    checked_queries::check_all();
+}
+
+/// Checks the queries against the migrated schema of every enabled backend
+///
+/// The queries are generic over the database and get their table prefix at runtime, so they
+/// cannot use the `sqlx::query!` macros directly. Instead, this does what the macros do at
+/// compile time: it applies the migrations of a backend to a scratch database and lets the
+/// database describe every query, which fails if a query uses a table or column that the
+/// migrations do not create.
+#[cfg(feature = "checked-queries")]
+mod checked_queries {
+    use std::{
+        collections::BTreeMap,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use sqlx_check::{migrate::Migrator, AnyConnection, Connection, Executor};
+    use tokio_check::runtime::{Builder, Runtime};
+
+    /// The directory containing the sources of the crate
+    const SOURCE_DIR: &str = "src";
+
+    /// Query functions by name, with all SQL statements that they return
+    type QueryMap = BTreeMap<String, Vec<String>>;
+
+    /// The queries of one source file
+    #[derive(Debug, Default)]
+    struct ParsedQueries {
+        /// The queries used by all backends
+        defaults: QueryMap,
+        /// The queries of the postgres implementation, overriding the defaults
+        postgres: QueryMap,
+        /// The queries of the sqlite implementation, overriding the defaults
+        sqlite: QueryMap,
+    }
+
+    /// Section of a source file that a query belongs to
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Section {
+        /// Code used by all backends
+        Defaults,
+        /// Code of the postgres backend
+        Postgres,
+        /// Code of the sqlite backend
+        Sqlite,
+    }
+
+    /// Parses the string literal at the start of `source`, ignoring leading whitespace
+    ///
+    /// Returns the value of the literal and the number of bytes up to its end, or `None` if
+    /// `source` does not start with a string literal.
+    fn parse_string_literal(source: &str) -> Option<(String, usize)> {
+        let offset = source.len() - source.trim_start().len();
+        let literal = &source[offset..];
+        if let Some(raw) = literal.strip_prefix("r#\"") {
+            let end = raw.find("\"#")?;
+            return Some((raw[..end].to_owned(), offset + 3 + end + 2));
+        }
+        if let Some(raw) = literal.strip_prefix("r\"") {
+            let end = raw.find('"')?;
+            return Some((raw[..end].to_owned(), offset + 2 + end + 1));
+        }
+        let mut chars = literal.strip_prefix('"')?.char_indices();
+        let mut value = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Some((value, offset + 1 + i + 1)),
+                '\\' => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    '\n' => {
+                        // A line continuation skips the leading whitespace of the next line
+                        let rest = chars.as_str();
+                        let skipped = rest.len() - rest.trim_start().len();
+                        for _ in rest[..skipped].chars() {
+                            chars.next();
+                        }
+                    }
+                    escaped => value.push(escaped),
+                },
+                c => value.push(c),
+            }
+        }
+        None
+    }
+
+    /// Extracts the SQL of every `prefixed_query` call with a string literal from a source file
+    ///
+    /// The queries are grouped by the function containing them. Queries in an `impl` block for
+    /// `Postgres` or `Sqlite`, or in a top-level item that is only compiled with the `postgres`
+    /// or `sqlite` feature, belong to that backend. Tests are skipped.
+    fn parse_queries(source: &str) -> ParsedQueries {
+        let mut parsed = ParsedQueries::default();
+        let mut section = Section::Defaults;
+        let mut cfg_section = None;
+        let mut function = String::new();
+        let mut skip_to = 0;
+        let mut line_start = 0;
+        for line in source.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+            if offset < skip_to {
+                continue;
+            }
+            let trimmed = line.trim();
+            if trimmed.starts_with("//") {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) && !trimmed.is_empty() {
+                if trimmed == "#[cfg(test)]" || trimmed.starts_with("mod tests") {
+                    break;
+                } else if trimmed == "#[cfg(feature = \"postgres\")]" {
+                    cfg_section = Some(Section::Postgres);
+                } else if trimmed == "#[cfg(feature = \"sqlite\")]" {
+                    cfg_section = Some(Section::Sqlite);
+                } else if trimmed == "}" {
+                    section = Section::Defaults;
+                } else if matches!(
+                    trimmed.split(|c: char| !c.is_alphanumeric()).next(),
+                    Some("pub" | "impl" | "fn" | "async" | "const" | "static" | "mod" | "trait")
+                ) {
+                    section = if !trimmed.starts_with("impl") {
+                        cfg_section.unwrap_or(Section::Defaults)
+                    } else if trimmed.contains("Postgres") {
+                        Section::Postgres
+                    } else if trimmed.contains("Sqlite") {
+                        Section::Sqlite
+                    } else {
+                        Section::Defaults
+                    };
+                    cfg_section = None;
+                }
+            }
+            let mut words = trimmed.split_whitespace();
+            if words.any(|word| word == "fn") {
+                if let Some(name) = words.next() {
+                    function = name
+                        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .next()
+                        .unwrap_or_default()
+                        .to_owned();
+                }
+            }
+            let mut search = offset;
+            while let Some(start) = source[search..line_start].find("prefixed_query(") {
+                let call = search + start;
+                search = call + "prefixed_query(".len();
+                if source[..call].ends_with("fn ") {
+                    continue;
+                }
+                if let Some((sql, len)) = parse_string_literal(&source[search..]) {
+                    let queries = match section {
+                        Section::Defaults => &mut parsed.defaults,
+                        Section::Postgres => &mut parsed.postgres,
+                        Section::Sqlite => &mut parsed.sqlite,
+                    };
+                    queries.entry(function.clone()).or_default().push(sql);
+                    search += len;
+                    skip_to = search;
+                    if search >= line_start {
+                        break;
+                    }
+                }
+            }
+        }
+        parsed
+    }
+
+    /// Returns the paths of all Rust source files in a directory and its subdirectories
+    #[allow(clippy::panic)]
+    fn source_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)
+            .unwrap_or_else(|error| panic!("Failed to list {}: {error}", dir.display()))
+        {
+            let path = entry
+                .unwrap_or_else(|error| panic!("Failed to list {}: {error}", dir.display()))
+                .path();
+            if path.is_dir() {
+                files.extend(source_files(&path));
+            } else if path
+                .extension()
+                .map_or(false, |extension| extension == "rs")
+            {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// Returns all queries used by a backend, with the name of the function returning them
+    #[allow(clippy::panic)]
+    fn backend_queries(sqlite: bool) -> Vec<(String, String)> {
+        let mut queries = Vec::new();
+        for file in source_files(Path::new(SOURCE_DIR)) {
+            let source = fs::read_to_string(&file)
+                .unwrap_or_else(|error| panic!("Failed to read {}: {error}", file.display()));
+            let parsed = parse_queries(&source);
+            let mut overrides = if sqlite {
+                parsed.sqlite
+            } else {
+                parsed.postgres
+            };
+            for (function, statements) in parsed.defaults {
+                let statements = overrides.remove(&function).unwrap_or(statements);
+                queries.extend(statements.into_iter().map(|sql| (function.clone(), sql)));
+            }
+            for (function, statements) in overrides {
+                queries.extend(statements.into_iter().map(|sql| (function.clone(), sql)));
+            }
+        }
+        queries
+    }
+
+    /// Migrates the database at `url` and describes all queries of the backend
+    ///
+    /// Returns a description of every query that the database rejected.
+    #[allow(clippy::panic)]
+    fn check_backend(runtime: &Runtime, url: &str, migrations: &str, sqlite: bool) -> Vec<String> {
+        let queries = backend_queries(sqlite);
+        runtime.block_on(async {
+            let mut conn = AnyConnection::connect(url)
+                .await
+                .unwrap_or_else(|error| panic!("Failed to connect to {url}: {error}"));
+            Migrator::new(Path::new(migrations))
+                .await
+                .unwrap_or_else(|error| panic!("Failed to load {migrations}: {error}"))
+                .run(&mut conn)
+                .await
+                .unwrap_or_else(|error| panic!("Failed to apply {migrations}: {error}"));
+            let mut errors = Vec::new();
+            for (function, sql) in queries {
+                if let Err(error) = (&mut conn).describe(&sql).await {
+                    errors.push(format!("{function}: {error}"));
+                }
+            }
+            errors
+        })
+    }
+
+    /// Checks the queries of all enabled backends
+    ///
+    /// The sqlite queries are checked against an in-memory database. The postgres queries are
+    /// checked against the database in `DATABASE_URL`, which has to be a scratch database because
+    /// the migrations are applied to it.
+    #[allow(clippy::panic)]
+    pub(super) fn check_all() {
+        println!("cargo:rerun-if-changed={SOURCE_DIR}");
+        println!("cargo:rerun-if-env-changed=DATABASE_URL");
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap_or_else(|error| panic!("Failed to start the runtime: {error}"));
+        let mut errors = Vec::new();
+        if cfg!(feature = "sqlite") {
+            errors.extend(
+                check_backend(&runtime, "sqlite::memory:", "migrations/sqlite", true)
+                    .into_iter()
+                    .map(|error| format!("sqlite: {error}")),
+            );
+        }
+        if cfg!(feature = "postgres") {
+            match std::env::var("DATABASE_URL") {
+                Ok(url) => errors.extend(
+                    check_backend(&runtime, &url, "migrations/postgres", false)
+                        .into_iter()
+                        .map(|error| format!("postgres: {error}")),
+                ),
+                Err(_) => println!(
+                    "cargo:warning=DATABASE_URL is not set, the postgres queries are not checked"
+                ),
+            }
+        }
+        if !errors.is_empty() {
+            panic!(
+                "Queries do not match the migrated schema:\n{}",
+                errors.join("\n")
+            );
+        }
+    }
 }
@@ -0,0 +1,2 @@
+DROP INDEX cryptostore_inbound_group_session_backed_up_idx;
+ALTER TABLE cryptostore_inbound_group_session DROP COLUMN backed_up;
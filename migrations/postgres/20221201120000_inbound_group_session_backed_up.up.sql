@@ -0,0 +1,3 @@
+ALTER TABLE cryptostore_inbound_group_session
+ADD COLUMN backed_up BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX cryptostore_inbound_group_session_backed_up_idx ON cryptostore_inbound_group_session (backed_up);
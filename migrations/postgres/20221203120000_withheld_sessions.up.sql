@@ -0,0 +1,6 @@
+CREATE TABLE cryptostore_withheld_sessions (
+    room_id BYTEA NOT NULL,
+    session_id BYTEA NOT NULL,
+    withheld_data BYTEA NOT NULL,
+    PRIMARY KEY (room_id, session_id)
+);
@@ -0,0 +1,2 @@
+DROP INDEX cryptostore_secrets_secret_name_idx;
+DROP TABLE cryptostore_secrets;
@@ -0,0 +1,6 @@
+CREATE TABLE cryptostore_secrets (
+    secret_id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    secret_name BYTEA NOT NULL,
+    secret_data BYTEA NOT NULL
+);
+CREATE INDEX cryptostore_secrets_secret_name_idx ON cryptostore_secrets (secret_name);
@@ -0,0 +1,4 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT 'private_identity'::BYTEA, identity_data FROM cryptostore_private_identity
+LIMIT 1;
+DROP TABLE cryptostore_private_identity;
@@ -0,0 +1,12 @@
+-- Own cross-signing key pairs, previously stored in statestore_kv
+CREATE TABLE cryptostore_private_identity (
+    user_id BYTEA PRIMARY KEY NOT NULL,
+    identity_data BYTEA NOT NULL
+);
+-- The user ID of the identity is only hashed once the store is unlocked, so the legacy key is
+-- kept until the identity is loaded
+INSERT INTO cryptostore_private_identity (user_id, identity_data)
+SELECT kv_key, kv_value FROM statestore_kv
+WHERE kv_key = 'private_identity'::BYTEA;
+DELETE FROM statestore_kv
+WHERE kv_key = 'private_identity'::BYTEA;
@@ -0,0 +1,4 @@
+CREATE TABLE cryptostore_room_settings (
+    room_id BYTEA PRIMARY KEY NOT NULL,
+    settings_data BYTEA NOT NULL
+);
@@ -0,0 +1,5 @@
+CREATE TABLE cryptostore_lease_locks (
+    lock_key TEXT PRIMARY KEY NOT NULL,
+    holder TEXT NOT NULL,
+    expiration BIGINT NOT NULL -- Unix timestamp in milliseconds
+);
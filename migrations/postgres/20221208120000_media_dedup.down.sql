@@ -0,0 +1,6 @@
+UPDATE statestore_media
+SET media_data = (SELECT media_data FROM statestore_media_blobs WHERE statestore_media_blobs.content_hash = statestore_media.content_hash)
+WHERE content_hash IS NOT NULL;
+DROP INDEX statestore_media_content_hash;
+ALTER TABLE statestore_media DROP COLUMN content_hash;
+DROP TABLE statestore_media_blobs;
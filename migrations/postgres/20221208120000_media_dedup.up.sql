@@ -0,0 +1,8 @@
+-- Media contents shared between mxc URLs, keyed by their SHA-256 hash
+CREATE TABLE statestore_media_blobs (
+  content_hash BYTEA PRIMARY KEY NOT NULL,
+  media_data BYTEA NOT NULL
+);
+ALTER TABLE statestore_media
+ADD COLUMN content_hash BYTEA;
+CREATE INDEX statestore_media_content_hash ON statestore_media (content_hash);
@@ -0,0 +1,8 @@
+DELETE FROM statestore_media
+WHERE thumbnail_width <> 0 OR thumbnail_height <> 0 OR thumbnail_method <> '';
+ALTER TABLE statestore_media DROP CONSTRAINT statestore_media_pkey;
+ALTER TABLE statestore_media ADD PRIMARY KEY (media_url);
+ALTER TABLE statestore_media
+DROP COLUMN thumbnail_width,
+DROP COLUMN thumbnail_height,
+DROP COLUMN thumbnail_method;
@@ -0,0 +1,8 @@
+-- Thumbnails are stored as separate entries of the media they belong to.
+-- The original file has a width and height of 0 and an empty method.
+ALTER TABLE statestore_media
+ADD COLUMN thumbnail_width BIGINT NOT NULL DEFAULT 0,
+ADD COLUMN thumbnail_height BIGINT NOT NULL DEFAULT 0,
+ADD COLUMN thumbnail_method TEXT NOT NULL DEFAULT '';
+ALTER TABLE statestore_media DROP CONSTRAINT statestore_media_pkey;
+ALTER TABLE statestore_media ADD PRIMARY KEY (media_url, thumbnail_width, thumbnail_height, thumbnail_method);
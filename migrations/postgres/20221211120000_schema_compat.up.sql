@@ -0,0 +1,7 @@
+-- Schema version written by the newest crate version that opened the database, and the oldest
+-- schema version a crate version needs to know about to be able to use the database
+CREATE TABLE statestore_schema_compat (
+  id BIGINT PRIMARY KEY NOT NULL, -- Always 0
+  schema_version BIGINT NOT NULL,
+  min_reader_version BIGINT NOT NULL
+);
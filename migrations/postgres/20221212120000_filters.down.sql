@@ -0,0 +1,5 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT convert_to('filter:' || filter_name, 'UTF8'), convert_to(filter_id, 'UTF8')
+FROM statestore_filters
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE statestore_filters;
@@ -0,0 +1,13 @@
+-- Sync filter ids by name, previously stored in statestore_kv
+CREATE TABLE statestore_filters (
+  filter_name TEXT PRIMARY KEY NOT NULL,
+  filter_id TEXT NOT NULL,
+  updated_at BIGINT NOT NULL -- Unix timestamp in seconds
+);
+INSERT INTO statestore_filters (filter_name, filter_id, updated_at)
+SELECT convert_from(substring(kv_key FROM 8), 'UTF8'), convert_from(kv_value, 'UTF8'),
+  EXTRACT(EPOCH FROM now())::BIGINT
+FROM statestore_kv
+WHERE substring(kv_key FROM 1 FOR 7) = 'filter:'::bytea;
+DELETE FROM statestore_kv
+WHERE substring(kv_key FROM 1 FOR 7) = 'filter:'::bytea;
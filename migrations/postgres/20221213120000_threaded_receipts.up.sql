@@ -0,0 +1,5 @@
+-- Thread of a receipt (MSC3771), empty for unthreaded receipts
+ALTER TABLE statestore_receipts
+ADD COLUMN thread_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE statestore_receipts DROP CONSTRAINT statestore_receipts_pkey;
+ALTER TABLE statestore_receipts ADD PRIMARY KEY (room_id, receipt_type, user_id, thread_id);
@@ -0,0 +1,5 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT 'sync_token'::bytea, convert_to(sync_token, 'UTF8') FROM statestore_sync_tokens
+ORDER BY id DESC LIMIT 1
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE statestore_sync_tokens;
@@ -0,0 +1,9 @@
+-- The most recent sync tokens, newest last
+CREATE TABLE statestore_sync_tokens (
+  id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+  sync_token TEXT NOT NULL,
+  created_at BIGINT NOT NULL -- Unix timestamp in seconds
+);
+INSERT INTO statestore_sync_tokens (sync_token, created_at)
+SELECT convert_from(kv_value, 'UTF8'), 0 FROM statestore_kv WHERE kv_key = 'sync_token'::bytea;
+DELETE FROM statestore_kv WHERE kv_key = 'sync_token'::bytea;
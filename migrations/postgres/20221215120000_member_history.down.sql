@@ -0,0 +1,2 @@
+DROP INDEX statestore_member_history_room_user;
+DROP TABLE statestore_member_history;
@@ -0,0 +1,10 @@
+-- Append-only log of membership changes, only written if enabled
+CREATE TABLE statestore_member_history (
+  id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+  room_id TEXT NOT NULL,
+  user_id TEXT NOT NULL,
+  membership TEXT NOT NULL,
+  event_id TEXT NOT NULL,
+  recorded_at BIGINT NOT NULL -- Unix timestamp in seconds
+);
+CREATE INDEX statestore_member_history_room_user ON statestore_member_history (room_id, user_id);
@@ -0,0 +1,5 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT 'custom:'::bytea || custom_key, custom_value
+FROM statestore_custom_values
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE statestore_custom_values;
@@ -0,0 +1,11 @@
+-- Custom values of the application, previously stored in statestore_kv
+CREATE TABLE statestore_custom_values (
+  custom_key BYTEA PRIMARY KEY NOT NULL,
+  custom_value BYTEA NOT NULL
+);
+INSERT INTO statestore_custom_values (custom_key, custom_value)
+SELECT substring(kv_key FROM 8), kv_value
+FROM statestore_kv
+WHERE substring(kv_key FROM 1 FOR 7) = 'custom:'::bytea;
+DELETE FROM statestore_kv
+WHERE substring(kv_key FROM 1 FOR 7) = 'custom:'::bytea;
@@ -0,0 +1,2 @@
+DROP INDEX statestore_display_names_room_name;
+DROP TABLE statestore_display_names;
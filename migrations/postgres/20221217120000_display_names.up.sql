@@ -0,0 +1,12 @@
+-- Display names of room members, for display name disambiguation
+CREATE TABLE statestore_display_names (
+  room_id TEXT NOT NULL,
+  user_id TEXT NOT NULL,
+  display_name TEXT NOT NULL,
+  PRIMARY KEY (room_id, user_id)
+);
+CREATE INDEX statestore_display_names_room_name ON statestore_display_names (room_id, display_name);
+INSERT INTO statestore_display_names (room_id, user_id, display_name)
+SELECT room_id, user_id, displayname
+FROM statestore_members
+WHERE displayname IS NOT NULL;
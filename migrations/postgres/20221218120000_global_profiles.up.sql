@@ -0,0 +1,16 @@
+-- Latest known profile of every user, independent of rooms
+CREATE TABLE statestore_profiles (
+  user_id TEXT PRIMARY KEY NOT NULL,
+  displayname TEXT,
+  avatar_url TEXT,
+  updated_at BIGINT NOT NULL
+);
+INSERT INTO statestore_profiles (user_id, displayname, avatar_url, updated_at)
+SELECT DISTINCT ON (user_id)
+  user_id,
+  user_profile->'content'->>'displayname',
+  user_profile->'content'->>'avatar_url',
+  0
+FROM statestore_members
+WHERE user_profile IS NOT NULL
+ORDER BY user_id, room_id;
@@ -0,0 +1,39 @@
+-- Index over the m.tag room account data
+CREATE TABLE statestore_room_tags (
+  room_id TEXT NOT NULL,
+  tag TEXT NOT NULL,
+  tag_order DOUBLE PRECISION,
+  PRIMARY KEY (room_id, tag)
+);
+CREATE INDEX statestore_room_tags_tag ON statestore_room_tags (tag);
+-- Index over the m.direct global account data
+CREATE TABLE statestore_direct_chats (
+  user_id TEXT NOT NULL,
+  room_id TEXT NOT NULL,
+  PRIMARY KEY (user_id, room_id)
+);
+INSERT INTO statestore_room_tags (room_id, tag, tag_order)
+SELECT
+  a.room_id,
+  t.key,
+  CASE WHEN jsonb_typeof(t.value->'order') = 'number' THEN (t.value->>'order')::DOUBLE PRECISION END
+FROM statestore_accountdata a,
+  jsonb_each(CASE
+    WHEN jsonb_typeof(a.account_data->'content'->'tags') = 'object'
+    THEN a.account_data->'content'->'tags'
+    ELSE '{}'::jsonb
+  END) t
+WHERE a.event_type = 'm.tag';
+INSERT INTO statestore_direct_chats (user_id, room_id)
+SELECT u.key, r.value
+FROM statestore_accountdata a,
+  jsonb_each(CASE
+    WHEN jsonb_typeof(a.account_data->'content') = 'object' THEN a.account_data->'content'
+    ELSE '{}'::jsonb
+  END) u,
+  jsonb_array_elements_text(CASE
+    WHEN jsonb_typeof(u.value) = 'array' THEN u.value
+    ELSE '[]'::jsonb
+  END) r
+WHERE a.room_id = '' AND a.event_type = 'm.direct'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,16 @@
+DROP INDEX statestore_rooms_tombstone_room;
+DROP TABLE statestore_rooms_tombstone;
+DROP INDEX statestore_accountdata_tombstone_room;
+DROP TABLE statestore_accountdata_tombstone;
+DROP INDEX statestore_members_tombstone_room;
+DROP TABLE statestore_members_tombstone;
+DROP INDEX statestore_display_names_tombstone_room;
+DROP TABLE statestore_display_names_tombstone;
+DROP INDEX statestore_state_tombstone_room;
+DROP TABLE statestore_state_tombstone;
+DROP INDEX statestore_receipts_tombstone_room;
+DROP TABLE statestore_receipts_tombstone;
+DROP INDEX statestore_room_tags_tombstone_room;
+DROP TABLE statestore_room_tags_tombstone;
+DROP INDEX statestore_room_tombstones_removed_at;
+DROP TABLE statestore_room_tombstones;
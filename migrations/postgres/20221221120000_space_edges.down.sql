@@ -0,0 +1,2 @@
+DROP INDEX statestore_space_edges_child;
+DROP TABLE statestore_space_edges;
@@ -0,0 +1,3 @@
+-- Pinned media is never evicted to enforce the media size budget
+ALTER TABLE statestore_media
+ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,9 @@
+-- Rows that could not be deserialized, moved out of their table so that loading can continue
+CREATE TABLE statestore_quarantine (
+  source_table TEXT NOT NULL,
+  row_key TEXT NOT NULL,
+  row_data TEXT NOT NULL,
+  error TEXT NOT NULL,
+  quarantined_at BIGINT NOT NULL,
+  PRIMARY KEY (source_table, row_key)
+);
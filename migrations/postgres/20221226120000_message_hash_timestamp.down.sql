@@ -0,0 +1,2 @@
+DROP INDEX cryptostore_message_hash_created_at_idx;
+ALTER TABLE cryptostore_message_hash DROP COLUMN created_at;
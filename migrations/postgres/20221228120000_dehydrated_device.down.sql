@@ -0,0 +1 @@
+DROP TABLE cryptostore_dehydrated_device;
@@ -0,0 +1,6 @@
+-- Verification requests that are still in progress
+CREATE TABLE cryptostore_verification (
+  flow_id BYTEA PRIMARY KEY NOT NULL,
+  verification_data BYTEA NOT NULL,
+  updated_at BIGINT NOT NULL -- Unix timestamp in seconds
+);
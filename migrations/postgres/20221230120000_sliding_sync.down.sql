@@ -0,0 +1,3 @@
+DROP TABLE statestore_sliding_sync_subscriptions;
+DROP TABLE statestore_sliding_sync_lists;
+DROP TABLE statestore_sliding_sync;
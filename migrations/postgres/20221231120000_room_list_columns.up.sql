@@ -0,0 +1,23 @@
+-- Room info fields used for sorting the room list in SQL
+ALTER TABLE statestore_rooms
+ADD COLUMN room_name TEXT;
+ALTER TABLE statestore_rooms
+ADD COLUMN is_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE statestore_rooms
+ADD COLUMN room_state TEXT;
+ALTER TABLE statestore_rooms_tombstone
+ADD COLUMN room_name TEXT;
+ALTER TABLE statestore_rooms_tombstone
+ADD COLUMN is_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE statestore_rooms_tombstone
+ADD COLUMN room_state TEXT;
+UPDATE statestore_rooms
+SET room_name = room_info #>> '{base_info,name,content,name}',
+  is_encrypted = COALESCE(jsonb_typeof(room_info #> '{base_info,encryption}') = 'object', FALSE),
+  room_state = room_info ->> 'room_type';
+UPDATE statestore_rooms_tombstone
+SET room_name = room_info #>> '{base_info,name,content,name}',
+  is_encrypted = COALESCE(jsonb_typeof(room_info #> '{base_info,encryption}') = 'object', FALSE),
+  room_state = room_info ->> 'room_type';
+CREATE INDEX statestore_rooms_name ON statestore_rooms (room_name);
+CREATE INDEX statestore_rooms_activity ON statestore_rooms (latest_event_ts);
@@ -0,0 +1,14 @@
+-- Index over the m.ignored_user_list global account data
+CREATE TABLE statestore_ignored_users (
+  user_id TEXT PRIMARY KEY NOT NULL
+);
+INSERT INTO statestore_ignored_users (user_id)
+SELECT u.key
+FROM statestore_accountdata a,
+  jsonb_each(CASE
+    WHEN jsonb_typeof(a.account_data->'content'->'ignored_users') = 'object'
+    THEN a.account_data->'content'->'ignored_users'
+    ELSE '{}'::jsonb
+  END) u
+WHERE a.room_id = '' AND a.event_type = 'm.ignored_user_list'
+ON CONFLICT DO NOTHING;
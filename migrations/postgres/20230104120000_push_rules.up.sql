@@ -0,0 +1,33 @@
+-- Normalized copy of the global rules of the m.push_rules account data
+CREATE TABLE statestore_push_rules (
+  kind TEXT NOT NULL,
+  rule_id TEXT NOT NULL,
+  rule_position BIGINT NOT NULL,
+  enabled BOOLEAN NOT NULL,
+  is_default BOOLEAN NOT NULL,
+  rule TEXT NOT NULL,
+  PRIMARY KEY (kind, rule_id)
+);
+CREATE INDEX statestore_push_rules_position ON statestore_push_rules (kind, rule_position);
+INSERT INTO statestore_push_rules (kind, rule_id, rule_position, enabled, is_default, rule)
+SELECT
+  k.key,
+  r.value->>'rule_id',
+  r.position - 1,
+  COALESCE(CASE WHEN jsonb_typeof(r.value->'enabled') = 'boolean' THEN (r.value->>'enabled')::BOOLEAN END, FALSE),
+  COALESCE(CASE WHEN jsonb_typeof(r.value->'default') = 'boolean' THEN (r.value->>'default')::BOOLEAN END, FALSE),
+  r.value::TEXT
+FROM statestore_accountdata a,
+  jsonb_each(CASE
+    WHEN jsonb_typeof(a.account_data->'content'->'global') = 'object'
+    THEN a.account_data->'content'->'global'
+    ELSE '{}'::jsonb
+  END) k,
+  jsonb_array_elements(CASE
+    WHEN jsonb_typeof(k.value) = 'array' THEN k.value
+    ELSE '[]'::jsonb
+  END) WITH ORDINALITY r(value, position)
+WHERE a.room_id = '' AND a.event_type = 'm.push_rules'
+  AND k.key IN ('override', 'content', 'room', 'sender', 'underride')
+  AND jsonb_typeof(r.value->'rule_id') = 'string'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,25 @@
+-- Index over the aliases of the m.room.canonical_alias state events
+CREATE TABLE statestore_aliases (
+  alias TEXT NOT NULL,
+  room_id TEXT NOT NULL,
+  is_canonical BOOLEAN NOT NULL,
+  PRIMARY KEY (alias, room_id)
+);
+CREATE INDEX statestore_aliases_room ON statestore_aliases (room_id);
+INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+SELECT state_event->'content'->>'alias', room_id, TRUE
+FROM statestore_state
+WHERE event_type = 'm.room.canonical_alias' AND state_key = '' AND is_partial = '0'
+  AND jsonb_typeof(state_event->'content'->'alias') = 'string'
+ON CONFLICT DO NOTHING;
+INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+SELECT a.value #>> '{}', s.room_id, FALSE
+FROM statestore_state s,
+  jsonb_array_elements(CASE
+    WHEN jsonb_typeof(s.state_event->'content'->'alt_aliases') = 'array'
+    THEN s.state_event->'content'->'alt_aliases'
+    ELSE '[]'::jsonb
+  END) a
+WHERE s.event_type = 'm.room.canonical_alias' AND s.state_key = '' AND s.is_partial = '0'
+  AND jsonb_typeof(a.value) = 'string'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,3 @@
+DROP FUNCTION IF EXISTS statestore_audit_log_row() CASCADE;
+DROP INDEX statestore_audit_log_changed_at;
+DROP TABLE statestore_audit_log;
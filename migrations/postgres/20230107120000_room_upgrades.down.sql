@@ -0,0 +1,2 @@
+DROP INDEX statestore_room_upgrades_new;
+DROP TABLE statestore_room_upgrades;
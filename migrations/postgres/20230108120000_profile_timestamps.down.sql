@@ -0,0 +1 @@
+ALTER TABLE statestore_profiles DROP COLUMN origin_server_ts;
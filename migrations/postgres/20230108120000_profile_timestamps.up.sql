@@ -0,0 +1,17 @@
+-- The global profile is taken from the member event with the latest origin_server_ts
+ALTER TABLE statestore_profiles ADD COLUMN origin_server_ts BIGINT NOT NULL DEFAULT 0;
+UPDATE statestore_profiles SET
+  displayname = latest.displayname,
+  avatar_url = latest.avatar_url,
+  origin_server_ts = latest.origin_server_ts
+FROM (
+  SELECT DISTINCT ON (user_id)
+    user_id,
+    user_profile->'content'->>'displayname' AS displayname,
+    user_profile->'content'->>'avatar_url' AS avatar_url,
+    (member_event->>'origin_server_ts')::BIGINT AS origin_server_ts
+  FROM statestore_members
+  WHERE user_profile IS NOT NULL AND member_event->>'origin_server_ts' IS NOT NULL
+  ORDER BY user_id, (member_event->>'origin_server_ts')::BIGINT DESC
+) AS latest
+WHERE statestore_profiles.user_id = latest.user_id;
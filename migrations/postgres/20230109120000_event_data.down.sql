@@ -0,0 +1,10 @@
+-- Events that are not stored as JSON cannot be converted in SQL, so they are dropped
+DELETE FROM statestore_state WHERE state_event IS NULL;
+ALTER TABLE statestore_state DROP COLUMN state_event_data;
+ALTER TABLE statestore_state ALTER COLUMN state_event SET NOT NULL;
+DELETE FROM statestore_state_tombstone WHERE state_event IS NULL;
+ALTER TABLE statestore_state_tombstone DROP COLUMN state_event_data;
+DELETE FROM statestore_members WHERE member_event_data IS NOT NULL;
+ALTER TABLE statestore_members DROP COLUMN member_event_data;
+DELETE FROM statestore_members_tombstone WHERE member_event_data IS NOT NULL;
+ALTER TABLE statestore_members_tombstone DROP COLUMN member_event_data;
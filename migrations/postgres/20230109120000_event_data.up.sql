@@ -0,0 +1,7 @@
+-- Events serialized with another format than JSON, see StateStore::set_serializer. The JSON
+-- column of these events is NULL.
+ALTER TABLE statestore_state ALTER COLUMN state_event DROP NOT NULL;
+ALTER TABLE statestore_state ADD COLUMN state_event_data BYTEA;
+ALTER TABLE statestore_state_tombstone ADD COLUMN state_event_data BYTEA;
+ALTER TABLE statestore_members ADD COLUMN member_event_data BYTEA;
+ALTER TABLE statestore_members_tombstone ADD COLUMN member_event_data BYTEA;
@@ -0,0 +1,3 @@
+-- Indexes matching the order of the room info pages, see StateStore::get_room_infos_paged
+CREATE INDEX statestore_rooms_name_page ON statestore_rooms (is_partial, room_name, room_id);
+CREATE INDEX statestore_rooms_activity_page ON statestore_rooms (is_partial, latest_event_ts DESC NULLS LAST, room_id);
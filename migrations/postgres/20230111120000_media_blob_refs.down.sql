@@ -0,0 +1 @@
+ALTER TABLE statestore_media_blobs DROP COLUMN ref_count;
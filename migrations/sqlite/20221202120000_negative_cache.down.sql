@@ -0,0 +1,2 @@
+DROP INDEX statestore_negative_cache_expires_at;
+DROP TABLE statestore_negative_cache;
@@ -0,0 +1,6 @@
+-- Cache of resources (media, profiles) that are known to not exist
+CREATE TABLE statestore_negative_cache (
+  cache_key TEXT PRIMARY KEY NOT NULL,
+  expires_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
+CREATE INDEX statestore_negative_cache_expires_at ON statestore_negative_cache (expires_at);
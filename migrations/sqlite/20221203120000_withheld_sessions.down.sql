@@ -0,0 +1 @@
+DROP TABLE cryptostore_withheld_sessions;
@@ -0,0 +1,6 @@
+CREATE TABLE cryptostore_withheld_sessions (
+    room_id BLOB NOT NULL,
+    session_id BLOB NOT NULL,
+    withheld_data BLOB NOT NULL,
+    PRIMARY KEY (room_id, session_id)
+);
@@ -0,0 +1,6 @@
+CREATE TABLE cryptostore_secrets (
+    secret_id INTEGER PRIMARY KEY,
+    secret_name BLOB NOT NULL,
+    secret_data BLOB NOT NULL
+);
+CREATE INDEX cryptostore_secrets_secret_name_idx ON cryptostore_secrets (secret_name);
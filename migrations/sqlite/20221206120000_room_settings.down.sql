@@ -0,0 +1 @@
+DROP TABLE cryptostore_room_settings;
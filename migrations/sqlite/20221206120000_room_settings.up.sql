@@ -0,0 +1,4 @@
+CREATE TABLE cryptostore_room_settings (
+    room_id BLOB PRIMARY KEY NOT NULL,
+    settings_data BLOB NOT NULL
+);
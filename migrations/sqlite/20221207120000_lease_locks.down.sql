@@ -0,0 +1 @@
+DROP TABLE cryptostore_lease_locks;
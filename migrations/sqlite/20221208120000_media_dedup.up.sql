@@ -0,0 +1,8 @@
+-- Media contents shared between mxc URLs, keyed by their SHA-256 hash
+CREATE TABLE statestore_media_blobs (
+  content_hash BLOB PRIMARY KEY NOT NULL,
+  media_data BLOB NOT NULL
+);
+ALTER TABLE statestore_media
+ADD COLUMN content_hash BLOB;
+CREATE INDEX statestore_media_content_hash ON statestore_media (content_hash);
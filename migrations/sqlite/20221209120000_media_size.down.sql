@@ -0,0 +1 @@
+ALTER TABLE statestore_media DROP COLUMN media_size;
@@ -0,0 +1,8 @@
+-- Size of the media contents in bytes, used for enforcing the media size budget
+ALTER TABLE statestore_media
+ADD COLUMN media_size INTEGER NOT NULL DEFAULT 0;
+UPDATE statestore_media
+SET media_size = COALESCE(
+  (SELECT LENGTH(media_data) FROM statestore_media_blobs WHERE statestore_media_blobs.content_hash = statestore_media.content_hash),
+  LENGTH(media_data)
+);
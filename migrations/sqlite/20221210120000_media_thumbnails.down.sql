@@ -0,0 +1,16 @@
+CREATE TABLE statestore_media_old (
+  media_url TEXT PRIMARY KEY NOT NULL,
+  media_data BLOB NOT NULL,
+  last_access TIMESTAMP WITH TIME ZONE NOT NULL, -- Because this table is an LRU cache
+  content_hash BLOB,
+  media_size INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO statestore_media_old (media_url, media_data, last_access, content_hash, media_size)
+SELECT media_url, media_data, last_access, content_hash, media_size FROM statestore_media
+WHERE thumbnail_width = 0 AND thumbnail_height = 0 AND thumbnail_method = '';
+DROP INDEX statestore_media_last_access;
+DROP INDEX statestore_media_content_hash;
+DROP TABLE statestore_media;
+ALTER TABLE statestore_media_old RENAME TO statestore_media;
+CREATE INDEX statestore_media_last_access ON statestore_media (last_access);
+CREATE INDEX statestore_media_content_hash ON statestore_media (content_hash);
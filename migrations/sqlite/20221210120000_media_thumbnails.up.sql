@@ -0,0 +1,22 @@
+-- Thumbnails are stored as separate entries of the media they belong to.
+-- The original file has a width and height of 0 and an empty method.
+-- SQLite cannot change the primary key of a table, so the table is rebuilt.
+CREATE TABLE statestore_media_new (
+  media_url TEXT NOT NULL,
+  thumbnail_width INTEGER NOT NULL DEFAULT 0,
+  thumbnail_height INTEGER NOT NULL DEFAULT 0,
+  thumbnail_method TEXT NOT NULL DEFAULT '',
+  media_data BLOB NOT NULL,
+  last_access TIMESTAMP WITH TIME ZONE NOT NULL, -- Because this table is an LRU cache
+  content_hash BLOB,
+  media_size INTEGER NOT NULL DEFAULT 0,
+  PRIMARY KEY (media_url, thumbnail_width, thumbnail_height, thumbnail_method)
+);
+INSERT INTO statestore_media_new (media_url, media_data, last_access, content_hash, media_size)
+SELECT media_url, media_data, last_access, content_hash, media_size FROM statestore_media;
+DROP INDEX statestore_media_last_access;
+DROP INDEX statestore_media_content_hash;
+DROP TABLE statestore_media;
+ALTER TABLE statestore_media_new RENAME TO statestore_media;
+CREATE INDEX statestore_media_last_access ON statestore_media (last_access);
+CREATE INDEX statestore_media_content_hash ON statestore_media (content_hash);
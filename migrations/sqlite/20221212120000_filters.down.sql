@@ -0,0 +1,6 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT CAST('filter:' || filter_name AS BLOB), CAST(filter_id AS BLOB)
+FROM statestore_filters
+WHERE true
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE statestore_filters;
@@ -0,0 +1,13 @@
+-- Sync filter ids by name, previously stored in statestore_kv
+CREATE TABLE statestore_filters (
+  filter_name TEXT PRIMARY KEY NOT NULL,
+  filter_id TEXT NOT NULL,
+  updated_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
+INSERT INTO statestore_filters (filter_name, filter_id, updated_at)
+SELECT CAST(substr(kv_key, 8) AS TEXT), CAST(kv_value AS TEXT),
+  CAST(strftime('%s', 'now') AS INTEGER)
+FROM statestore_kv
+WHERE substr(kv_key, 1, 7) = CAST('filter:' AS BLOB);
+DELETE FROM statestore_kv
+WHERE substr(kv_key, 1, 7) = CAST('filter:' AS BLOB);
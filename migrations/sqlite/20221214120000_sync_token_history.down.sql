@@ -0,0 +1,5 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT CAST('sync_token' AS BLOB), CAST(sync_token AS BLOB) FROM statestore_sync_tokens
+WHERE id = (SELECT MAX(id) FROM statestore_sync_tokens)
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE statestore_sync_tokens;
@@ -0,0 +1,9 @@
+-- The most recent sync tokens, newest last
+CREATE TABLE statestore_sync_tokens (
+  id INTEGER PRIMARY KEY,
+  sync_token TEXT NOT NULL,
+  created_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
+INSERT INTO statestore_sync_tokens (sync_token, created_at)
+SELECT CAST(kv_value AS TEXT), 0 FROM statestore_kv WHERE kv_key = CAST('sync_token' AS BLOB);
+DELETE FROM statestore_kv WHERE kv_key = CAST('sync_token' AS BLOB);
@@ -0,0 +1,6 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT CAST('custom:' || custom_key AS BLOB), custom_value
+FROM statestore_custom_values
+WHERE true
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE statestore_custom_values;
@@ -0,0 +1,11 @@
+-- Custom values of the application, previously stored in statestore_kv
+CREATE TABLE statestore_custom_values (
+  custom_key BLOB PRIMARY KEY NOT NULL,
+  custom_value BLOB NOT NULL
+);
+INSERT INTO statestore_custom_values (custom_key, custom_value)
+SELECT substr(kv_key, 8), kv_value
+FROM statestore_kv
+WHERE substr(kv_key, 1, 7) = CAST('custom:' AS BLOB);
+DELETE FROM statestore_kv
+WHERE substr(kv_key, 1, 7) = CAST('custom:' AS BLOB);
@@ -0,0 +1,16 @@
+-- Latest known profile of every user, independent of rooms
+CREATE TABLE statestore_profiles (
+  user_id TEXT PRIMARY KEY NOT NULL,
+  displayname TEXT,
+  avatar_url TEXT,
+  updated_at INTEGER NOT NULL
+);
+INSERT INTO statestore_profiles (user_id, displayname, avatar_url, updated_at)
+SELECT
+  user_id,
+  json_extract(user_profile, '$.content.displayname'),
+  json_extract(user_profile, '$.content.avatar_url'),
+  0
+FROM statestore_members
+WHERE user_profile IS NOT NULL
+GROUP BY user_id;
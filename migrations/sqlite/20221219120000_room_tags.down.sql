@@ -0,0 +1,3 @@
+DROP TABLE statestore_direct_chats;
+DROP INDEX statestore_room_tags_tag;
+DROP TABLE statestore_room_tags;
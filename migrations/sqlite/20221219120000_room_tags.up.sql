@@ -0,0 +1,31 @@
+-- Index over the m.tag room account data
+CREATE TABLE statestore_room_tags (
+  room_id TEXT NOT NULL,
+  tag TEXT NOT NULL,
+  tag_order REAL,
+  PRIMARY KEY (room_id, tag)
+);
+CREATE INDEX statestore_room_tags_tag ON statestore_room_tags (tag);
+-- Index over the m.direct global account data
+CREATE TABLE statestore_direct_chats (
+  user_id TEXT NOT NULL,
+  room_id TEXT NOT NULL,
+  PRIMARY KEY (user_id, room_id)
+);
+INSERT INTO statestore_room_tags (room_id, tag, tag_order)
+SELECT
+  a.room_id,
+  t.key,
+  CASE WHEN t.type = 'object' THEN
+    CASE WHEN json_type(t.value, '$.order') IN ('integer', 'real')
+    THEN json_extract(t.value, '$.order') END
+  END
+FROM statestore_accountdata a, json_each(a.account_data, '$.content.tags') t
+WHERE a.event_type = 'm.tag' AND json_type(a.account_data, '$.content.tags') = 'object';
+INSERT OR IGNORE INTO statestore_direct_chats (user_id, room_id)
+SELECT u.key, r.value
+FROM statestore_accountdata a,
+  json_each(a.account_data, '$.content') u,
+  json_each(CASE WHEN u.type = 'array' THEN u.value ELSE '[]' END) r
+WHERE a.room_id = '' AND a.event_type = 'm.direct'
+  AND json_type(a.account_data, '$.content') = 'object' AND r.type = 'text';
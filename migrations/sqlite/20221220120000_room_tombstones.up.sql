@@ -0,0 +1,22 @@
+-- Rooms removed with soft deletion enabled, see StateStore::set_room_retention
+CREATE TABLE statestore_room_tombstones (
+  room_id TEXT PRIMARY KEY NOT NULL,
+  removed_at INTEGER NOT NULL
+);
+CREATE INDEX statestore_room_tombstones_removed_at ON statestore_room_tombstones (removed_at);
+-- Copies of the removed rows. Column changes of the original tables need to be applied here as
+-- well, as rows are moved with SELECT *.
+CREATE TABLE statestore_rooms_tombstone AS SELECT * FROM statestore_rooms WHERE false;
+CREATE INDEX statestore_rooms_tombstone_room ON statestore_rooms_tombstone (room_id);
+CREATE TABLE statestore_accountdata_tombstone AS SELECT * FROM statestore_accountdata WHERE false;
+CREATE INDEX statestore_accountdata_tombstone_room ON statestore_accountdata_tombstone (room_id);
+CREATE TABLE statestore_members_tombstone AS SELECT * FROM statestore_members WHERE false;
+CREATE INDEX statestore_members_tombstone_room ON statestore_members_tombstone (room_id);
+CREATE TABLE statestore_display_names_tombstone AS SELECT * FROM statestore_display_names WHERE false;
+CREATE INDEX statestore_display_names_tombstone_room ON statestore_display_names_tombstone (room_id);
+CREATE TABLE statestore_state_tombstone AS SELECT * FROM statestore_state WHERE false;
+CREATE INDEX statestore_state_tombstone_room ON statestore_state_tombstone (room_id);
+CREATE TABLE statestore_receipts_tombstone AS SELECT * FROM statestore_receipts WHERE false;
+CREATE INDEX statestore_receipts_tombstone_room ON statestore_receipts_tombstone (room_id);
+CREATE TABLE statestore_room_tags_tombstone AS SELECT * FROM statestore_room_tags WHERE false;
+CREATE INDEX statestore_room_tags_tombstone_room ON statestore_room_tags_tombstone (room_id);
@@ -0,0 +1,17 @@
+-- Edges of the space hierarchy from m.space.child and m.space.parent state events
+CREATE TABLE statestore_space_edges (
+  parent_id TEXT NOT NULL,
+  child_id TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  PRIMARY KEY (parent_id, child_id, event_type)
+);
+CREATE INDEX statestore_space_edges_child ON statestore_space_edges (child_id);
+INSERT INTO statestore_space_edges (parent_id, child_id, event_type)
+SELECT
+  CASE WHEN event_type = 'm.space.child' THEN room_id ELSE state_key END,
+  CASE WHEN event_type = 'm.space.child' THEN state_key ELSE room_id END,
+  event_type
+FROM statestore_state
+WHERE event_type IN ('m.space.child', 'm.space.parent')
+  AND is_partial = '0'
+  AND json_array_length(state_event, '$.content.via') > 0;
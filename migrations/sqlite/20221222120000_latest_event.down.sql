@@ -0,0 +1,4 @@
+ALTER TABLE statestore_rooms_tombstone DROP COLUMN latest_event_ts;
+ALTER TABLE statestore_rooms_tombstone DROP COLUMN latest_event;
+ALTER TABLE statestore_rooms DROP COLUMN latest_event_ts;
+ALTER TABLE statestore_rooms DROP COLUMN latest_event;
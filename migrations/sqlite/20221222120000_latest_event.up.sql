@@ -0,0 +1,9 @@
+-- Latest event of a room suitable for a preview in the room list
+ALTER TABLE statestore_rooms
+ADD COLUMN latest_event JSON;
+ALTER TABLE statestore_rooms
+ADD COLUMN latest_event_ts INTEGER;
+ALTER TABLE statestore_rooms_tombstone
+ADD COLUMN latest_event JSON;
+ALTER TABLE statestore_rooms_tombstone
+ADD COLUMN latest_event_ts INTEGER;
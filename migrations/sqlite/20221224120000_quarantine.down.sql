@@ -0,0 +1 @@
+DROP TABLE statestore_quarantine;
@@ -0,0 +1,4 @@
+INSERT INTO statestore_kv (kv_key, kv_value)
+SELECT CAST('e2e_account' AS BLOB), account_data FROM cryptostore_account
+ON CONFLICT (kv_key) DO NOTHING;
+DROP TABLE cryptostore_account;
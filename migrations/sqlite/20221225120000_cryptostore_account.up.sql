@@ -0,0 +1,14 @@
+-- The own olm account, with its state kept in plain columns for inspection
+CREATE TABLE cryptostore_account (
+  id INTEGER PRIMARY KEY NOT NULL, -- Always 0
+  account_data BLOB NOT NULL,
+  shared BOOLEAN NOT NULL,
+  key_counts TEXT, -- JSON of the key counts last reported by the homeserver
+  updated_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
+-- The account is encrypted, its state columns are filled in when the store is unlocked
+INSERT INTO cryptostore_account (id, account_data, shared, updated_at)
+SELECT 0, kv_value, FALSE, 0 FROM statestore_kv WHERE kv_key = CAST('e2e_account' AS BLOB);
+DELETE FROM statestore_kv WHERE kv_key = CAST('e2e_account' AS BLOB);
+-- The key counts were stored encrypted, they are reported again with the next sync response
+DELETE FROM statestore_kv WHERE kv_key = CAST('e2e_key_counts' AS BLOB);
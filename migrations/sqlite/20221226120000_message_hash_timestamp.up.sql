@@ -0,0 +1,6 @@
+-- Message hashes are pruned once they are older than the configured maximum age. Existing
+-- hashes are treated as if they had been stored during the migration.
+ALTER TABLE cryptostore_message_hash
+ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0; -- Unix timestamp in seconds
+UPDATE cryptostore_message_hash SET created_at = CAST(strftime('%s', 'now') AS INTEGER);
+CREATE INDEX cryptostore_message_hash_created_at_idx ON cryptostore_message_hash (created_at);
@@ -0,0 +1 @@
+DROP INDEX cryptostore_gossip_request_unsent_idx;
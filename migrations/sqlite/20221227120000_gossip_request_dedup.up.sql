@@ -0,0 +1,12 @@
+-- At most one unsent key request per recipient and requested secret. Existing duplicates are
+-- dropped, keeping the one with the smallest request ID.
+DELETE FROM cryptostore_gossip_request
+WHERE NOT sent_out AND EXISTS (
+  SELECT 1 FROM cryptostore_gossip_request other
+  WHERE NOT other.sent_out
+    AND other.recipient_id = cryptostore_gossip_request.recipient_id
+    AND other.info_key = cryptostore_gossip_request.info_key
+    AND other.request_id < cryptostore_gossip_request.request_id
+);
+CREATE UNIQUE INDEX cryptostore_gossip_request_unsent_idx
+ON cryptostore_gossip_request (recipient_id, info_key) WHERE NOT sent_out;
@@ -0,0 +1,5 @@
+-- The dehydrated device of the account, if one has been created
+CREATE TABLE cryptostore_dehydrated_device (
+  id INTEGER PRIMARY KEY NOT NULL, -- Always 0
+  device_data BLOB NOT NULL
+);
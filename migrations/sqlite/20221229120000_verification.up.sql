@@ -0,0 +1,6 @@
+-- Verification requests that are still in progress
+CREATE TABLE cryptostore_verification (
+  flow_id BLOB PRIMARY KEY NOT NULL,
+  verification_data BLOB NOT NULL,
+  updated_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
@@ -0,0 +1,20 @@
+-- Window state of sliding sync connections
+CREATE TABLE statestore_sliding_sync (
+  conn_id TEXT PRIMARY KEY NOT NULL,
+  pos TEXT,
+  updated_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
+CREATE TABLE statestore_sliding_sync_lists (
+  conn_id TEXT NOT NULL,
+  list_name TEXT NOT NULL,
+  ranges TEXT NOT NULL, -- JSON array of [start, end] pairs
+  room_count INTEGER,
+  room_list TEXT NOT NULL, -- JSON array of room IDs or nulls
+  PRIMARY KEY (conn_id, list_name)
+);
+CREATE TABLE statestore_sliding_sync_subscriptions (
+  conn_id TEXT NOT NULL,
+  room_id TEXT NOT NULL,
+  subscription TEXT NOT NULL, -- JSON room subscription
+  PRIMARY KEY (conn_id, room_id)
+);
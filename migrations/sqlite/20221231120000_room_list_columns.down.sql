@@ -0,0 +1,8 @@
+DROP INDEX statestore_rooms_activity;
+DROP INDEX statestore_rooms_name;
+ALTER TABLE statestore_rooms_tombstone DROP COLUMN room_state;
+ALTER TABLE statestore_rooms_tombstone DROP COLUMN is_encrypted;
+ALTER TABLE statestore_rooms_tombstone DROP COLUMN room_name;
+ALTER TABLE statestore_rooms DROP COLUMN room_state;
+ALTER TABLE statestore_rooms DROP COLUMN is_encrypted;
+ALTER TABLE statestore_rooms DROP COLUMN room_name;
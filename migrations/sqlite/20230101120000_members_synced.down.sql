@@ -0,0 +1,2 @@
+ALTER TABLE statestore_rooms_tombstone DROP COLUMN members_synced;
+ALTER TABLE statestore_rooms DROP COLUMN members_synced;
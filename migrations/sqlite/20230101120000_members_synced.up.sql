@@ -0,0 +1,7 @@
+-- Whether the full member list of a room has been loaded, as opposed to a lazy-loaded subset
+ALTER TABLE statestore_rooms
+ADD COLUMN members_synced BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE statestore_rooms_tombstone
+ADD COLUMN members_synced BOOLEAN NOT NULL DEFAULT 0;
+UPDATE statestore_rooms
+SET members_synced = COALESCE(json_extract(room_info, '$.members_synced'), 0);
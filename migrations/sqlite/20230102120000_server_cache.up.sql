@@ -0,0 +1,6 @@
+-- Cached /capabilities and .well-known responses of the homeserver
+CREATE TABLE statestore_server_cache (
+  cache_key TEXT PRIMARY KEY NOT NULL,
+  response TEXT NOT NULL, -- JSON response body
+  expires_at INTEGER NOT NULL -- Unix timestamp in seconds
+);
@@ -0,0 +1,9 @@
+-- Index over the m.ignored_user_list global account data
+CREATE TABLE statestore_ignored_users (
+  user_id TEXT PRIMARY KEY NOT NULL
+);
+INSERT OR IGNORE INTO statestore_ignored_users (user_id)
+SELECT u.key
+FROM statestore_accountdata a, json_each(a.account_data, '$.content.ignored_users') u
+WHERE a.room_id = '' AND a.event_type = 'm.ignored_user_list'
+  AND json_type(a.account_data, '$.content.ignored_users') = 'object';
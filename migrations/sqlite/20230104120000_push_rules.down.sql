@@ -0,0 +1,2 @@
+DROP INDEX statestore_push_rules_position;
+DROP TABLE statestore_push_rules;
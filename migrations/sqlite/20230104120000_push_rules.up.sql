@@ -0,0 +1,26 @@
+-- Normalized copy of the global rules of the m.push_rules account data
+CREATE TABLE statestore_push_rules (
+  kind TEXT NOT NULL,
+  rule_id TEXT NOT NULL,
+  rule_position INTEGER NOT NULL,
+  enabled BOOLEAN NOT NULL,
+  is_default BOOLEAN NOT NULL,
+  rule TEXT NOT NULL,
+  PRIMARY KEY (kind, rule_id)
+);
+CREATE INDEX statestore_push_rules_position ON statestore_push_rules (kind, rule_position);
+INSERT OR IGNORE INTO statestore_push_rules (kind, rule_id, rule_position, enabled, is_default, rule)
+SELECT
+  k.key,
+  json_extract(r.value, '$.rule_id'),
+  r.key,
+  json_type(r.value, '$.enabled') = 'true',
+  json_type(r.value, '$.default') = 'true',
+  r.value
+FROM statestore_accountdata a,
+  json_each(a.account_data, '$.content.global') k,
+  json_each(CASE WHEN k.type = 'array' THEN k.value ELSE '[]' END) r
+WHERE a.room_id = '' AND a.event_type = 'm.push_rules'
+  AND json_type(a.account_data, '$.content.global') = 'object'
+  AND k.key IN ('override', 'content', 'room', 'sender', 'underride')
+  AND r.type = 'object' AND json_type(r.value, '$.rule_id') = 'text';
@@ -0,0 +1,2 @@
+DROP INDEX statestore_aliases_room;
+DROP TABLE statestore_aliases;
@@ -0,0 +1,18 @@
+-- Index over the aliases of the m.room.canonical_alias state events
+CREATE TABLE statestore_aliases (
+  alias TEXT NOT NULL,
+  room_id TEXT NOT NULL,
+  is_canonical BOOLEAN NOT NULL,
+  PRIMARY KEY (alias, room_id)
+);
+CREATE INDEX statestore_aliases_room ON statestore_aliases (room_id);
+INSERT OR IGNORE INTO statestore_aliases (alias, room_id, is_canonical)
+SELECT json_extract(state_event, '$.content.alias'), room_id, 1
+FROM statestore_state
+WHERE event_type = 'm.room.canonical_alias' AND state_key = '' AND is_partial = '0'
+  AND json_type(state_event, '$.content.alias') = 'text';
+INSERT OR IGNORE INTO statestore_aliases (alias, room_id, is_canonical)
+SELECT a.value, s.room_id, 0
+FROM statestore_state s, json_each(s.state_event, '$.content.alt_aliases') a
+WHERE s.event_type = 'm.room.canonical_alias' AND s.state_key = '' AND s.is_partial = '0'
+  AND json_type(s.state_event, '$.content.alt_aliases') = 'array' AND a.type = 'text';
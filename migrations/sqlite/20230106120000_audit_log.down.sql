@@ -0,0 +1,3 @@
+-- The audit triggers have to be removed with StateStore::disable_audit_log first
+DROP INDEX statestore_audit_log_changed_at;
+DROP TABLE statestore_audit_log;
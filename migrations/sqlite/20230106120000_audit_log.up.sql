@@ -0,0 +1,11 @@
+-- Mutations of the state store tables, written by the triggers of StateStore::enable_audit_log
+CREATE TABLE statestore_audit_log (
+  id INTEGER PRIMARY KEY,
+  table_name TEXT NOT NULL,
+  operation TEXT NOT NULL,
+  row_key TEXT,
+  changed_at INTEGER NOT NULL,
+  old_hash TEXT,
+  new_hash TEXT
+);
+CREATE INDEX statestore_audit_log_changed_at ON statestore_audit_log (changed_at);
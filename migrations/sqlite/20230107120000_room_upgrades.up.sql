@@ -0,0 +1,22 @@
+-- Room upgrades from m.room.tombstone and m.room.create state events
+CREATE TABLE statestore_room_upgrades (
+  old_room_id TEXT NOT NULL,
+  new_room_id TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  PRIMARY KEY (old_room_id, new_room_id, event_type)
+);
+CREATE INDEX statestore_room_upgrades_new ON statestore_room_upgrades (new_room_id);
+INSERT OR IGNORE INTO statestore_room_upgrades (old_room_id, new_room_id, event_type)
+SELECT
+  CASE WHEN event_type = 'm.room.tombstone' THEN room_id ELSE json_extract(state_event, '$.content.predecessor.room_id') END,
+  CASE WHEN event_type = 'm.room.tombstone' THEN json_extract(state_event, '$.content.replacement_room') ELSE room_id END,
+  event_type
+FROM statestore_state
+WHERE state_key = '' AND is_partial = '0'
+  AND CASE
+    WHEN event_type = 'm.room.tombstone'
+    THEN json_type(state_event, '$.content.replacement_room') = 'text'
+    WHEN event_type = 'm.room.create'
+    THEN json_type(state_event, '$.content.predecessor.room_id') = 'text'
+    ELSE 0
+  END;
@@ -0,0 +1,20 @@
+-- The global profile is taken from the member event with the latest origin_server_ts
+ALTER TABLE statestore_profiles ADD COLUMN origin_server_ts INTEGER NOT NULL DEFAULT 0;
+UPDATE statestore_profiles SET (displayname, avatar_url, origin_server_ts) = (
+  SELECT
+    json_extract(user_profile, '$.content.displayname'),
+    json_extract(user_profile, '$.content.avatar_url'),
+    json_extract(member_event, '$.origin_server_ts')
+  FROM statestore_members
+  WHERE statestore_members.user_id = statestore_profiles.user_id
+    AND user_profile IS NOT NULL
+    AND json_extract(member_event, '$.origin_server_ts') IS NOT NULL
+  ORDER BY json_extract(member_event, '$.origin_server_ts') DESC
+  LIMIT 1
+)
+WHERE EXISTS (
+  SELECT 1 FROM statestore_members
+  WHERE statestore_members.user_id = statestore_profiles.user_id
+    AND user_profile IS NOT NULL
+    AND json_extract(member_event, '$.origin_server_ts') IS NOT NULL
+);
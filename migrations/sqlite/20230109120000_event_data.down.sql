@@ -0,0 +1,21 @@
+-- Events that are not stored as JSON cannot be converted in SQL, so they are dropped
+CREATE TABLE statestore_state_old (
+  room_id TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  state_key TEXT NOT NULL,
+  is_partial BOOLEAN NOT NULL,
+  state_event JSON NOT NULL,
+  event_id TEXT,
+  PRIMARY KEY (room_id, event_type, state_key)
+);
+INSERT INTO statestore_state_old (room_id, event_type, state_key, is_partial, state_event, event_id)
+SELECT room_id, event_type, state_key, is_partial, state_event, event_id FROM statestore_state
+WHERE state_event IS NOT NULL;
+DROP TABLE statestore_state;
+ALTER TABLE statestore_state_old RENAME TO statestore_state;
+DELETE FROM statestore_state_tombstone WHERE state_event IS NULL;
+ALTER TABLE statestore_state_tombstone DROP COLUMN state_event_data;
+DELETE FROM statestore_members WHERE member_event_data IS NOT NULL;
+ALTER TABLE statestore_members DROP COLUMN member_event_data;
+DELETE FROM statestore_members_tombstone WHERE member_event_data IS NOT NULL;
+ALTER TABLE statestore_members_tombstone DROP COLUMN member_event_data;
@@ -0,0 +1,20 @@
+-- Events serialized with another format than JSON, see StateStore::set_serializer. The JSON
+-- column of these events is NULL. SQLite cannot drop the NOT NULL constraint of a column, so
+-- the state table is rebuilt.
+CREATE TABLE statestore_state_new (
+  room_id TEXT NOT NULL,
+  event_type TEXT NOT NULL,
+  state_key TEXT NOT NULL,
+  is_partial BOOLEAN NOT NULL,
+  state_event JSON,
+  event_id TEXT,
+  state_event_data BLOB,
+  PRIMARY KEY (room_id, event_type, state_key)
+);
+INSERT INTO statestore_state_new (room_id, event_type, state_key, is_partial, state_event, event_id)
+SELECT room_id, event_type, state_key, is_partial, state_event, event_id FROM statestore_state;
+DROP TABLE statestore_state;
+ALTER TABLE statestore_state_new RENAME TO statestore_state;
+ALTER TABLE statestore_state_tombstone ADD COLUMN state_event_data BLOB;
+ALTER TABLE statestore_members ADD COLUMN member_event_data BLOB;
+ALTER TABLE statestore_members_tombstone ADD COLUMN member_event_data BLOB;
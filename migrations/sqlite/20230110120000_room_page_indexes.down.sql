@@ -0,0 +1,2 @@
+DROP INDEX statestore_rooms_activity_page;
+DROP INDEX statestore_rooms_name_page;
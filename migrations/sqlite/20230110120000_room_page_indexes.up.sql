@@ -0,0 +1,5 @@
+-- Indexes matching the order of the room info pages, see StateStore::get_room_infos_paged.
+-- SQLite sorts NULL first and cannot index NULLS LAST, so rooms without a value are moved to
+-- the end with an expression.
+CREATE INDEX statestore_rooms_name_page ON statestore_rooms (is_partial, room_name IS NULL, room_name, room_id);
+CREATE INDEX statestore_rooms_activity_page ON statestore_rooms (is_partial, latest_event_ts IS NULL, latest_event_ts DESC, room_id);
@@ -0,0 +1,6 @@
+-- Number of media rows referencing a shared media content, the content is deleted at zero
+ALTER TABLE statestore_media_blobs
+ADD COLUMN ref_count INTEGER NOT NULL DEFAULT 0;
+UPDATE statestore_media_blobs
+SET ref_count = (SELECT COUNT(*) FROM statestore_media WHERE statestore_media.content_hash = statestore_media_blobs.content_hash);
+DELETE FROM statestore_media_blobs WHERE ref_count = 0;
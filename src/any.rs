@@ -0,0 +1,311 @@
+//! Runtime selection of the database backend
+
+use std::{collections::BTreeSet, str::FromStr, sync::Arc};
+
+use crate::{Result, SQLStoreError, StateStore};
+use async_trait::async_trait;
+use matrix_sdk_base::{
+    deserialized_responses::RawMemberEvent, media::MediaRequest, store::StoreConfig,
+    MinimalRoomMemberEvent, RoomInfo, StateChanges, StateStore as BaseStateStore, StoreError,
+};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::{Receipt, ReceiptType},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnySyncStateEvent,
+        GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType,
+    },
+    serde::Raw,
+    EventId, MxcUri, OwnedEventId, OwnedUserId, RoomId, UserId,
+};
+
+/// Shorthand for the store error type
+type StoreResult<T> = Result<T, StoreError>;
+
+/// State store over any of the enabled database backends
+///
+/// Use this if the database is only known at runtime, for example because it is read from a
+/// configuration file. The store is selected from the scheme of the database URL.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AnyStateStore {
+    /// Store backed by a postgres database
+    #[cfg(feature = "postgres")]
+    Postgres(StateStore<sqlx::Postgres>),
+    /// Store backed by a sqlite database
+    #[cfg(feature = "sqlite")]
+    Sqlite(StateStore<sqlx::Sqlite>),
+}
+
+/// Calls the same expression on the store of every backend
+macro_rules! dispatch {
+    ($self:expr, $store:ident => $body:expr) => {
+        match $self {
+            #[cfg(feature = "postgres")]
+            AnyStateStore::Postgres($store) => $body,
+            #[cfg(feature = "sqlite")]
+            AnyStateStore::Sqlite($store) => $body,
+        }
+    };
+}
+
+/// Database backend named by a database URL
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Backend {
+    /// Postgres database
+    #[cfg(feature = "postgres")]
+    Postgres,
+    /// Sqlite database
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl Backend {
+    /// Determines the backend from the scheme of a database URL
+    ///
+    /// # Errors
+    /// This function will return an error if the scheme does not belong to an enabled backend
+    fn from_url(url: &str) -> Result<Self> {
+        let scheme = url.split_once(':').map_or(url, |(scheme, _)| scheme);
+        match scheme {
+            #[cfg(feature = "postgres")]
+            "postgres" | "postgresql" => Ok(Self::Postgres),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(SQLStoreError::UnsupportedDatabaseUrl(scheme.to_owned())),
+        }
+    }
+}
+
+impl AnyStateStore {
+    /// Connects to the database at the given URL and performs migrations
+    ///
+    /// `postgres://` and `postgresql://` URLs open a postgres store, `sqlite:` URLs open a
+    /// sqlite store. Sqlite database files are created if they do not exist.
+    ///
+    /// # Errors
+    /// This function will return an error if the URL does not belong to an enabled backend,
+    /// if the connection fails, or if the migration cannot be applied
+    pub async fn connect(url: &str) -> Result<Self> {
+        match Backend::from_url(url)? {
+            #[cfg(feature = "postgres")]
+            Backend::Postgres => {
+                let db = Arc::new(sqlx::PgPool::connect(url).await?);
+                Ok(Self::Postgres(StateStore::new(&db).await?))
+            }
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => {
+                let db = connect_sqlite(url).await?;
+                Ok(Self::Sqlite(StateStore::new(&db).await?))
+            }
+        }
+    }
+}
+
+/// Connects to a sqlite database, creating the database file if it does not exist
+///
+/// # Errors
+/// This function will return an error if the URL is invalid or the connection fails
+#[cfg(feature = "sqlite")]
+async fn connect_sqlite(url: &str) -> Result<Arc<sqlx::SqlitePool>> {
+    let options = sqlx::sqlite::SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+    Ok(Arc::new(sqlx::SqlitePool::connect_with(options).await?))
+}
+
+#[cfg(feature = "postgres")]
+impl From<StateStore<sqlx::Postgres>> for AnyStateStore {
+    fn from(store: StateStore<sqlx::Postgres>) -> Self {
+        Self::Postgres(store)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<StateStore<sqlx::Sqlite>> for AnyStateStore {
+    fn from(store: StateStore<sqlx::Sqlite>) -> Self {
+        Self::Sqlite(store)
+    }
+}
+
+/// Creates a new store config for the database at the given URL
+///
+/// This is the runtime counterpart of [`crate::store_config`], see [`AnyStateStore::connect`]
+/// for the accepted URLs.
+///
+/// # Errors
+/// This function will return an error if the URL does not belong to an enabled backend,
+/// if the connection fails, if the migration cannot be applied, or if the passphrase is
+/// incorrect
+pub async fn any_store_config(url: &str, passphrase: Option<&str>) -> Result<StoreConfig> {
+    match Backend::from_url(url)? {
+        #[cfg(feature = "postgres")]
+        Backend::Postgres => {
+            let db = Arc::new(sqlx::PgPool::connect(url).await?);
+            crate::store_config(&db, passphrase).await
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => {
+            let db = connect_sqlite(url).await?;
+            crate::store_config(&db, passphrase).await
+        }
+    }
+}
+
+#[async_trait]
+impl BaseStateStore for AnyStateStore {
+    async fn save_filter(&self, filter_name: &str, filter_id: &str) -> StoreResult<()> {
+        dispatch!(self, store => BaseStateStore::save_filter(store, filter_name, filter_id).await)
+    }
+
+    async fn save_changes(&self, changes: &StateChanges) -> StoreResult<()> {
+        dispatch!(self, store => BaseStateStore::save_changes(store, changes).await)
+    }
+
+    async fn get_filter(&self, filter_name: &str) -> StoreResult<Option<String>> {
+        dispatch!(self, store => BaseStateStore::get_filter(store, filter_name).await)
+    }
+
+    async fn get_sync_token(&self) -> StoreResult<Option<String>> {
+        dispatch!(self, store => BaseStateStore::get_sync_token(store).await)
+    }
+
+    async fn get_presence_event(
+        &self,
+        user_id: &UserId,
+    ) -> StoreResult<Option<Raw<PresenceEvent>>> {
+        dispatch!(self, store => BaseStateStore::get_presence_event(store, user_id).await)
+    }
+
+    async fn get_state_event(
+        &self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+        state_key: &str,
+    ) -> StoreResult<Option<Raw<AnySyncStateEvent>>> {
+        dispatch!(self, store => {
+            BaseStateStore::get_state_event(store, room_id, event_type, state_key).await
+        })
+    }
+
+    async fn get_state_events(
+        &self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+    ) -> StoreResult<Vec<Raw<AnySyncStateEvent>>> {
+        dispatch!(self, store => BaseStateStore::get_state_events(store, room_id, event_type).await)
+    }
+
+    async fn get_profile(
+        &self,
+        room_id: &RoomId,
+        user_id: &UserId,
+    ) -> StoreResult<Option<MinimalRoomMemberEvent>> {
+        dispatch!(self, store => BaseStateStore::get_profile(store, room_id, user_id).await)
+    }
+
+    async fn get_member_event(
+        &self,
+        room_id: &RoomId,
+        state_key: &UserId,
+    ) -> StoreResult<Option<RawMemberEvent>> {
+        dispatch!(self, store => BaseStateStore::get_member_event(store, room_id, state_key).await)
+    }
+
+    async fn get_user_ids(&self, room_id: &RoomId) -> StoreResult<Vec<OwnedUserId>> {
+        dispatch!(self, store => BaseStateStore::get_user_ids(store, room_id).await)
+    }
+
+    async fn get_invited_user_ids(&self, room_id: &RoomId) -> StoreResult<Vec<OwnedUserId>> {
+        dispatch!(self, store => BaseStateStore::get_invited_user_ids(store, room_id).await)
+    }
+
+    async fn get_joined_user_ids(&self, room_id: &RoomId) -> StoreResult<Vec<OwnedUserId>> {
+        dispatch!(self, store => BaseStateStore::get_joined_user_ids(store, room_id).await)
+    }
+
+    async fn get_room_infos(&self) -> StoreResult<Vec<RoomInfo>> {
+        dispatch!(self, store => BaseStateStore::get_room_infos(store).await)
+    }
+
+    async fn get_stripped_room_infos(&self) -> StoreResult<Vec<RoomInfo>> {
+        dispatch!(self, store => BaseStateStore::get_stripped_room_infos(store).await)
+    }
+
+    async fn get_users_with_display_name(
+        &self,
+        room_id: &RoomId,
+        display_name: &str,
+    ) -> StoreResult<BTreeSet<OwnedUserId>> {
+        dispatch!(self, store => {
+            BaseStateStore::get_users_with_display_name(store, room_id, display_name).await
+        })
+    }
+
+    async fn get_account_data_event(
+        &self,
+        event_type: GlobalAccountDataEventType,
+    ) -> StoreResult<Option<Raw<AnyGlobalAccountDataEvent>>> {
+        dispatch!(self, store => BaseStateStore::get_account_data_event(store, event_type).await)
+    }
+
+    async fn get_room_account_data_event(
+        &self,
+        room_id: &RoomId,
+        event_type: RoomAccountDataEventType,
+    ) -> StoreResult<Option<Raw<AnyRoomAccountDataEvent>>> {
+        dispatch!(self, store => {
+            BaseStateStore::get_room_account_data_event(store, room_id, event_type).await
+        })
+    }
+
+    async fn get_user_room_receipt_event(
+        &self,
+        room_id: &RoomId,
+        receipt_type: ReceiptType,
+        user_id: &UserId,
+    ) -> StoreResult<Option<(OwnedEventId, Receipt)>> {
+        dispatch!(self, store => {
+            BaseStateStore::get_user_room_receipt_event(store, room_id, receipt_type, user_id)
+                .await
+        })
+    }
+
+    async fn get_event_room_receipt_events(
+        &self,
+        room_id: &RoomId,
+        receipt_type: ReceiptType,
+        event_id: &EventId,
+    ) -> StoreResult<Vec<(OwnedUserId, Receipt)>> {
+        dispatch!(self, store => {
+            BaseStateStore::get_event_room_receipt_events(store, room_id, receipt_type, event_id)
+                .await
+        })
+    }
+
+    async fn get_custom_value(&self, key: &[u8]) -> StoreResult<Option<Vec<u8>>> {
+        dispatch!(self, store => BaseStateStore::get_custom_value(store, key).await)
+    }
+
+    async fn set_custom_value(&self, key: &[u8], value: Vec<u8>) -> StoreResult<Option<Vec<u8>>> {
+        dispatch!(self, store => BaseStateStore::set_custom_value(store, key, value).await)
+    }
+
+    async fn add_media_content(&self, request: &MediaRequest, content: Vec<u8>) -> StoreResult<()> {
+        dispatch!(self, store => BaseStateStore::add_media_content(store, request, content).await)
+    }
+
+    async fn get_media_content(&self, request: &MediaRequest) -> StoreResult<Option<Vec<u8>>> {
+        dispatch!(self, store => BaseStateStore::get_media_content(store, request).await)
+    }
+
+    async fn remove_media_content(&self, request: &MediaRequest) -> StoreResult<()> {
+        dispatch!(self, store => BaseStateStore::remove_media_content(store, request).await)
+    }
+
+    async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> StoreResult<()> {
+        dispatch!(self, store => BaseStateStore::remove_media_content_for_uri(store, uri).await)
+    }
+
+    async fn remove_room(&self, room_id: &RoomId) -> StoreResult<()> {
+        dispatch!(self, store => BaseStateStore::remove_room(store, room_id).await)
+    }
+}
@@ -0,0 +1,265 @@
+//! Write-behind batching of state changes
+
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use crate::{
+    helpers::{retry_transient, BorrowedSqlType, SqlType},
+    Result, RoomLocks, StateStore, StoreSerializer, SupportedDatabase,
+};
+use matrix_sdk_base::{
+    locks::{Mutex, MutexGuard},
+    MinimalRoomMemberEvent, RoomInfo, StateChanges,
+};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::Receipt,
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent,
+    },
+    serde::Raw,
+    RoomId,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments, Pool,
+    Transaction,
+};
+use tracing::warn;
+
+/// Settings for batching state store writes
+///
+/// See [`StateStore::set_write_batching`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteBatching {
+    /// Number of queued state changes after which the queue is written immediately
+    pub max_changes: usize,
+    /// Interval at which the queue is written in the background
+    pub interval: Duration,
+}
+
+impl WriteBatching {
+    /// Creates new batching settings
+    #[must_use]
+    pub const fn new(max_changes: usize, interval: Duration) -> Self {
+        Self {
+            max_changes,
+            interval,
+        }
+    }
+}
+
+impl Default for WriteBatching {
+    fn default() -> Self {
+        Self::new(32, Duration::from_secs(1))
+    }
+}
+
+/// Queue of state changes that have not been written yet
+#[allow(single_use_lifetimes)]
+#[derive(Debug)]
+pub(crate) struct WriteQueue<DB: SupportedDatabase> {
+    /// The database connection
+    db: Arc<Pool<DB>>,
+    /// Prefix prepended to the names of all tables
+    table_prefix: &'static str,
+    /// State changes in the order they were saved
+    pending: Mutex<Vec<StateChanges>>,
+    /// The settings the queue has been started with
+    batching: WriteBatching,
+    /// Whether membership changes are recorded in the member history
+    member_history: bool,
+    /// Number of sync tokens kept in the sync token history
+    sync_token_history_len: u32,
+    /// Serialization format of new events
+    serializer: Arc<dyn StoreSerializer>,
+    /// Number of times the write transaction is repeated after a transient conflict
+    transaction_retries: u32,
+    /// The write locks of the rooms, shared with the store
+    room_locks: Arc<RoomLocks>,
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> WriteQueue<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Creates a new queue and starts writing it in the background
+    ///
+    /// The background task ends once the queue is dropped.
+    ///
+    /// # Panics
+    /// This function panics if it is not called from within a tokio runtime
+    pub(crate) fn start(
+        db: &Arc<Pool<DB>>,
+        table_prefix: &'static str,
+        batching: WriteBatching,
+        member_history: bool,
+        sync_token_history_len: u32,
+        serializer: &Arc<dyn StoreSerializer>,
+        transaction_retries: u32,
+        room_locks: &Arc<RoomLocks>,
+    ) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            db: Arc::clone(db),
+            table_prefix,
+            pending: Mutex::new(Vec::new()),
+            batching,
+            member_history,
+            sync_token_history_len,
+            serializer: Arc::clone(serializer),
+            transaction_retries,
+            room_locks: Arc::clone(room_locks),
+        });
+        tokio::spawn(Self::flush_periodically(
+            Arc::downgrade(&queue),
+            batching.interval,
+        ));
+        queue
+    }
+
+    /// Returns the settings the queue has been started with
+    pub(crate) const fn batching(&self) -> WriteBatching {
+        self.batching
+    }
+
+    /// Writes the queue at the given interval for as long as it exists
+    async fn flush_periodically(queue: Weak<Self>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let queue = match queue.upgrade() {
+                Some(queue) => queue,
+                None => return,
+            };
+            if let Err(error) = queue.flush().await {
+                warn!(%error, "Failed to write batched state changes");
+            }
+        }
+    }
+
+    /// Adds state changes to the queue, writing it if it is full
+    ///
+    /// # Errors
+    /// This function will return an error if the queue is full and cannot be written
+    pub(crate) async fn push(&self, state_changes: &StateChanges) -> Result<()> {
+        let mut pending = self.pending.lock().await;
+        pending.push(state_changes.clone());
+        if pending.len() >= self.batching.max_changes.max(1) {
+            self.write(&mut pending).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes all queued state changes
+    ///
+    /// # Errors
+    /// This function will return an error if the database query fails
+    pub(crate) async fn flush(&self) -> Result<()> {
+        let mut pending = self.pending.lock().await;
+        self.write(&mut pending).await
+    }
+
+    /// Drops all queued changes of a room
+    ///
+    /// The returned guard blocks writing the queue, the room can be deleted from the database
+    /// while it is held.
+    pub(crate) async fn discard_room(&self, room_id: &RoomId) -> MutexGuard<'_, Vec<StateChanges>> {
+        let mut pending = self.pending.lock().await;
+        for state_changes in pending.iter_mut() {
+            state_changes.room_infos.remove(room_id);
+            state_changes.stripped_room_infos.remove(room_id);
+            state_changes.members.remove(room_id);
+            state_changes.stripped_members.remove(room_id);
+            state_changes.profiles.remove(room_id);
+            state_changes.state.remove(room_id);
+            state_changes.stripped_state.remove(room_id);
+            state_changes.room_account_data.remove(room_id);
+            state_changes.redactions.remove(room_id);
+            state_changes.receipts.remove(room_id);
+        }
+        pending
+    }
+
+    /// Drops all queued changes, including the sync token
+    ///
+    /// The returned guard blocks writing the queue, the store can be cleared while it is held.
+    pub(crate) async fn discard(&self) -> MutexGuard<'_, Vec<StateChanges>> {
+        let mut pending = self.pending.lock().await;
+        pending.clear();
+        pending
+    }
+
+    /// Writes the given state changes in a single transaction
+    ///
+    /// The state changes are only removed from the queue once the transaction has been
+    /// committed, so that they are written again by the next flush otherwise. The write locks
+    /// of all affected rooms are held while writing.
+    ///
+    /// # Errors
+    /// This function will return an error if the database query fails
+    async fn write(&self, pending: &mut Vec<StateChanges>) -> Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let batch = &*pending;
+        let _guards = self
+            .room_locks
+            .lock_all(batch.iter().flat_map(StateStore::<DB>::changed_rooms))
+            .await;
+        retry_transient(self.transaction_retries, || async move {
+            let mut txn = self.db.begin().await?;
+            for state_changes in batch {
+                StateStore::<DB>::save_state_changes_txn(
+                    &mut txn,
+                    self.table_prefix,
+                    state_changes,
+                    self.sync_token_history_len,
+                    &*self.serializer,
+                )
+                .await?;
+                if self.member_history {
+                    for room_id in state_changes.members.keys() {
+                        StateStore::<DB>::record_member_history_txn(
+                            &mut txn,
+                            self.table_prefix,
+                            room_id,
+                            state_changes,
+                        )
+                        .await?;
+                    }
+                }
+            }
+            txn.commit().await?;
+            Ok(())
+        })
+        .await?;
+        pending.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Key management for the crypto store cipher
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use async_trait::async_trait;
+use educe::Educe;
+use matrix_sdk_store_encryption::StoreCipher;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{helpers::hex_encode, Result, SQLStoreError};
+
+/// Key management for the cipher that encrypts the crypto store
+///
+/// The store cipher is generated once and kept in the database in exported form. A provider
+/// decides how the cipher is protected at rest, for example by deriving the wrapping key from a
+/// passphrase, or by fetching it from an HSM, the OS keychain or a cloud KMS.
+///
+/// Errors of the key management backend can be reported through
+/// [`SQLStoreError::CipherProvider`](crate::SQLStoreError::CipherProvider).
+#[async_trait]
+pub trait StoreCipherProvider: Send + Sync {
+    /// Protects a newly created store cipher for storage in the database
+    ///
+    /// # Errors
+    /// This function will return an error if the cipher cannot be exported
+    async fn export(&self, cipher: &StoreCipher) -> Result<Vec<u8>>;
+
+    /// Restores the store cipher from the data returned by [`StoreCipherProvider::export`]
+    ///
+    /// # Errors
+    /// This function will return an error if the cipher cannot be imported, for example
+    /// because the wrong key was used
+    async fn import(&self, exported: &[u8]) -> Result<StoreCipher>;
+
+    /// Returns whether the exported cipher should be exported again with the current settings
+    ///
+    /// This is checked after every successful unlock, so that strengthened settings are applied
+    /// to existing databases.
+    fn needs_rewrap(&self, exported: &[u8]) -> bool {
+        let _ = exported;
+        false
+    }
+}
+
+/// Parameters of the Argon2id key derivation applied to the passphrase
+///
+/// The derived key is used as the passphrase of the store cipher export, which applies its own
+/// fixed PBKDF2 rounds on top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of iterations
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Creates a new set of key derivation parameters
+    #[must_use]
+    pub const fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        Self {
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+    }
+
+    /// Returns whether any of the costs is lower than in `other`
+    #[must_use]
+    pub const fn is_weaker_than(&self, other: &Self) -> bool {
+        self.memory_kib < other.memory_kib
+            || self.iterations < other.iterations
+            || self.parallelism < other.parallelism
+    }
+
+    /// Derives a passphrase for the store cipher export
+    ///
+    /// The derivation takes a lot of CPU time and memory on purpose, so it runs on the blocking
+    /// thread pool instead of stalling the async runtime.
+    ///
+    /// # Errors
+    /// This function will return an error if the parameters are out of range
+    async fn derive(self, passphrase: &str, salt: &[u8]) -> Result<Zeroizing<String>> {
+        let passphrase = Zeroizing::new(passphrase.to_owned());
+        let salt = salt.to_vec();
+        tokio::task::spawn_blocking(move || self.derive_blocking(&passphrase, &salt))
+            .await
+            .map_err(|e| SQLStoreError::CipherProvider(Box::new(e)))?
+    }
+
+    /// Derives a passphrase for the store cipher export on the current thread
+    ///
+    /// # Errors
+    /// This function will return an error if the parameters are out of range
+    fn derive_blocking(&self, passphrase: &str, salt: &[u8]) -> Result<Zeroizing<String>> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| SQLStoreError::CipherProvider(Box::new(e)))?;
+        let mut key = Zeroizing::new([0_u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+            .map_err(|e| SQLStoreError::CipherProvider(Box::new(e)))?;
+        Ok(Zeroizing::new(hex_encode(&*key)))
+    }
+}
+
+impl Default for KdfParams {
+    /// The parameters recommended by OWASP for Argon2id
+    fn default() -> Self {
+        Self::new(19 * 1024, 2, 1)
+    }
+}
+
+/// Store cipher export protected with an Argon2id derived passphrase
+#[derive(Serialize, Deserialize)]
+struct KdfExport {
+    /// Parameters used for the key derivation
+    kdf: KdfParams,
+    /// Salt used for the key derivation
+    salt: Vec<u8>,
+    /// The store cipher export
+    cipher: Vec<u8>,
+}
+
+/// Protects the store cipher with a passphrase
+///
+/// This is what [`StateStore::unlock_with_passphrase`](crate::StateStore::unlock_with_passphrase)
+/// uses. By default only the fixed key derivation of the store cipher is applied. With
+/// [`PassphraseCipherProvider::with_kdf_params`], the passphrase is additionally run through
+/// Argon2id, and databases protected with weaker parameters are upgraded when unlocked.
+#[derive(Educe)]
+#[educe(Debug)]
+pub struct PassphraseCipherProvider<'a> {
+    /// The passphrase
+    #[educe(Debug(ignore))]
+    passphrase: &'a str,
+    /// Parameters of the additional key derivation
+    kdf: Option<KdfParams>,
+}
+
+impl<'a> PassphraseCipherProvider<'a> {
+    /// Creates a provider for the given passphrase
+    #[must_use]
+    pub const fn new(passphrase: &'a str) -> Self {
+        Self {
+            passphrase,
+            kdf: None,
+        }
+    }
+
+    /// Creates a provider for the given passphrase that uses the given key derivation parameters
+    #[must_use]
+    pub const fn with_kdf_params(passphrase: &'a str, kdf: KdfParams) -> Self {
+        Self {
+            passphrase,
+            kdf: Some(kdf),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreCipherProvider for PassphraseCipherProvider<'_> {
+    async fn export(&self, cipher: &StoreCipher) -> Result<Vec<u8>> {
+        let kdf = if let Some(kdf) = self.kdf {
+            kdf
+        } else {
+            return Ok(cipher.export(self.passphrase)?);
+        };
+        let salt = rand::random::<[u8; 16]>().to_vec();
+        let export = KdfExport {
+            kdf,
+            cipher: cipher.export(&kdf.derive(self.passphrase, &salt).await?)?,
+            salt,
+        };
+        Ok(serde_json::to_vec(&export)?)
+    }
+
+    async fn import(&self, exported: &[u8]) -> Result<StoreCipher> {
+        // Exports without additional key derivation are read regardless of the configuration
+        match serde_json::from_slice::<KdfExport>(exported) {
+            Ok(export) => Ok(StoreCipher::import(
+                &export.kdf.derive(self.passphrase, &export.salt).await?,
+                &export.cipher,
+            )?),
+            Err(_) => Ok(StoreCipher::import(self.passphrase, exported)?),
+        }
+    }
+
+    fn needs_rewrap(&self, exported: &[u8]) -> bool {
+        let kdf = if let Some(kdf) = self.kdf {
+            kdf
+        } else {
+            return false;
+        };
+        serde_json::from_slice::<KdfExport>(exported)
+            .map_or(true, |export| export.kdf.is_weaker_than(&kdf))
+    }
+}
+
+/// Protects the store cipher with a raw 32-byte key
+///
+/// This is what [`StateStore::unlock_with_key`](crate::StateStore::unlock_with_key) uses. The key
+/// should come from a key management system, such as the OS keyring, and be uniformly random.
+#[derive(Educe)]
+#[educe(Debug)]
+pub struct KeyCipherProvider<'a> {
+    /// The key
+    #[educe(Debug(ignore))]
+    key: &'a [u8; 32],
+}
+
+impl<'a> KeyCipherProvider<'a> {
+    /// Creates a provider for the given key
+    #[must_use]
+    pub const fn new(key: &'a [u8; 32]) -> Self {
+        Self { key }
+    }
+}
+
+#[async_trait]
+impl StoreCipherProvider for KeyCipherProvider<'_> {
+    async fn export(&self, cipher: &StoreCipher) -> Result<Vec<u8>> {
+        let passphrase = Zeroizing::new(hex_encode(self.key));
+        Ok(cipher.export(&passphrase)?)
+    }
+
+    async fn import(&self, exported: &[u8]) -> Result<StoreCipher> {
+        let passphrase = Zeroizing::new(hex_encode(self.key));
+        Ok(StoreCipher::import(&passphrase, exported)?)
+    }
+}
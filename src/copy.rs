@@ -0,0 +1,94 @@
+//! Copying SQLite databases to a different file
+
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+
+use sqlx::{sqlite::SqliteConnectOptions, Sqlite};
+
+use crate::{telemetry::timed, Result, StateStore};
+
+impl StateStore<Sqlite> {
+    /// Copies the database to `new_path` and returns a store that uses the copy
+    ///
+    /// Queued writes are flushed before the database is copied with `VACUUM INTO`, which takes
+    /// a consistent copy without blocking concurrent writers. The returned store has the
+    /// settings of this store and a new pool with the pool and connect options of the pool of
+    /// this store, which is also used for reads. If the cryptostore is unlocked, the returned
+    /// store is unlocked as well.
+    ///
+    /// This is a copy, not a move: the pool of this store is not closed, as it may be shared with
+    /// the application, and the old database is left untouched. Writes made through this store
+    /// after the copy are not part of the copy, so it should not be used anymore. The old database files can be removed once all users of the old pool,
+    /// such as a running [`StateStore::spawn_maintenance`] task, are done and the pool has been
+    /// closed. If the copy fails, the partially written file at `new_path` is removed.
+    ///
+    /// # Errors
+    /// This function will return an error if the database is not stored in a file, if
+    /// `new_path` already exists, or if the database cannot be copied or reopened
+    pub async fn copy_sqlite_to(&self, new_path: &Path) -> Result<Self> {
+        let old_path: String =
+            sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+                .fetch_one(timed(&*self.db, self.table_prefix))
+                .await?;
+        if old_path.is_empty() {
+            return Err(
+                Error::new(ErrorKind::Unsupported, "database is not stored in a file").into(),
+            );
+        }
+        let new_path_str = new_path
+            .to_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path is not valid UTF-8"))?;
+
+        self.flush().await?;
+        // `VACUUM INTO` accepts an empty file, creating it first fails if the path exists
+        tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(new_path)
+            .await?;
+        if let Err(error) = sqlx::query("VACUUM INTO $1")
+            .bind(new_path_str)
+            .execute(timed(&*self.db, self.table_prefix))
+            .await
+        {
+            tokio::fs::remove_file(new_path).await?;
+            return Err(error.into());
+        }
+
+        let options: SqliteConnectOptions = (*self.db.connect_options()).clone();
+        let db = Arc::new(
+            self.db
+                .options()
+                .clone()
+                .connect_with(options.filename(new_path))
+                .await?,
+        );
+        let mut store = Self::from_pool(&db);
+        store.table_prefix = self.table_prefix;
+        store.media_deduplication = self.media_deduplication;
+        store.max_media_bytes = self.max_media_bytes;
+        store.member_history = self.member_history;
+        store.sync_token_history_len = self.sync_token_history_len;
+        store.transaction_retries = self.transaction_retries;
+        store.room_retention = self.room_retention;
+        store.serializer = Arc::clone(&self.serializer);
+        #[cfg(feature = "compression")]
+        {
+            store.compression = self.compression;
+        }
+        #[cfg(feature = "e2e-encryption")]
+        {
+            *store
+                .cryptostore
+                .get_mut()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = self.ensure_e2e().ok();
+        }
+        if let Some(batching) = self.write_queue.as_ref().map(|queue| queue.batching()) {
+            store.set_write_batching(Some(batching)).await?;
+        }
+        Ok(store)
+    }
+}
@@ -1,812 +1,983 @@
 //! Various helper functionality
 
-use sqlx::{
-    database::HasArguments, migrate::Migrator, query::Query, Database, Decode, Encode, Type,
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    future::Future,
+    path::Path,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use self::private::Sealed;
-
-/// Private module for the [`Sealed`] trait.
-mod private {
+use futures::future::BoxFuture;
+#[cfg(feature = "postgres")]
+use futures::TryStreamExt;
+use once_cell::sync::Lazy;
+use sqlx::{
+    database::HasArguments,
+    migrate::{MigrateError, Migrator},
+    query::Query,
+    ColumnIndex, Connection, Database, Decode, Encode, Executor, IntoArguments, Pool, Row,
+    Transaction, Type,
+};
+#[cfg(feature = "postgres")]
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+};
+use tracing::debug;
 
-    /// A trait for “sealing” a trait
-    #[allow(unreachable_pub)]
-    pub trait Sealed {}
+use crate::{
+    queries::{
+        CryptoQueries, KvQueries, MaintenanceQueries, MediaQueries, MemberQueries, RoomQueries,
+        RowQueries, StateQueries, SyncQueries,
+    },
+    schema::{latest_schema_version, MIN_READER_SCHEMA_VERSION},
+    telemetry::timed,
+    Result, SQLStoreError,
+};
 
-    #[cfg(feature = "postgres")]
-    impl Sealed for sqlx::postgres::Postgres {}
-    #[cfg(feature = "sqlite")]
-    impl Sealed for sqlx::sqlite::Sqlite {}
+/// Encodes bytes as lowercase hexadecimal
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        // Writing to a string cannot fail
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
 }
 
-/// Helper trait that marks an SQL-Compatible type
-pub trait SqlType<DB: Database>:
-    for<'a> Encode<'a, DB> + for<'a> Decode<'a, DB> + Type<DB>
-{
-}
-impl<DB: Database, T> SqlType<DB> for T where
-    T: for<'a> Encode<'a, DB> + for<'a> Decode<'a, DB> + Type<DB>
-{
+/// Returns the current time as a unix timestamp in seconds
+pub(crate) fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
 }
 
-/// Helper trait for a borrowed SQL-Compatible type
-pub trait BorrowedSqlType<'a, DB: Database>:
-    Encode<'a, DB> + Decode<'a, DB> + Type<DB> + 'a
-{
-}
-impl<'a, DB: Database, T> BorrowedSqlType<'a, DB> for T where
-    T: Encode<'a, DB> + Decode<'a, DB> + Type<DB> + 'a
-{
+/// Returns the current time as a unix timestamp in milliseconds
+#[cfg(feature = "e2e-encryption")]
+pub(crate) fn unix_timestamp_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_millis()).unwrap_or(i64::MAX))
 }
 
-/// Supported Database trait
-///
-/// It contains many methods that try to generate queries for the supported databases.
-#[allow(single_use_lifetimes)]
-#[doc(hidden)]
-pub trait SupportedDatabase: Database + Sealed {
-    /// Returns the migrator for the current database type
-    fn get_migrator() -> &'static Migrator;
+/// Table prefixes in use, each leaked once
+static TABLE_PREFIXES: Lazy<Mutex<HashSet<&'static str>>> = Lazy::new(Mutex::default);
 
-    /// Returns a query for upserting into the `statestore_kv` table
-    ///
-    /// # Arguments
-    /// * `$1` - The key to insert
-    /// * `$2` - The value to insert
-    fn kv_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_kv (kv_key, kv_value)
-                VALUES ($1, $2)
-                ON CONFLICT (kv_key) DO UPDATE SET kv_value = $2
-            "#,
-        )
-    }
+/// Queries with a table prefix applied, keyed by the prefix and the original query
+static PREFIXED_QUERIES: Lazy<Mutex<HashMap<&'static str, HashMap<String, &'static str>>>> =
+    Lazy::new(Mutex::default);
 
-    /// Returns a query for loading from the `statestore_kv` table
-    ///
-    /// # Arguments
-    /// * `$1` - The key to load
-    fn kv_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT kv_value FROM statestore_kv WHERE kv_key = $1
-            "#,
-        )
+/// Checks that the prefix of table names is a valid identifier, and returns it for the lifetime
+/// of the process
+///
+/// # Errors
+/// This function will return an error if the prefix is not a valid identifier
+pub(crate) fn table_prefix(prefix: &str) -> Result<&'static str> {
+    if prefix.is_empty() {
+        return Ok("");
     }
-
-    /// Returns a query for loading from the `statestore_media` table
-    ///
-    /// # Arguments
-    /// * `$1` - The key to load
-    fn media_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                UPDATE statestore_media
-                SET last_access = NOW()
-                WHERE media_url = $1
-                RETURNING media_data
-            "#,
-        )
+    let valid = prefix
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(SQLStoreError::InvalidTablePrefix(prefix.to_owned()));
     }
-
-    /// Returns the first query for storing into the `statestore_media` table
-    ///
-    /// # Arguments
-    /// * `$1` - The key to insert
-    /// * `$2` - The value to insert
-    fn media_insert_query_1<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_media (media_url, media_data, last_access)
-                VALUES ($1, $2, NOW())
-                ON CONFLICT (media_url) DO NOTHING
-            "#,
-        )
+    let mut prefixes = TABLE_PREFIXES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(prefix) = prefixes.get(prefix) {
+        return Ok(prefix);
     }
+    let prefix: &'static str = Box::leak(prefix.to_owned().into_boxed_str());
+    prefixes.insert(prefix);
+    Ok(prefix)
+}
 
-    /// Returns the second query for storing into the `statestore_media` table
-    fn media_insert_query_2<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                DELETE FROM statestore_media
-                WHERE media_url NOT IN
-                    (SELECT media_url FROM statestore_media
-                     ORDER BY last_access DESC
-                     LIMIT 100)
-            "#,
-        )
+/// Prepends the prefix to every table name in the SQL statement
+///
+/// All tables of this crate are named `statestore_*` or `cryptostore_*`. The migrations
+/// bookkeeping table `_sqlx_migrations` of sqlx is renamed to `<prefix>sqlx_migrations`, so that
+/// every prefix keeps its own migrations. Statements that are already prefixed are not changed.
+pub(crate) fn apply_table_prefix(sql: &str, prefix: &str) -> String {
+    let mut out = String::with_capacity(sql.len() + prefix.len() * 4);
+    let mut after_identifier = false;
+    let mut chars = sql.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if !after_identifier && !prefix.is_empty() {
+            if sql[i..].starts_with("statestore_") || sql[i..].starts_with("cryptostore_") {
+                out.push_str(prefix);
+            } else if sql[i..].starts_with("_sqlx_migrations") {
+                out.push_str(prefix);
+                after_identifier = true;
+                continue;
+            }
+        }
+        after_identifier = c.is_ascii_alphanumeric() || c == '_';
+        out.push(c);
     }
+    out
+}
 
-    /// Deletes the media with the mxc URL
-    ///
-    /// # Arguments
-    /// * `$1` - The mxc URL
-    fn media_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                DELETE FROM statestore_media
-                WHERE media_url = $1
-            "#,
-        )
+/// Returns the SQL statement with the table prefix applied, or `None` if it is used unchanged
+///
+/// Every statement is only leaked once per prefix.
+pub(crate) fn prefixed_sql(sql: &str, prefix: &'static str) -> Option<&'static str> {
+    if prefix.is_empty() {
+        return None;
     }
-
-    /// Deletes a room given its ID
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    #[must_use]
-    fn room_remove_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
-        vec![
-            sqlx::query("DELETE FROM statestore_rooms WHERE room_id = $1"),
-            sqlx::query("DELETE FROM statestore_accountdata WHERE room_id = $1"),
-            sqlx::query("DELETE FROM statestore_members WHERE room_id = $1"),
-            sqlx::query("DELETE FROM statestore_state WHERE room_id = $1"),
-            sqlx::query("DELETE FROM statestore_receipts WHERE room_id = $1"),
-        ]
+    let mut queries = PREFIXED_QUERIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let queries = queries.entry(prefix).or_default();
+    if let Some(prefixed) = queries.get(sql) {
+        return Some(prefixed);
     }
+    let prefixed: &'static str = Box::leak(apply_table_prefix(sql, prefix).into_boxed_str());
+    queries.insert(sql.to_owned(), prefixed);
+    Some(prefixed)
+}
 
-    /// Upserts account data
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID for the account data
-    /// * `$2` - The account data event type
-    /// * `$3` - The account data event content
-    fn account_data_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_accountdata
-                    (room_id, event_type, account_data)
-                VALUES ($1, $2, $3)
-                ON CONFLICT(room_id, event_type) DO UPDATE SET account_data = $3
-            "#,
-        )
-    }
+/// Creates a query for the SQL statement
+///
+/// Implementations of [`SupportedDatabase`] for other databases should create their queries
+/// with this function. The statement has to name the tables of this crate without a prefix,
+/// the table prefix of the store is applied when the query is run, see
+/// [`StateStore::with_table_prefix`](crate::StateStore::with_table_prefix).
+pub fn prefixed_query<'q, DB: Database>(
+    sql: &'static str,
+) -> Query<'q, DB, <DB as HasArguments<'q>>::Arguments> {
+    sqlx::query(sql)
+}
 
-    /// Retrieves account data
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID for the account data
-    /// * `$2` - The account data event type
-    fn account_data_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT account_data FROM statestore_accountdata
-                WHERE room_id = $1 AND event_type = $2
-            "#,
-        )
-    }
+/// Delay before the first repetition of a transaction after a transient conflict
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(10);
 
-    /// Upserts user presence data
-    ///
-    /// # Arguments
-    /// * `$1` - The user ID
-    /// * `$2` - The presence data
-    fn presence_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_presence
-                    (user_id, presence)
-                VALUES ($1, $2)
-                ON CONFLICT(user_id) DO UPDATE SET presence = $2
-            "#,
-        )
+/// Runs a write transaction, repeating it with exponential backoff after transient conflicts
+///
+/// # Errors
+/// This function will return the error of the last attempt if it is not transient, or if the
+/// transaction still fails after `retries` repetitions
+pub(crate) async fn retry_transient<T, F, Fut>(retries: u32, mut transaction: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match transaction().await {
+            Err(e) if attempt < retries && e.is_transient() => {
+                let delay = RETRY_BASE_DELAY * 2_u32.saturating_pow(attempt);
+                debug!(%e, attempt, ?delay, "Repeating transaction after transient conflict");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
     }
+}
 
-    /// Retrieves user presence data
-    ///
-    /// # Arguments
-    /// * `$1` - The user ID
-    fn presence_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT presence FROM statestore_presence
-                WHERE user_id = $1
-            "#,
-        )
+/// Returns whether a query failed because one of its tables does not exist
+///
+/// Postgres reports these with SQLSTATE 42P01, sqlite only with the error message.
+pub(crate) fn is_missing_table(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(e) => {
+            e.code().as_deref() == Some("42P01") || e.message().starts_with("no such table")
+        }
+        _ => false,
     }
+}
 
-    /// Upserts room membership information
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The user ID
-    /// * `$3` - Whether or not the membership event is stripped
-    /// * `$4` - The membership event content
-    /// * `$5` - The display name of the user
-    /// * `$6` - Whether or not the user has joined
-    fn member_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_members
-                    (room_id, user_id, is_partial, member_event, displayname, joined)
-                VALUES ($1, $2, $3, $4, $5, $6)
-                ON CONFLICT(room_id, user_id) DO UPDATE SET is_partial = $3, member_event = $4, displayname = $5, joined = $6
-            "#,
-        )
+/// Makes sure that the database has not been written by a newer, incompatible crate version
+///
+/// Returns the schema version recorded in the database, if any. This only reads from the
+/// database, databases that have not been migrated yet are compatible.
+///
+/// # Errors
+/// This function will return an error if the database is incompatible, or if a query fails
+#[allow(single_use_lifetimes)]
+pub async fn check_schema_compat<DB: SupportedDatabase>(
+    db: &Pool<DB>,
+    table_prefix: &'static str,
+) -> Result<Option<i64>>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    i64: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    let supported = latest_schema_version();
+    let row = match DB::schema_compat_fetch_query()
+        .fetch_optional(timed(db, table_prefix))
+        .await
+    {
+        Ok(row) => row,
+        Err(error) if is_missing_table(&error) => None,
+        Err(error) => return Err(error.into()),
+    };
+    let row = if let Some(row) = row {
+        row
+    } else {
+        return Ok(None);
+    };
+    let schema_version: i64 = row.try_get("schema_version")?;
+    let min_reader_version: i64 = row.try_get("min_reader_version")?;
+    if min_reader_version > supported {
+        return Err(SQLStoreError::IncompatibleSchema {
+            database: schema_version,
+            supported,
+        });
     }
+    Ok(Some(schema_version))
+}
 
-    /// Upserts user profile information
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The user ID
-    /// * `$3` - Whether or not the information is partial
-    /// * `$4` - The profile event content
-    fn member_profile_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_members
-                    (room_id, user_id, is_partial, user_profile)
-                VALUES ($1, $2, $3, $4)
-                ON CONFLICT(room_id, user_id) DO UPDATE SET user_profile = $4
-            "#,
-        )
+/// Makes sure that the database has not been written by a newer, incompatible crate version, and
+/// records the schema version of this crate version otherwise
+///
+/// # Errors
+/// This function will return an error if the database is incompatible, or if a query fails
+///
+/// Implementations of [`SupportedDatabase::run_migrations`] need to call this after applying
+/// the migrations.
+#[allow(single_use_lifetimes)]
+pub async fn record_schema_compat<DB: SupportedDatabase>(
+    db: &Pool<DB>,
+    table_prefix: &'static str,
+) -> Result<()>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    i64: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    let supported = latest_schema_version();
+    if check_schema_compat(db, table_prefix).await? >= Some(supported) {
+        return Ok(());
     }
+    DB::schema_compat_upsert_query()
+        .bind(supported)
+        .bind(MIN_READER_SCHEMA_VERSION)
+        .execute(timed(db, table_prefix))
+        .await?;
+    Ok(())
+}
 
-    /// Upserts a state event
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The event type
-    /// * `$3` - The state key
-    /// * `$4` - Whether or not the state is partial
-    /// * `$5` - The event content
-    /// * `$5` - The event ID
-    fn state_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_state
-                    (room_id, event_type, state_key, is_partial, state_event, event_id)
-                VALUES ($1, $2, $3, $4, $5, $6)
-                ON CONFLICT(room_id, event_type, state_key) DO UPDATE SET is_partial = $4, state_event = $5, event_id = $6
-            "#,
-        )
+/// Applies all pending migrations on the connection
+///
+/// This does the same as [`Migrator::run`], except that the migrations are recorded in the
+/// migrations table of the table prefix, `<prefix>sqlx_migrations`, instead of the
+/// `_sqlx_migrations` table of the application. Every migration is applied in its own
+/// transaction. Implementations of [`SupportedDatabase::run_migrations`] should call this while
+/// holding a database-wide lock, if the database has one.
+///
+/// # Errors
+/// This function will return an error if a migration fails, or if a migration was applied with
+/// different contents
+#[allow(single_use_lifetimes)]
+pub async fn apply_migrations<DB: SupportedDatabase>(
+    conn: &mut <DB as Database>::Connection,
+    table_prefix: &'static str,
+) -> Result<()>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+    for<'c, 'a> &'a mut Transaction<'c, DB>: Executor<'a, Database = DB>,
+    i64: SqlType<DB>,
+    Vec<u8>: SqlType<DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB> + ColumnIndex<<DB as Database>::Row>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+{
+    timed(&mut *conn, table_prefix)
+        .execute(DB::migrations_table_sql())
+        .await?;
+    let applied = DB::applied_migrations_query()
+        .fetch_all(timed(&mut *conn, table_prefix))
+        .await?
+        .iter()
+        .map(|row| Ok((row.try_get("version")?, row.try_get("checksum")?)))
+        .collect::<Result<HashMap<i64, Vec<u8>>>>()?;
+    for migration in DB::get_migrator().iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+        if let Some(checksum) = applied.get(&migration.version) {
+            if checksum.as_slice() != &*migration.checksum {
+                return Err(MigrateError::VersionMismatch(migration.version).into());
+            }
+            continue;
+        }
+        let start = Instant::now();
+        let mut txn = conn.begin().await?;
+        timed(&mut txn, table_prefix)
+            .execute(&*migration.sql)
+            .await?;
+        DB::migration_record_query()
+            .bind(migration.version)
+            .bind(&*migration.description)
+            .bind(&*migration.checksum)
+            .bind(i64::try_from(start.elapsed().as_nanos()).unwrap_or(i64::MAX))
+            .execute(timed(&mut txn, table_prefix))
+            .await?;
+        txn.commit().await?;
     }
+    Ok(())
+}
 
-    /// Redacts a state event
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The state event ID
-    fn state_redact_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                DELETE FROM statestore_state
-                WHERE room_id = $1 AND event_id = $2
-            "#,
-        )
+/// Rewrites the DDL of a postgres migration that CockroachDB does not understand
+///
+/// CockroachDB cannot drop the primary key of a table, it changes primary keys with
+/// `ALTER PRIMARY KEY` instead.
+#[cfg(feature = "cockroach")]
+fn adapt_migration_for_cockroach(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    for line in sql.lines() {
+        if line.starts_with("ALTER TABLE ")
+            && line.ends_with("_pkey;")
+            && line.contains(" DROP CONSTRAINT ")
+        {
+            continue;
+        }
+        if let Some((table, columns)) = line
+            .strip_prefix("ALTER TABLE ")
+            .and_then(|line| line.split_once(" ADD PRIMARY KEY "))
+        {
+            out.push_str(&format!(
+                "ALTER TABLE {table} ALTER PRIMARY KEY USING COLUMNS {columns}\n"
+            ));
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
     }
+    out
+}
 
-    /// Upserts room information
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - Whether or not the state is partial
-    /// * `$3` - The room info
-    fn room_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_rooms
-                    (room_id, is_partial, room_info)
-                VALUES ($1, $2, $3)
-                ON CONFLICT(room_id) DO UPDATE SET is_partial = $2, room_info = $3
-            "#,
-        )
-    }
+/// Table holding the lease of the instance migrating a CockroachDB database, and its progress
+#[cfg(feature = "cockroach")]
+const COCKROACH_LEASE_TABLE_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS _sqlx_migrations_lease (
+        id BIGINT PRIMARY KEY,
+        holder TEXT NOT NULL,
+        expires_at TIMESTAMPTZ NOT NULL,
+        version BIGINT,
+        checksum BYTEA,
+        statements_done BIGINT NOT NULL DEFAULT 0
+    )
+"#;
 
-    /// Upserts an event receipt
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The event ID
-    /// * `$3` - The receipt type
-    /// * `$4` - The user id
-    /// * `$5` - The receipt content
-    fn receipt_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO statestore_receipts
-                    (room_id, event_id, receipt_type, user_id, receipt)
-                VALUES ($1, $2, $3, $4, $5)
-                ON CONFLICT(room_id, receipt_type, user_id) DO UPDATE SET event_id = $2, receipt_type = $3, receipt = $5
-            "#,
-        )
-    }
+/// Takes the migration lease if it is free or has expired, returning the recorded progress
+#[cfg(feature = "cockroach")]
+const COCKROACH_LEASE_ACQUIRE_SQL: &str = r#"
+    INSERT INTO _sqlx_migrations_lease (id, holder, expires_at)
+    VALUES (1, $1, now() + INTERVAL '5 minutes')
+    ON CONFLICT (id) DO UPDATE SET holder = excluded.holder, expires_at = excluded.expires_at
+    WHERE _sqlx_migrations_lease.expires_at < now()
+    RETURNING version, checksum, statements_done
+"#;
 
-    /// Retrieves a state event
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The event type
-    /// * `$3` - The state key
-    fn state_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT state_event FROM statestore_state
-                WHERE room_id = $1 AND event_type = $2 AND state_key = $3 AND is_partial = '0'
-            "#,
-        )
-    }
+/// Records the progress of the running migration and renews the lease
+#[cfg(feature = "cockroach")]
+const COCKROACH_LEASE_PROGRESS_SQL: &str = r#"
+    UPDATE _sqlx_migrations_lease
+    SET version = $2, checksum = $3, statements_done = $4,
+        expires_at = now() + INTERVAL '5 minutes'
+    WHERE id = 1 AND holder = $1
+"#;
 
-    /// Retrieves all state events by type in room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The event type
-    /// * `$3` - Whether the state is partial
-    fn states_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT state_event FROM statestore_state
-                WHERE room_id = $1 AND event_type = $2 AND is_partial = $3
-            "#,
-        )
-    }
+/// Releases the migration lease
+#[cfg(feature = "cockroach")]
+const COCKROACH_LEASE_RELEASE_SQL: &str = r#"
+    UPDATE _sqlx_migrations_lease SET expires_at = now() WHERE id = 1 AND holder = $1
+"#;
 
-    /// Retrieves the user profile event for a user in a room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The user ID
-    fn profile_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT user_profile FROM statestore_members
-                WHERE room_id = $1 AND user_id = $2 AND user_profile IS NOT NULL
-            "#,
-        )
+/// Splits a migration into its statements
+///
+/// Semicolons in string literals, quoted identifiers, comments and dollar-quoted function
+/// bodies do not end a statement. Statements that only consist of comments are dropped.
+#[cfg(feature = "cockroach")]
+fn split_sql_statements(sql: &str) -> Vec<&str> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut has_code = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &sql[i..];
+        let skip_to = |end: &str, from: usize| {
+            sql[from..]
+                .find(end)
+                .map_or(bytes.len(), |pos| from + pos + end.len())
+        };
+        match bytes[i] {
+            b'\'' | b'"' => {
+                has_code = true;
+                let quote = &rest[..1];
+                i = skip_to(quote, i + 1);
+            }
+            b'-' if rest.starts_with("--") => i = skip_to("\n", i),
+            b'/' if rest.starts_with("/*") => i = skip_to("*/", i + 2),
+            b'$' => {
+                has_code = true;
+                let tag_len = rest[1..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .map_or(rest.len(), |len| len + 1);
+                if rest[tag_len..].starts_with('$')
+                    && !rest[1..].starts_with(|c: char| c.is_ascii_digit())
+                {
+                    let tag = &rest[..=tag_len];
+                    i = skip_to(tag, i + tag.len());
+                } else {
+                    i += 1;
+                }
+            }
+            b';' => {
+                if has_code {
+                    statements.push(sql[start..i].trim());
+                }
+                has_code = false;
+                i += 1;
+                start = i;
+            }
+            c => {
+                has_code |= !c.is_ascii_whitespace();
+                i += 1;
+            }
+        }
     }
-
-    /// Removes a member from a room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The user ID
-    fn member_remove_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                DELETE FROM statestore_members
-                WHERE room_id = $1 AND user_id = $2
-            "#,
-        )
+    if has_code {
+        statements.push(sql[start..].trim());
     }
+    statements
+}
 
-    /// List all users in a room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    fn members_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT user_id FROM statestore_members
-                WHERE room_id = $1
-            "#,
-        )
-    }
+/// Applies all pending migrations to a CockroachDB database
+///
+/// CockroachDB supports neither the advisory locks taken by the migrator of SQLx, nor writes
+/// to columns that were added earlier in the same transaction. Instead, the instance that
+/// migrates holds a lease row in `<prefix>sqlx_migrations_lease`, which is renewed after every
+/// statement and expires after five minutes, so that an instance that died does not block the
+/// others forever. Other instances wait until the lease is released. The statements of a
+/// migration are run one by one, each in its own transaction that also records the progress in
+/// the lease row, and the migration is recorded together with its last statement. A migration
+/// that was interrupted is continued after its last committed statement.
+///
+/// # Errors
+/// This function will return an error if a migration fails, if the recorded progress does not
+/// belong to the next pending migration, if the lease expired while migrating, or if the
+/// database has been written by a newer, incompatible version of this crate
+#[cfg(feature = "cockroach")]
+async fn run_cockroach_migrations(
+    db: &Pool<sqlx::Postgres>,
+    table_prefix: &'static str,
+) -> Result<()> {
+    let mut conn = db.acquire().await?;
+    timed(&mut *conn, table_prefix)
+        .execute(<sqlx::Postgres as SupportedDatabase>::migrations_table_sql())
+        .await?;
+    timed(&mut *conn, table_prefix)
+        .execute(COCKROACH_LEASE_TABLE_SQL)
+        .await?;
+    let holder = format!(
+        "{}-{}",
+        std::process::id(),
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+    );
+    let progress = loop {
+        let row = sqlx::query(COCKROACH_LEASE_ACQUIRE_SQL)
+            .bind(&holder)
+            .fetch_optional(timed(&mut *conn, table_prefix))
+            .await?;
+        if let Some(row) = row {
+            break row;
+        }
+        debug!("Waiting for another instance to finish the migrations");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    };
+    let version: Option<i64> = progress.try_get("version")?;
+    let checksum: Option<Vec<u8>> = progress.try_get("checksum")?;
+    let statements_done: i64 = progress.try_get("statements_done")?;
+    let progress = version.map(|version| (version, checksum.unwrap_or_default(), statements_done));
 
-    /// List all users in a room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - Whether or not the user has joined
-    fn members_load_query_with_join_status<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT user_id FROM statestore_members
-                WHERE room_id = $1 AND joined = $2
-            "#,
-        )
-    }
+    let result = apply_cockroach_migrations(&mut conn, table_prefix, &holder, progress).await;
+    sqlx::query(COCKROACH_LEASE_RELEASE_SQL)
+        .bind(&holder)
+        .execute(timed(&mut *conn, table_prefix))
+        .await?;
+    result?;
+    drop(conn);
+    record_schema_compat(db, table_prefix).await
+}
 
-    /// Get specific member event
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The user ID
-    fn member_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT is_partial, member_event FROM statestore_members
-                WHERE room_id = $1 AND user_id = $2 AND member_event IS NOT NULL
-            "#,
-        )
-    }
+/// Applies the pending migrations while holding the migration lease
+///
+/// `progress` is the version, checksum and number of applied statements of an interrupted
+/// migration.
+#[cfg(feature = "cockroach")]
+async fn apply_cockroach_migrations(
+    conn: &mut sqlx::PgConnection,
+    table_prefix: &'static str,
+    holder: &str,
+    mut progress: Option<(i64, Vec<u8>, i64)>,
+) -> Result<()> {
+    type Postgres = sqlx::Postgres;
 
-    /// Get room infos
-    ///
-    /// # Arguments
-    /// * `$1` - Whether or not the info is partial
-    fn room_info_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT room_info FROM statestore_rooms
-                WHERE is_partial = $1
-            "#,
-        )
-    }
+    let applied = <Postgres as SupportedDatabase>::applied_migrations_query()
+        .fetch_all(timed(&mut *conn, table_prefix))
+        .await?
+        .iter()
+        .map(|row| Ok((row.try_get("version")?, row.try_get("checksum")?)))
+        .collect::<Result<HashMap<i64, Vec<u8>>>>()?;
+    for migration in <Postgres as SupportedDatabase>::get_migrator().iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+        if let Some(checksum) = applied.get(&migration.version) {
+            if checksum.as_slice() != &*migration.checksum {
+                return Err(MigrateError::VersionMismatch(migration.version).into());
+            }
+            continue;
+        }
+        let statements = split_sql_statements(&migration.sql);
+        let statements_done = match progress.take() {
+            None => 0,
+            Some((version, checksum, statements_done)) => {
+                if version != migration.version
+                    || usize::try_from(statements_done).map_or(true, |done| done > statements.len())
+                {
+                    return Err(MigrateError::Dirty(version).into());
+                }
+                if checksum != *migration.checksum {
+                    return Err(MigrateError::VersionMismatch(version).into());
+                }
+                usize::try_from(statements_done).unwrap_or_default()
+            }
+        };
 
-    /// Get users with display name in room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The display name
-    fn users_with_display_name_load_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT user_id FROM statestore_members
-                WHERE room_id = $1 AND displayname = $2
-            "#,
-        )
+        let start = Instant::now();
+        for i in statements_done..statements.len().max(1) {
+            let mut txn = conn.begin().await?;
+            if let Some(statement) = statements.get(i) {
+                timed(&mut txn, table_prefix).execute(*statement).await?;
+            }
+            let done = i + 1 >= statements.len();
+            let updated = sqlx::query(COCKROACH_LEASE_PROGRESS_SQL)
+                .bind(holder)
+                .bind((!done).then_some(migration.version))
+                .bind((!done).then_some(&*migration.checksum))
+                .bind(if done {
+                    0
+                } else {
+                    i64::try_from(i + 1).unwrap_or(i64::MAX)
+                })
+                .execute(timed(&mut txn, table_prefix))
+                .await?;
+            if updated.rows_affected() == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "the migration lease expired",
+                )
+                .into());
+            }
+            if done {
+                <Postgres as SupportedDatabase>::migration_record_query()
+                    .bind(migration.version)
+                    .bind(&*migration.description)
+                    .bind(&*migration.checksum)
+                    .bind(i64::try_from(start.elapsed().as_nanos()).unwrap_or(i64::MAX))
+                    .execute(timed(&mut txn, table_prefix))
+                    .await?;
+            }
+            txn.commit().await?;
+        }
     }
-
-    /// Get latest receipt for user in room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The receipt type
-    /// * `$3` - The user ID
-    fn receipt_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT event_id, receipt FROM statestore_receipts
-                WHERE room_id = $1 AND receipt_type = $2 AND user_id = $3
-            "#,
-        )
+    match progress {
+        Some((version, _, _)) => Err(MigrateError::Dirty(version).into()),
+        None => Ok(()),
     }
+}
 
-    /// Get all receipts for event in room
-    ///
-    /// # Arguments
-    /// * `$1` - The room ID
-    /// * `$2` - The receipt type
-    /// * `$3` - The event ID
-    fn event_receipt_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT user_id, receipt FROM statestore_receipts
-                WHERE room_id = $1 AND receipt_type = $2 AND event_id = $3
-            "#,
-        )
-    }
+/// First line of a postgres snapshot, followed by the schema version of the snapshot
+#[cfg(feature = "postgres")]
+const SNAPSHOT_HEADER: &str = "-- matrix-sdk-sql snapshot of schema version ";
 
-    /// Stores a cryptostore session
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed sender key
-    /// * `$2` - The encrypted session data
-    #[cfg(feature = "e2e-encryption")]
-    fn session_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_session (sender_key, session_data)
-                VALUES ($1, $2)
-            "#,
-        )
-    }
+/// Returns whether the table is loaded when seeding a store from a snapshot
+///
+/// Only the state store is loaded. Cryptostore data belongs to the device that wrote the
+/// snapshot, and the schema compatibility information belongs to the target database.
+fn is_seeded_table(table: &str, table_prefix: &str) -> bool {
+    table.strip_prefix(table_prefix).map_or(false, |table| {
+        table.starts_with("statestore_")
+            && table != "statestore_kv"
+            && table != "statestore_schema_compat"
+    })
+}
 
-    /// Stores an Olm message hash
-    ///
-    /// # Arguments
-    /// * `$1` - The sender key
-    /// * `$2` - The message hash
-    #[cfg(feature = "e2e-encryption")]
-    fn olm_message_hash_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
-    {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_message_hash (sender_key, message_hash)
-                VALUES ($1, $2)
-            "#,
-        )
-    }
+/// Returns whether mutations of the table are recorded when the audit log is enabled
+///
+/// The audit log itself and the schema compatibility information are not audited.
+fn is_audited_table(table: &str, table_prefix: &str) -> bool {
+    table.strip_prefix(table_prefix).map_or(false, |table| {
+        table.starts_with("statestore_")
+            && table != "statestore_audit_log"
+            && table != "statestore_schema_compat"
+    })
+}
 
-    /// Upserts an inbound group session
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed room ID
-    /// * `$2` - The hashed sender key
-    /// * `$3` - The hashed session id
-    /// * `$4` - The encrypted session data
-    #[cfg(feature = "e2e-encryption")]
-    fn inbound_group_session_upsert_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_inbound_group_session
-                    (room_id, sender_key, session_id, session_data)
-                VALUES ($1, $2, $3, $4)
-                ON CONFLICT (room_id, sender_key, session_id)
-                DO UPDATE SET session_data = $4
-            "#,
-        )
-    }
+/// Quotes an identifier for use in a dynamically built SQL statement
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
 
-    /// Upserts an outbound group session
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed room id
-    /// * `$2` - The encrypted session data
-    #[cfg(feature = "e2e-encryption")]
-    fn outbound_group_session_store_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_outbound_group_session (room_id, session_data)
-                VALUES ($1, $2)
-                ON CONFLICT (room_id)
-                DO UPDATE SET session_data = $2
-            "#,
-        )
-    }
+/// Trigger function writing the mutated row to the audit log
+///
+/// The arguments of the trigger are the primary key columns of the table.
+#[cfg(feature = "postgres")]
+const POSTGRES_AUDIT_FUNCTION: &str = r#"
+    CREATE OR REPLACE FUNCTION statestore_audit_log_row() RETURNS trigger AS $$
+    DECLARE
+        old_row JSONB;
+        new_row JSONB;
+    BEGIN
+        IF TG_OP <> 'INSERT' THEN
+            old_row := to_jsonb(OLD);
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            new_row := to_jsonb(NEW);
+        END IF;
+        INSERT INTO statestore_audit_log
+            (table_name, operation, row_key, changed_at, old_hash, new_hash)
+        VALUES (
+            TG_TABLE_NAME,
+            TG_OP,
+            (
+                SELECT jsonb_agg(COALESCE(new_row, old_row)->k.name ORDER BY k.ordinal)::TEXT
+                FROM unnest(TG_ARGV) WITH ORDINALITY AS k(name, ordinal)
+            ),
+            EXTRACT(EPOCH FROM now())::BIGINT,
+            encode(sha256(convert_to(old_row::TEXT, 'UTF8')), 'hex'),
+            encode(sha256(convert_to(new_row::TEXT, 'UTF8')), 'hex')
+        );
+        RETURN NULL;
+    END
+    $$ LANGUAGE plpgsql
+"#;
 
-    /// Stores a gossip request
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed recipient ID
-    /// * `$2` - The hashed request ID
-    /// * `$3` - The hashed secret request info
-    /// * `$4` - Whether or not the request has been sent
-    /// * `$5` - The encrypted request data
-    #[cfg(feature = "e2e-encryption")]
-    fn gossip_request_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_gossip_request (recipient_id, request_id, info_key, sent_out, gossip_data)
-                VALUES ($1, $2, $3, $4, $5)
-                ON CONFLICT (request_id)
-                DO UPDATE SET recipient_id = $1, info_key = $3, sent_out = $4, gossip_data = $5
-            "#,
-        )
+/// Checks that a snapshot has the same schema version as the database it is loaded into
+///
+/// # Errors
+/// This function will return an error if the schema versions differ
+fn check_snapshot_version<R: Row>(snapshot: i64, database: Option<R>) -> Result<()>
+where
+    for<'a> &'a str: ColumnIndex<R>,
+    i64: Type<R::Database> + for<'a> Decode<'a, R::Database>,
+{
+    let database = database
+        .map(|row| row.try_get::<'_, i64, _>("schema_version"))
+        .transpose()?
+        .unwrap_or_default();
+    if snapshot == database {
+        Ok(())
+    } else {
+        Err(SQLStoreError::InvalidSnapshot(format!(
+            "snapshot has schema version {snapshot}, but the database has schema version {database}"
+        )))
     }
+}
 
-    /// Upserts a cryptographic identity
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed user ID
-    /// * `$2` - The encrypted identity data
-    #[cfg(feature = "e2e-encryption")]
-    fn identity_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_identity (user_id, identity_data)
-                VALUES ($1, $2)
-                ON CONFLICT (user_id) DO UPDATE SET identity_data = $2
-            "#,
-        )
+/// Copies the state store tables of the attached `snapshot` database
+///
+/// # Errors
+/// This function will return an error if the query fails or the snapshot has a different
+/// schema version
+#[cfg(feature = "sqlite")]
+async fn seed_from_attached_sqlite(
+    conn: &mut sqlx::SqliteConnection,
+    table_prefix: &'static str,
+) -> Result<()> {
+    let mut txn = conn.begin().await?;
+    let snapshot_version: Option<i64> = sqlx::query_scalar(
+        "SELECT schema_version FROM snapshot.statestore_schema_compat WHERE id = 0",
+    )
+    .fetch_optional(timed(&mut txn, table_prefix))
+    .await?;
+    let snapshot_version = snapshot_version
+        .ok_or_else(|| SQLStoreError::InvalidSnapshot("missing schema version".to_owned()))?;
+    let row = <sqlx::Sqlite as SupportedDatabase>::schema_compat_fetch_query()
+        .fetch_optional(timed(&mut txn, table_prefix))
+        .await?;
+    check_snapshot_version(snapshot_version, row)?;
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM snapshot.sqlite_master WHERE type = 'table' ORDER BY name",
+    )
+    .fetch_all(&mut txn)
+    .await?;
+    for table in tables
+        .iter()
+        .filter(|table| is_seeded_table(table, table_prefix))
+    {
+        let sql = format!("INSERT INTO main.\"{table}\" SELECT * FROM snapshot.\"{table}\"");
+        sqlx::query(&sql).execute(&mut txn).await?;
     }
+    txn.commit().await?;
+    Ok(())
+}
 
-    /// Upserts a device
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed user ID
-    /// * `$2` - The hashed device ID
-    /// * `$3` - The encrypted device data
-    #[cfg(feature = "e2e-encryption")]
-    fn device_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_device (user_id, device_id, device_info)
-                VALUES ($1, $2, $3)
-                ON CONFLICT (user_id, device_id) DO UPDATE SET device_info = $3
-            "#,
-        )
-    }
+/// Helper trait that marks an SQL-Compatible type
+pub trait SqlType<DB: Database>:
+    for<'a> Encode<'a, DB> + for<'a> Decode<'a, DB> + Type<DB>
+{
+}
+impl<DB: Database, T> SqlType<DB> for T where
+    T: for<'a> Encode<'a, DB> + for<'a> Decode<'a, DB> + Type<DB>
+{
+}
 
-    /// Deletes a device
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed user ID
-    /// * `$2` - The hashed device ID
-    #[cfg(feature = "e2e-encryption")]
-    fn device_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                DELETE FROM cryptostore_device
-                WHERE user_id = $1 AND device_id = $2
-            "#,
-        )
-    }
+/// Helper trait for a borrowed SQL-Compatible type
+pub trait BorrowedSqlType<'a, DB: Database>:
+    Encode<'a, DB> + Decode<'a, DB> + Type<DB> + 'a
+{
+}
+impl<'a, DB: Database, T> BorrowedSqlType<'a, DB> for T where
+    T: Encode<'a, DB> + Decode<'a, DB> + Type<DB> + 'a
+{
+}
+
+/// Supported Database trait
+///
+/// It contains the database-specific functionality that is not a query of a single domain, like
+/// migrations and snapshots. The queries of the stores are provided by the supertraits in
+/// [`crate::queries`], such as [`KvQueries`] or [`CryptoQueries`].
+///
+/// The trait is implemented for postgres and sqlite. Other databases supported by SQLx can be
+/// added by implementing it and all query traits in a downstream crate. The default queries are
+/// written for postgres, and only the queries that do not work on the database need to be
+/// overridden, see the sqlite implementation for an example. Queries should be created with
+/// [`prefixed_query`] and run with the table prefix passed to the functions of this trait.
+/// [`SupportedDatabase::run_migrations`] should apply the migrations with [`apply_migrations`]
+/// and has to call [`record_schema_compat`].
+///
+/// New queries with default implementations may be added in minor releases, and the schema
+/// changes of new migrations have to be ported to other databases before upgrading.
+#[allow(single_use_lifetimes)]
+pub trait SupportedDatabase:
+    Database
+    + KvQueries
+    + SyncQueries
+    + MediaQueries
+    + RoomQueries
+    + MemberQueries
+    + StateQueries
+    + MaintenanceQueries
+    + CryptoQueries
+    + RowQueries
+{
+    /// Returns the migrator for the current database type
+    fn get_migrator() -> &'static Migrator;
 
-    /// Query to get all sessions for a sender key
+    /// Returns the SQL statement creating the migrations bookkeeping table of sqlx
     ///
-    /// # Arguments
-    /// * `$1` - The hashed sender key
-    #[cfg(feature = "e2e-encryption")]
-    fn sessions_for_user_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT session_data FROM cryptostore_session
-                WHERE sender_key = $1
-            "#,
-        )
+    /// The table is renamed along with the tables of the store if a table prefix is used.
+    fn migrations_table_sql() -> &'static str {
+        r#"CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    installed_on TIMESTAMPTZ NOT NULL DEFAULT now(),
+    success BOOLEAN NOT NULL,
+    checksum BYTEA NOT NULL,
+    execution_time BIGINT NOT NULL
+);"#
     }
 
-    /// Fetch an inbound group session
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed room ID
-    /// * `$2` - The hashed sender key
-    /// * `$3` - The hashed session id
-    #[cfg(feature = "e2e-encryption")]
-    fn inbound_group_session_fetch_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT session_data FROM cryptostore_inbound_group_session
-                WHERE room_id = $1 AND session_id = $2
-            "#,
-        )
+    /// Returns an SQL literal for the given bytes
+    fn bytes_literal(bytes: &[u8]) -> String {
+        format!("'\\x{}'", hex_encode(bytes))
     }
 
-    /// Fetch all inbound group sessions
-    #[cfg(feature = "e2e-encryption")]
-    fn inbound_group_sessions_fetch_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT session_data FROM cryptostore_inbound_group_session
-            "#,
-        )
+    /// Returns the SQL statement that sets the statement timeout of a connection
+    fn statement_timeout_sql(timeout: Duration) -> String {
+        format!("SET statement_timeout = {}", timeout.as_millis())
     }
 
-    /// Load the outbound group session for a room
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed room ID
-    #[cfg(feature = "e2e-encryption")]
-    fn outbound_group_session_load_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT session_data FROM cryptostore_outbound_group_session
-                WHERE room_id = $1
-            "#,
-        )
+    /// Returns the SQL statement that refreshes the query planner statistics
+    fn optimize_sql() -> &'static str {
+        "ANALYZE"
     }
 
-    /// Upserts a tracked user
+    /// Applies all pending migrations while holding a database-wide lock
     ///
-    /// # Arguments
-    /// * `$1` - The hashed user ID
-    /// * `$2` - The encrypted tracked user data
-    #[cfg(feature = "e2e-encryption")]
-    fn tracked_user_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                INSERT INTO cryptostore_tracked_user (user_id, tracked_user_data)
-                VALUES ($1, $2)
-                ON CONFLICT (user_id) DO UPDATE SET tracked_user_data = $2
-            "#,
-        )
-    }
+    /// This makes sure that only one instance applies migrations when several instances are
+    /// started concurrently against the same database.
+    fn run_migrations(db: &Pool<Self>, table_prefix: &'static str) -> BoxFuture<'_, Result<()>>;
 
-    /// Fetch a device
+    /// Writes a consistent copy of the store to the file at the given path
     ///
-    /// # Arguments
-    /// * `$1` - The hashed user ID
-    /// * `$2` - The hashed device ID
-    #[cfg(feature = "e2e-encryption")]
-    fn device_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT device_info FROM cryptostore_device
-                WHERE user_id = $1 AND device_id = $2
-            "#,
-        )
-    }
+    /// This does not block concurrent writers.
+    fn snapshot_to<'a>(
+        db: &'a Pool<Self>,
+        path: &'a Path,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'a, Result<()>>;
 
-    /// Fetch all devices of a user
+    /// Loads the state store tables of a snapshot written by [`SupportedDatabase::snapshot_to`]
+    fn seed_from_snapshot<'a>(
+        db: &'a Pool<Self>,
+        path: &'a Path,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Installs or removes the triggers that write mutations of the state store tables to the
+    /// audit log
     ///
-    /// # Arguments
-    /// * `$1` - The hashed user ID
-    #[cfg(feature = "e2e-encryption")]
-    fn devices_for_user_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT device_info FROM cryptostore_device
-                WHERE user_id = $1
-            "#,
-        )
-    }
+    /// See [`StateStore::enable_audit_log`](crate::StateStore::enable_audit_log).
+    fn set_audit_triggers(
+        db: &Pool<Self>,
+        enabled: bool,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'_, Result<()>>;
 
-    /// Retrieves all tracked users
-    #[cfg(feature = "e2e-encryption")]
-    fn tracked_users_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
+    /// Returns a query for listing the versions and checksums of all applied migrations
+    fn applied_migrations_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
             r#"
-                SELECT tracked_user_data FROM cryptostore_tracked_user
+                SELECT version, checksum FROM _sqlx_migrations
+                WHERE success
             "#,
         )
     }
 
-    /// Retrieves the cryptographic identity of a user
+    /// Returns a query for recording an applied migration
     ///
     /// # Arguments
-    /// * `$1` - The hashed user ID
-    #[cfg(feature = "e2e-encryption")]
-    fn identity_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
+    /// * `$1` - The version of the migration
+    /// * `$2` - The description of the migration
+    /// * `$3` - The checksum of the migration
+    /// * `$4` - How long applying the migration took, in nanoseconds
+    fn migration_record_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
             r#"
-                SELECT identity_data FROM cryptostore_identity
-                WHERE user_id = $1
+                INSERT INTO _sqlx_migrations
+                    (version, description, success, checksum, execution_time)
+                VALUES ($1, $2, TRUE, $3, $4)
             "#,
         )
     }
 
-    /// Checks whether a message is known
-    ///
-    /// # Arguments
-    /// * `$1` - The sender key
-    /// * `$2` - The message hash
-    #[cfg(feature = "e2e-encryption")]
-    fn message_known_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
+    /// Returns a query for listing the tables of the state store and the cryptostore
+    fn store_tables_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
             r#"
-                SELECT 1 FROM cryptostore_message_hash
-                WHERE sender_key = $1 AND message_hash = $2
+                SELECT table_name::text AS table_name FROM information_schema.tables
+                WHERE table_schema = current_schema() AND table_type = 'BASE TABLE'
+                    AND (table_name LIKE 'statestore_%' OR table_name LIKE 'cryptostore_%')
+                ORDER BY table_name
             "#,
         )
     }
 
-    /// Retrieves a gossip equest by ID
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed request ID
-    #[cfg(feature = "e2e-encryption")]
-    fn gossip_request_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
+    /// Returns a query for loading the schema compatibility information
+    fn schema_compat_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
             r#"
-                SELECT gossip_data FROM cryptostore_gossip_request
-                WHERE request_id = $1
+                SELECT schema_version, min_reader_version FROM statestore_schema_compat
+                WHERE id = 0
             "#,
         )
     }
 
-    /// Retrieves a gossip equest by info
+    /// Returns a query for storing the schema compatibility information
     ///
     /// # Arguments
-    /// * `$1` - The hashed request info
-    #[cfg(feature = "e2e-encryption")]
-    fn gossip_request_info_fetch_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
+    /// * `$1` - The schema version
+    /// * `$2` - The oldest schema version that is able to use the database
+    fn schema_compat_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
             r#"
-                SELECT gossip_data FROM cryptostore_gossip_request
-                WHERE info_key = $1
+                INSERT INTO statestore_schema_compat (id, schema_version, min_reader_version)
+                VALUES (0, $1, $2)
+                ON CONFLICT (id) DO UPDATE SET schema_version = $1, min_reader_version = $2
             "#,
         )
     }
 
-    /// Retrieves gossip requests by sent state
+    /// Deletes all data of the state store
     ///
-    /// # Arguments
-    /// * `$1` - The sent state
-    #[cfg(feature = "e2e-encryption")]
-    fn gossip_requests_sent_state_fetch_query<'q>(
-    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                SELECT gossip_data FROM cryptostore_gossip_request
-                WHERE sent_out = $1
-            "#,
-        )
+    /// The schema compatibility information and the key-value table, which holds cryptostore
+    /// data, are kept.
+    #[must_use]
+    fn state_store_clear_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>>
+    {
+        vec![
+            prefixed_query("DELETE FROM statestore_rooms"),
+            prefixed_query("DELETE FROM statestore_accountdata"),
+            prefixed_query("DELETE FROM statestore_presence"),
+            prefixed_query("DELETE FROM statestore_members"),
+            prefixed_query("DELETE FROM statestore_display_names"),
+            prefixed_query("DELETE FROM statestore_member_history"),
+            prefixed_query("DELETE FROM statestore_profiles"),
+            prefixed_query("DELETE FROM statestore_state"),
+            prefixed_query("DELETE FROM statestore_receipts"),
+            prefixed_query("DELETE FROM statestore_room_tags"),
+            prefixed_query("DELETE FROM statestore_direct_chats"),
+            prefixed_query("DELETE FROM statestore_ignored_users"),
+            prefixed_query("DELETE FROM statestore_push_rules"),
+            prefixed_query("DELETE FROM statestore_space_edges"),
+            prefixed_query("DELETE FROM statestore_aliases"),
+            prefixed_query("DELETE FROM statestore_room_upgrades"),
+            prefixed_query("DELETE FROM statestore_media"),
+            prefixed_query("DELETE FROM statestore_media_blobs"),
+            prefixed_query("DELETE FROM statestore_negative_cache"),
+            prefixed_query("DELETE FROM statestore_filters"),
+            prefixed_query("DELETE FROM statestore_server_cache"),
+            prefixed_query("DELETE FROM statestore_sync_tokens"),
+            prefixed_query("DELETE FROM statestore_sliding_sync"),
+            prefixed_query("DELETE FROM statestore_sliding_sync_lists"),
+            prefixed_query("DELETE FROM statestore_sliding_sync_subscriptions"),
+            prefixed_query("DELETE FROM statestore_custom_values"),
+            prefixed_query("DELETE FROM statestore_quarantine"),
+            prefixed_query("DELETE FROM statestore_room_tombstones"),
+            prefixed_query("DELETE FROM statestore_rooms_tombstone"),
+            prefixed_query("DELETE FROM statestore_accountdata_tombstone"),
+            prefixed_query("DELETE FROM statestore_members_tombstone"),
+            prefixed_query("DELETE FROM statestore_display_names_tombstone"),
+            prefixed_query("DELETE FROM statestore_state_tombstone"),
+            prefixed_query("DELETE FROM statestore_receipts_tombstone"),
+            prefixed_query("DELETE FROM statestore_room_tags_tombstone"),
+        ]
     }
 
-    /// Deletes gossip request by transaction ID
-    ///
-    /// # Arguments
-    /// * `$1` - The hashed transaction ID
+    /// Deletes all data of the cryptostore, including the store cipher
     #[cfg(feature = "e2e-encryption")]
-    fn gossip_request_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                DELETE FROM cryptostore_gossip_request
-                WHERE request_id = $1
-            "#,
-        )
+    #[must_use]
+    fn crypto_store_clear_queries<'q>(
+    ) -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query("DELETE FROM statestore_kv"),
+            prefixed_query("DELETE FROM cryptostore_account"),
+            prefixed_query("DELETE FROM cryptostore_dehydrated_device"),
+            prefixed_query("DELETE FROM cryptostore_session"),
+            prefixed_query("DELETE FROM cryptostore_message_hash"),
+            prefixed_query("DELETE FROM cryptostore_inbound_group_session"),
+            prefixed_query("DELETE FROM cryptostore_outbound_group_session"),
+            prefixed_query("DELETE FROM cryptostore_gossip_request"),
+            prefixed_query("DELETE FROM cryptostore_identity"),
+            prefixed_query("DELETE FROM cryptostore_private_identity"),
+            prefixed_query("DELETE FROM cryptostore_device"),
+            prefixed_query("DELETE FROM cryptostore_tracked_user"),
+            prefixed_query("DELETE FROM cryptostore_withheld_sessions"),
+            prefixed_query("DELETE FROM cryptostore_secrets"),
+            prefixed_query("DELETE FROM cryptostore_room_settings"),
+            prefixed_query("DELETE FROM cryptostore_lease_locks"),
+            prefixed_query("DELETE FROM cryptostore_verification"),
+        ]
     }
 }
 
@@ -818,8 +989,269 @@ impl SupportedDatabase for sqlx::postgres::Postgres {
             migrations: sqlx::migrate!("./migrations/postgres").migrations,
             ignore_missing: true,
         };
+        #[cfg(feature = "cockroach")]
+        {
+            use std::borrow::Cow;
+
+            use once_cell::sync::OnceCell;
+            use sqlx::migrate::Migration;
+
+            /// The migrator for CockroachDB
+            static COCKROACH_MIGRATOR: OnceCell<Migrator> = OnceCell::new();
+            COCKROACH_MIGRATOR.get_or_init(|| Migrator {
+                migrations: MIGRATOR
+                    .iter()
+                    .map(|migration| Migration {
+                        sql: Cow::Owned(adapt_migration_for_cockroach(&migration.sql)),
+                        ..migration.clone()
+                    })
+                    .collect(),
+                ignore_missing: MIGRATOR.ignore_missing,
+            })
+        }
+        #[cfg(not(feature = "cockroach"))]
         &MIGRATOR
     }
+
+    #[cfg(feature = "cockroach")]
+    fn run_migrations(db: &Pool<Self>, table_prefix: &'static str) -> BoxFuture<'_, Result<()>> {
+        Box::pin(run_cockroach_migrations(db, table_prefix))
+    }
+
+    #[cfg(not(feature = "cockroach"))]
+    fn run_migrations(db: &Pool<Self>, table_prefix: &'static str) -> BoxFuture<'_, Result<()>> {
+        /// Key of the advisory lock held while migrating ("matrixsq")
+        const MIGRATION_LOCK_KEY: i64 = 0x6d61_7472_6978_7371;
+
+        Box::pin(async move {
+            let mut conn = db.acquire().await?;
+            sqlx::query("SELECT pg_advisory_lock($1)")
+                .bind(MIGRATION_LOCK_KEY)
+                .execute(&mut *conn)
+                .await?;
+            let result = match apply_migrations::<Self>(&mut conn, table_prefix).await {
+                Ok(()) => record_schema_compat(db, table_prefix).await,
+                Err(error) => Err(error),
+            };
+            sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(MIGRATION_LOCK_KEY)
+                .execute(&mut *conn)
+                .await?;
+            result
+        })
+    }
+
+    fn snapshot_to<'a>(
+        db: &'a Pool<Self>,
+        path: &'a Path,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'a, Result<()>> {
+        // The tables are copied in the same format as `pg_dump --data-only`, so that the snapshot
+        // can be loaded into a migrated database with `psql`
+        Box::pin(async move {
+            let mut txn = db.begin().await?;
+            sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                .execute(&mut txn)
+                .await?;
+            let tables = Self::store_tables_query()
+                .fetch_all(timed(&mut txn, table_prefix))
+                .await?
+                .iter()
+                .map(|row| row.try_get("table_name"))
+                .collect::<Result<Vec<String>, _>>()?;
+            let schema_version: i64 = Self::schema_compat_fetch_query()
+                .fetch_one(timed(&mut txn, table_prefix))
+                .await?
+                .try_get("schema_version")?;
+            let mut file = File::create(path).await?;
+            file.write_all(format!("{SNAPSHOT_HEADER}{schema_version}\n").as_bytes())
+                .await?;
+            for table in tables {
+                file.write_all(format!("COPY \"{table}\" FROM stdin;\n").as_bytes())
+                    .await?;
+                let mut data = txn
+                    .copy_out_raw(&format!("COPY \"{table}\" TO STDOUT"))
+                    .await?;
+                while let Some(chunk) = data.try_next().await? {
+                    file.write_all(&chunk).await?;
+                }
+                file.write_all(b"\\.\n\n").await?;
+            }
+            file.sync_all().await?;
+            txn.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn seed_from_snapshot<'a>(
+        db: &'a Pool<Self>,
+        path: &'a Path,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'a, Result<()>> {
+        /// Size of the chunks of table data sent to the database
+        const CHUNK_SIZE: usize = 64 * 1024;
+
+        Box::pin(async move {
+            let mut file = BufReader::new(File::open(path).await?);
+            let mut line = Vec::new();
+            file.read_until(b'\n', &mut line).await?;
+            let snapshot_version = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| line.trim_end().strip_prefix(SNAPSHOT_HEADER))
+                .and_then(|version| version.parse().ok())
+                .ok_or_else(|| SQLStoreError::InvalidSnapshot("missing header".to_owned()))?;
+            let mut txn = db.begin().await?;
+            let row = Self::schema_compat_fetch_query()
+                .fetch_optional(timed(&mut txn, table_prefix))
+                .await?;
+            check_snapshot_version(snapshot_version, row)?;
+            loop {
+                line.clear();
+                if file.read_until(b'\n', &mut line).await? == 0 {
+                    break;
+                }
+                if line == b"\n" {
+                    continue;
+                }
+                let table = std::str::from_utf8(&line)
+                    .ok()
+                    .and_then(|line| line.strip_prefix("COPY \""))
+                    .and_then(|line| line.strip_suffix("\" FROM stdin;\n"))
+                    .ok_or_else(|| {
+                        SQLStoreError::InvalidSnapshot("expected a table header".to_owned())
+                    })?
+                    .to_owned();
+                let mut copy = if is_seeded_table(&table, table_prefix) {
+                    Some(
+                        txn.copy_in_raw(&format!("COPY \"{table}\" FROM STDIN"))
+                            .await?,
+                    )
+                } else {
+                    None
+                };
+                let mut chunk = Vec::new();
+                loop {
+                    line.clear();
+                    if file.read_until(b'\n', &mut line).await? == 0 {
+                        return Err(SQLStoreError::InvalidSnapshot(format!(
+                            "data of table {table} is truncated"
+                        )));
+                    }
+                    if line == b"\\.\n" {
+                        break;
+                    }
+                    chunk.extend_from_slice(&line);
+                    if chunk.len() >= CHUNK_SIZE {
+                        if let Some(copy) = &mut copy {
+                            copy.send(chunk.as_slice()).await?;
+                        }
+                        chunk.clear();
+                    }
+                }
+                if let Some(mut copy) = copy {
+                    if !chunk.is_empty() {
+                        copy.send(chunk.as_slice()).await?;
+                    }
+                    copy.finish().await?;
+                    // COPY does not advance identity sequences, so later inserts would reuse the
+                    // IDs of the copied rows
+                    let identity_columns: Vec<String> = sqlx::query_scalar(
+                        r#"
+                            SELECT column_name::text FROM information_schema.columns
+                            WHERE table_schema = current_schema() AND table_name = $1
+                                AND is_identity = 'YES'
+                        "#,
+                    )
+                    .bind(&table)
+                    .fetch_all(&mut txn)
+                    .await?;
+                    for column in identity_columns {
+                        let quoted_table = quote_identifier(&table);
+                        let quoted_column = quote_identifier(&column);
+                        sqlx::query(&format!(
+                            "SELECT setval(pg_get_serial_sequence($1, $2), \
+                             COALESCE(MAX({quoted_column}), 0) + 1, false) FROM {quoted_table}"
+                        ))
+                        .bind(&quoted_table)
+                        .bind(&column)
+                        .execute(&mut txn)
+                        .await?;
+                    }
+                }
+            }
+            txn.commit().await?;
+            Ok(())
+        })
+    }
+
+    fn set_audit_triggers(
+        db: &Pool<Self>,
+        enabled: bool,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let mut txn = db.begin().await?;
+            let tables: Vec<String> = sqlx::query_scalar(
+                r#"
+                    SELECT table_name::text FROM information_schema.tables
+                    WHERE table_schema = current_schema() AND table_type = 'BASE TABLE'
+                      AND table_name LIKE 'statestore_%'
+                    ORDER BY table_name
+                "#,
+            )
+            .fetch_all(timed(&mut txn, table_prefix))
+            .await?;
+            if enabled {
+                timed(&mut txn, table_prefix)
+                    .execute(POSTGRES_AUDIT_FUNCTION)
+                    .await?;
+            }
+            let function = apply_table_prefix("statestore_audit_log_row", table_prefix);
+            for table in tables
+                .iter()
+                .filter(|table| is_audited_table(table, table_prefix))
+            {
+                let quoted = quote_identifier(table);
+                (&mut txn)
+                    .execute(format!("DROP TRIGGER IF EXISTS audit_log ON {quoted}").as_str())
+                    .await?;
+                if !enabled {
+                    continue;
+                }
+                let key_columns: Vec<String> = sqlx::query_scalar(
+                    r#"
+                        SELECT kcu.column_name::text
+                        FROM information_schema.table_constraints tc
+                        JOIN information_schema.key_column_usage kcu
+                            ON kcu.constraint_schema = tc.constraint_schema
+                            AND kcu.constraint_name = tc.constraint_name
+                        WHERE tc.constraint_type = 'PRIMARY KEY'
+                            AND tc.table_schema = current_schema() AND tc.table_name = $1
+                        ORDER BY kcu.ordinal_position
+                    "#,
+                )
+                .bind(table)
+                .fetch_all(&mut txn)
+                .await?;
+                let arguments = key_columns
+                    .iter()
+                    .map(|column| format!("'{}'", column.replace('\'', "''")))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (&mut txn)
+                    .execute(
+                        format!(
+                            "CREATE TRIGGER audit_log AFTER INSERT OR UPDATE OR DELETE ON {quoted} \
+                             FOR EACH ROW EXECUTE PROCEDURE {function}({arguments})"
+                        )
+                        .as_str(),
+                    )
+                    .await?;
+            }
+            txn.commit().await?;
+            Ok(())
+        })
+    }
 }
 
 #[cfg(feature = "sqlite")]
@@ -833,24 +1265,233 @@ impl SupportedDatabase for sqlx::sqlite::Sqlite {
         &MIGRATOR
     }
 
-    fn media_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
-            r#"
-                UPDATE statestore_media
-                SET last_access = datetime(CURRENT_TIMESTAMP, 'localtime')
-                WHERE media_url = $1
-                RETURNING media_data
-            "#,
-        )
+    fn migrations_table_sql() -> &'static str {
+        r#"CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    success BOOLEAN NOT NULL,
+    checksum BLOB NOT NULL,
+    execution_time BIGINT NOT NULL
+);"#
     }
 
-    fn media_insert_query_1<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
-        sqlx::query(
+    fn store_tables_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
             r#"
-                INSERT INTO statestore_media (media_url, media_data, last_access)
-                VALUES ($1, $2, datetime(CURRENT_TIMESTAMP, 'localtime'))
-                ON CONFLICT (media_url) DO NOTHING
+                SELECT name AS table_name FROM sqlite_master
+                WHERE type = 'table'
+                    AND (name LIKE 'statestore_%' OR name LIKE 'cryptostore_%')
+                ORDER BY name
             "#,
         )
     }
+
+    fn bytes_literal(bytes: &[u8]) -> String {
+        format!("X'{}'", hex_encode(bytes))
+    }
+
+    fn statement_timeout_sql(timeout: Duration) -> String {
+        format!("PRAGMA busy_timeout = {}", timeout.as_millis())
+    }
+
+    fn optimize_sql() -> &'static str {
+        "PRAGMA optimize"
+    }
+
+    fn run_migrations(db: &Pool<Self>, table_prefix: &'static str) -> BoxFuture<'_, Result<()>> {
+        // SQLite has no advisory locks. Every migration is applied in its own transaction, which
+        // takes the database write lock.
+        Box::pin(async move {
+            let mut conn = db.acquire().await?;
+            apply_migrations::<Self>(&mut conn, table_prefix).await?;
+            drop(conn);
+            record_schema_compat(db, table_prefix).await
+        })
+    }
+
+    fn snapshot_to<'a>(
+        db: &'a Pool<Self>,
+        path: &'a Path,
+        _table_prefix: &'static str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let path = path.to_str().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "path is not valid UTF-8")
+            })?;
+            sqlx::query("VACUUM INTO $1").bind(path).execute(db).await?;
+            Ok(())
+        })
+    }
+
+    fn seed_from_snapshot<'a>(
+        db: &'a Pool<Self>,
+        path: &'a Path,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let path = path.to_str().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "path is not valid UTF-8")
+            })?;
+            // Databases cannot be attached within a transaction, and attachments only apply to
+            // the connection they were made on
+            let mut conn = db.acquire().await?;
+            sqlx::query("ATTACH DATABASE $1 AS snapshot")
+                .bind(path)
+                .execute(&mut *conn)
+                .await?;
+            let result = seed_from_attached_sqlite(&mut conn, table_prefix).await;
+            sqlx::query("DETACH DATABASE snapshot")
+                .execute(&mut *conn)
+                .await?;
+            result
+        })
+    }
+    fn set_audit_triggers(
+        db: &Pool<Self>,
+        enabled: bool,
+        table_prefix: &'static str,
+    ) -> BoxFuture<'_, Result<()>> {
+        // SQLite has no hash functions, so the hashes of the rows are not recorded
+        Box::pin(async move {
+            let mut txn = db.begin().await?;
+            let tables: Vec<String> = sqlx::query_scalar(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'statestore_%' ORDER BY name",
+            )
+            .fetch_all(timed(&mut txn, table_prefix))
+            .await?;
+            let audit_log =
+                quote_identifier(&apply_table_prefix("statestore_audit_log", table_prefix));
+            for table in tables
+                .iter()
+                .filter(|table| is_audited_table(table, table_prefix))
+            {
+                let key_columns: Vec<String> = sqlx::query_scalar(
+                    "SELECT name FROM pragma_table_info($1) WHERE pk > 0 ORDER BY pk",
+                )
+                .bind(table)
+                .fetch_all(&mut txn)
+                .await?;
+                for (operation, row) in [("INSERT", "NEW"), ("UPDATE", "NEW"), ("DELETE", "OLD")] {
+                    let trigger =
+                        quote_identifier(&format!("{table}_audit_{}", operation.to_lowercase()));
+                    (&mut txn)
+                        .execute(format!("DROP TRIGGER IF EXISTS {trigger}").as_str())
+                        .await?;
+                    if !enabled {
+                        continue;
+                    }
+                    // JSON cannot hold blobs, so binary keys are recorded as hex
+                    let key = if key_columns.is_empty() {
+                        "NULL".to_owned()
+                    } else {
+                        let values = key_columns
+                            .iter()
+                            .map(|column| {
+                                let column = format!("{row}.{}", quote_identifier(column));
+                                format!(
+                                    "CASE WHEN typeof({column}) = 'blob' THEN hex({column}) ELSE {column} END"
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!("json_array({values})")
+                    };
+                    (&mut txn)
+                        .execute(
+                            format!(
+                                "CREATE TRIGGER {trigger} AFTER {operation} ON {} FOR EACH ROW \
+                                 BEGIN \
+                                     INSERT INTO {audit_log} (table_name, operation, row_key, changed_at) \
+                                     VALUES ('{}', '{operation}', {key}, CAST(strftime('%s', 'now') AS INTEGER)); \
+                                 END",
+                                quote_identifier(table),
+                                table.replace('\'', "''"),
+                            )
+                            .as_str(),
+                        )
+                        .await?;
+                }
+            }
+            txn.commit().await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_table_prefix, table_prefix};
+
+    #[test]
+    fn test_apply_table_prefix() {
+        assert_eq!(
+            apply_table_prefix(
+                "SELECT s.event FROM statestore_state s JOIN cryptostore_kv ON true",
+                "app_"
+            ),
+            "SELECT s.event FROM app_statestore_state s JOIN app_cryptostore_kv ON true"
+        );
+        assert_eq!(
+            apply_table_prefix(
+                "CREATE INDEX statestore_members_room ON statestore_members (room_id)",
+                "app_"
+            ),
+            "CREATE INDEX app_statestore_members_room ON app_statestore_members (room_id)"
+        );
+        assert_eq!(
+            apply_table_prefix("SELECT my_statestore_x FROM t", "app_"),
+            "SELECT my_statestore_x FROM t"
+        );
+        assert_eq!(
+            apply_table_prefix("SELECT version FROM _sqlx_migrations", "app_"),
+            "SELECT version FROM app_sqlx_migrations"
+        );
+        assert_eq!(
+            apply_table_prefix(
+                "SELECT * FROM app_statestore_state, app_sqlx_migrations",
+                "app_"
+            ),
+            "SELECT * FROM app_statestore_state, app_sqlx_migrations"
+        );
+        assert_eq!(
+            apply_table_prefix("SELECT * FROM statestore_state, _sqlx_migrations", ""),
+            "SELECT * FROM statestore_state, _sqlx_migrations"
+        );
+    }
+
+    #[test]
+    fn test_table_prefix() {
+        assert_eq!(table_prefix("").unwrap(), "");
+        let prefix = table_prefix("app_").unwrap();
+        assert!(std::ptr::eq(prefix, table_prefix("app_").unwrap()));
+        assert!(table_prefix("1app").is_err());
+        assert!(table_prefix("app;").is_err());
+    }
+
+    #[cfg(feature = "cockroach")]
+    #[test]
+    fn test_split_sql_statements() {
+        assert_eq!(
+            super::split_sql_statements(
+                "-- Leading comment; with a semicolon\nCREATE TABLE a (b TEXT DEFAULT 'x;y');\nCREATE FUNCTION f() RETURNS trigger AS $$\nBEGIN\n    RETURN NEW;\nEND;\n$$ LANGUAGE plpgsql;\n/* a; b */ SELECT \"c;d\" FROM a WHERE b = $1;\n-- trailing comment\n"
+            ),
+            [
+                "-- Leading comment; with a semicolon\nCREATE TABLE a (b TEXT DEFAULT 'x;y')",
+                "CREATE FUNCTION f() RETURNS trigger AS $$\nBEGIN\n    RETURN NEW;\nEND;\n$$ LANGUAGE plpgsql",
+                "/* a; b */ SELECT \"c;d\" FROM a WHERE b = $1",
+            ]
+        );
+    }
+
+    #[cfg(feature = "cockroach")]
+    #[test]
+    fn test_adapt_migration_for_cockroach() {
+        assert_eq!(
+            super::adapt_migration_for_cockroach(
+                "ALTER TABLE statestore_receipts\nADD COLUMN thread_id TEXT NOT NULL DEFAULT '';\nALTER TABLE statestore_receipts DROP CONSTRAINT statestore_receipts_pkey;\nALTER TABLE statestore_receipts ADD PRIMARY KEY (room_id, thread_id);\n"
+            ),
+            "ALTER TABLE statestore_receipts\nADD COLUMN thread_id TEXT NOT NULL DEFAULT '';\nALTER TABLE statestore_receipts ALTER PRIMARY KEY USING COLUMNS (room_id, thread_id);\n"
+        );
+    }
 }
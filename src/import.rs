@@ -0,0 +1,488 @@
+//! Importing the stores of the official `matrix-sdk-sqlite` crate
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use crate::{
+    helpers::{BorrowedSqlType, SqlType},
+    telemetry::timed,
+    Result, SQLStoreError, StateStore, SupportedDatabase,
+};
+use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo, StateChanges};
+use matrix_sdk_crypto::{
+    olm::{InboundGroupSession, PrivateCrossSigningIdentity, Session},
+    store::{Changes, DeviceChanges},
+    ReadOnlyAccount, ReadOnlyDevice,
+};
+use matrix_sdk_store_encryption::StoreCipher;
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::Receipt,
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent, GlobalAccountDataEventType,
+        RoomAccountDataEventType, StateEventType,
+    },
+    serde::Raw,
+    OwnedRoomId,
+};
+use serde::de::DeserializeOwned;
+use sqlx::{
+    database::HasArguments,
+    sqlite::{SqliteConnectOptions, SqlitePool},
+    types::Json,
+    ColumnIndex, Database, Executor, IntoArguments, Row, Transaction,
+};
+use tracing::warn;
+
+/// File name of the state store database of `matrix-sdk-sqlite`
+const STATE_DATABASE_NAME: &str = "matrix-sdk-state.sqlite3";
+/// File name of the crypto store database of `matrix-sdk-sqlite`
+const CRYPTO_DATABASE_NAME: &str = "matrix-sdk-crypto.sqlite3";
+/// Table of `matrix-sdk-sqlite` holding state events, used for hashing room IDs
+const STATE_EVENT_TABLE: &str = "state_event";
+/// Table of `matrix-sdk-sqlite` holding room account data, used for hashing room IDs
+const ROOM_ACCOUNT_DATA_TABLE: &str = "room_account_data";
+/// Newest schema version of the `matrix-sdk-sqlite` databases that can be imported
+const SUPPORTED_SOURCE_VERSION: i64 = 1;
+
+/// Numbers of entries imported by [`StateStore::import_matrix_sdk_sqlite`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SqliteImportCounts {
+    /// Rooms, including invited rooms
+    pub rooms: usize,
+    /// Rooms whose room info could not be decoded
+    pub skipped_rooms: usize,
+    /// State events, including membership events
+    pub state_events: usize,
+    /// Global and room account data events
+    pub account_data: usize,
+    /// Inbound group sessions
+    pub room_keys: usize,
+    /// Olm sessions
+    pub olm_sessions: usize,
+    /// Devices of the own and other users
+    pub devices: usize,
+}
+
+/// A database of `matrix-sdk-sqlite`, opened read-only
+struct SourceDatabase {
+    /// The connection pool
+    pool: SqlitePool,
+    /// The store cipher of the database, if it is encrypted
+    cipher: Option<StoreCipher>,
+}
+
+impl SourceDatabase {
+    /// Opens a database, unlocking its store cipher with the passphrase if it is encrypted
+    ///
+    /// # Errors
+    /// This function will return an error if the database cannot be opened, if its schema
+    /// version is not supported, or if it is encrypted and the passphrase is missing or incorrect
+    async fn open(path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+        let pool = SqlitePool::connect_with(options).await?;
+        let mut source = Self { pool, cipher: None };
+        // The version is a single unencrypted byte, it is missing if the database is not a
+        // store of `matrix-sdk-sqlite`
+        let version = source
+            .kv("version")
+            .await?
+            .and_then(|version| version.first().copied())
+            .map_or(0, i64::from);
+        if version == 0 || version > SUPPORTED_SOURCE_VERSION {
+            return Err(SQLStoreError::IncompatibleImportSchema {
+                database: version,
+                supported: SUPPORTED_SOURCE_VERSION,
+            });
+        }
+        if let Some(exported) = source.kv("cipher").await? {
+            let passphrase = passphrase.ok_or(SQLStoreError::DatabaseLocked)?;
+            source.cipher = Some(StoreCipher::import(passphrase, &exported)?);
+        }
+        Ok(source)
+    }
+
+    /// Loads a raw value from the key-value table
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    async fn kv(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(sqlx::query_scalar("SELECT value FROM kv WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    /// Encodes a key the way `matrix-sdk-sqlite` does, hashing it if the database is encrypted
+    fn encode_key(&self, table_name: &str, key: &str) -> Vec<u8> {
+        match &self.cipher {
+            Some(cipher) => cipher.hash_key(table_name, key.as_bytes()).to_vec(),
+            None => key.as_bytes().to_vec(),
+        }
+    }
+
+    /// Decrypts a value if the database is encrypted
+    ///
+    /// # Errors
+    /// This function will return an error if the value cannot be decrypted
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match &self.cipher {
+            Some(cipher) => Ok(cipher.decrypt_value_data(rmp_serde::from_slice(data)?)?),
+            None => Ok(data.to_vec()),
+        }
+    }
+
+    /// Decodes a value that `matrix-sdk-sqlite` stored as JSON, like events and room infos
+    ///
+    /// # Errors
+    /// This function will return an error if the value cannot be decrypted or decoded
+    fn decode_json<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(serde_json::from_slice(&self.decrypt(data)?)?)
+    }
+
+    /// Decodes a value that `matrix-sdk-sqlite` stored as MessagePack, like pickles
+    ///
+    /// # Errors
+    /// This function will return an error if the value cannot be decrypted or decoded
+    fn decode_msgpack<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        Ok(rmp_serde::from_slice(&self.decrypt(data)?)?)
+    }
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> StateStore<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Imports the stores of `matrix-sdk-sqlite` from the given directory
+    ///
+    /// This reads `matrix-sdk-state.sqlite3` and, if it exists, `matrix-sdk-crypto.sqlite3`. The
+    /// passphrase is needed if the stores were opened with one. This store has to be empty, and it
+    /// has to be unlocked if a crypto store is imported.
+    ///
+    /// The room infos, state events, members and account data of the state store are imported
+    /// in a single transaction. The sync token is only imported if every room info could be
+    /// decoded, otherwise the next sync starts from scratch and restores the skipped rooms.
+    /// Receipts, presence and media are not imported. From the crypto store, the account, the
+    /// private cross-signing identity, the olm sessions, the devices and the inbound group
+    /// sessions are imported. User identities are queried from the server again.
+    ///
+    /// Only databases of the `matrix-sdk-sqlite` schema versions known to this crate are
+    /// imported.
+    ///
+    /// # Errors
+    /// This function will return an error if this store is not empty or not unlocked, if a
+    /// database has an unsupported schema version, if the passphrase is missing or incorrect, if
+    /// the imported data cannot be decoded, or if a query fails
+    pub async fn import_matrix_sdk_sqlite(
+        &self,
+        dir: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<SqliteImportCounts> {
+        if DB::sync_token_load_query()
+            .fetch_optional(timed(&*self.db, self.table_prefix))
+            .await?
+            .is_some()
+        {
+            return Err(SQLStoreError::StoreNotEmpty);
+        }
+        let crypto_path = dir.join(CRYPTO_DATABASE_NAME);
+        let crypto = if tokio::fs::metadata(&crypto_path).await.is_ok() {
+            if self.load_account().await?.is_some() {
+                return Err(SQLStoreError::StoreNotEmpty);
+            }
+            Some(SourceDatabase::open(&crypto_path, passphrase).await?)
+        } else {
+            None
+        };
+        let state = SourceDatabase::open(&dir.join(STATE_DATABASE_NAME), passphrase).await?;
+
+        let mut counts = SqliteImportCounts::default();
+        self.import_state(&state, &mut counts).await?;
+        if let Some(crypto) = crypto {
+            self.import_crypto(&crypto, &mut counts).await?;
+        }
+        Ok(counts)
+    }
+
+    /// Imports the state store of `matrix-sdk-sqlite`
+    ///
+    /// # Errors
+    /// This function will return an error if the data cannot be decoded or if a query fails
+    async fn import_state(
+        &self,
+        source: &SourceDatabase,
+        counts: &mut SqliteImportCounts,
+    ) -> Result<()> {
+        let mut changes = StateChanges::default();
+        // Room IDs are hashed in encrypted databases, so they are recovered from the room infos
+        let mut room_ids: HashMap<(&str, Vec<u8>), OwnedRoomId> = HashMap::new();
+
+        let rows = sqlx::query("SELECT stripped, data FROM room_info")
+            .fetch_all(&source.pool)
+            .await?;
+        for row in rows {
+            let stripped: bool = row.try_get("stripped")?;
+            let data: Vec<u8> = row.try_get("data")?;
+            let info: serde_json::Value = source.decode_json(&data)?;
+            let room_id = match info.get("room_id").and_then(serde_json::Value::as_str) {
+                Some(room_id) => OwnedRoomId::try_from(room_id)?,
+                None => {
+                    counts.skipped_rooms += 1;
+                    continue;
+                }
+            };
+            for table_name in [STATE_EVENT_TABLE, ROOM_ACCOUNT_DATA_TABLE] {
+                room_ids.insert(
+                    (table_name, source.encode_key(table_name, room_id.as_str())),
+                    room_id.clone(),
+                );
+            }
+            match serde_json::from_value::<RoomInfo>(info) {
+                Ok(info) if stripped => {
+                    changes.stripped_room_infos.insert(room_id, info);
+                }
+                Ok(info) => {
+                    changes.room_infos.insert(room_id, info);
+                }
+                Err(error) => {
+                    warn!(%room_id, %error, "Skipping room info that cannot be decoded");
+                    counts.skipped_rooms += 1;
+                    continue;
+                }
+            }
+            counts.rooms += 1;
+        }
+
+        let rows = sqlx::query("SELECT room_id, stripped, data FROM state_event")
+            .fetch_all(&source.pool)
+            .await?;
+        for row in rows {
+            let key: Vec<u8> = row.try_get("room_id")?;
+            let room_id = match room_ids.get(&(STATE_EVENT_TABLE, key)) {
+                Some(room_id) => room_id.clone(),
+                None => continue,
+            };
+            let stripped: bool = row.try_get("stripped")?;
+            let data: Vec<u8> = row.try_get("data")?;
+            let event: serde_json::Value = source.decode_json(&data)?;
+            let event_type = event
+                .get("type")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_owned();
+            let state_key = event
+                .get("state_key")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_owned();
+            let event_type = StateEventType::from(event_type.as_str());
+            match (stripped, &event_type) {
+                (false, StateEventType::RoomMember) => {
+                    let raw: Raw<SyncRoomMemberEvent> = serde_json::from_value(event)?;
+                    match raw.deserialize() {
+                        Ok(member) => {
+                            changes
+                                .members
+                                .entry(room_id)
+                                .or_default()
+                                .insert(member.state_key().to_owned(), raw);
+                        }
+                        Err(error) => {
+                            warn!(%room_id, %error, "Skipping member event that cannot be decoded");
+                            continue;
+                        }
+                    }
+                }
+                (true, StateEventType::RoomMember) => {
+                    let raw: Raw<StrippedRoomMemberEvent> = serde_json::from_value(event)?;
+                    match raw.deserialize() {
+                        Ok(member) => {
+                            changes
+                                .stripped_members
+                                .entry(room_id)
+                                .or_default()
+                                .insert(member.state_key, raw);
+                        }
+                        Err(error) => {
+                            warn!(%room_id, %error, "Skipping member event that cannot be decoded");
+                            continue;
+                        }
+                    }
+                }
+                (false, _) => {
+                    changes
+                        .state
+                        .entry(room_id)
+                        .or_default()
+                        .entry(event_type)
+                        .or_default()
+                        .insert(state_key, serde_json::from_value(event)?);
+                }
+                (true, _) => {
+                    changes
+                        .stripped_state
+                        .entry(room_id)
+                        .or_default()
+                        .entry(event_type)
+                        .or_default()
+                        .insert(state_key, serde_json::from_value(event)?);
+                }
+            }
+            counts.state_events += 1;
+        }
+
+        let rows = sqlx::query("SELECT data FROM global_account_data")
+            .fetch_all(&source.pool)
+            .await?;
+        for row in rows {
+            let data: Vec<u8> = row.try_get("data")?;
+            let event: Raw<AnyGlobalAccountDataEvent> = source.decode_json(&data)?;
+            let event_type: String = event.get_field("type")?.unwrap_or_default();
+            changes
+                .account_data
+                .insert(GlobalAccountDataEventType::from(event_type.as_str()), event);
+            counts.account_data += 1;
+        }
+
+        let rows = sqlx::query("SELECT room_id, data FROM room_account_data")
+            .fetch_all(&source.pool)
+            .await?;
+        for row in rows {
+            let key: Vec<u8> = row.try_get("room_id")?;
+            let room_id = match room_ids.get(&(ROOM_ACCOUNT_DATA_TABLE, key)) {
+                Some(room_id) => room_id.clone(),
+                None => continue,
+            };
+            let data: Vec<u8> = row.try_get("data")?;
+            let event: Raw<AnyRoomAccountDataEvent> = source.decode_json(&data)?;
+            let event_type: String = event.get_field("type")?.unwrap_or_default();
+            changes
+                .room_account_data
+                .entry(room_id)
+                .or_default()
+                .insert(RoomAccountDataEventType::from(event_type.as_str()), event);
+            counts.account_data += 1;
+        }
+
+        if counts.skipped_rooms == 0 {
+            if let Some(token) = source.kv("sync_token").await? {
+                changes.sync_token = Some(source.decode_msgpack(&token)?);
+            }
+        }
+
+        let mut txn = self.db.begin().await?;
+        Self::save_state_changes_txn(
+            &mut txn,
+            self.table_prefix,
+            &changes,
+            self.sync_token_history_len,
+            &*self.serializer,
+        )
+        .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Imports the account, olm sessions, devices and room keys of the crypto store of
+    /// `matrix-sdk-sqlite`
+    ///
+    /// # Errors
+    /// This function will return an error if this store is locked, if the data cannot be decoded
+    /// or if a query fails
+    async fn import_crypto(
+        &self,
+        source: &SourceDatabase,
+        counts: &mut SqliteImportCounts,
+    ) -> Result<()> {
+        let account = match source.kv("account").await? {
+            Some(data) => Some(ReadOnlyAccount::from_pickle(source.decode_msgpack(&data)?)?),
+            None => None,
+        };
+        let private_identity = match source.kv("identity").await? {
+            Some(data) => Some(
+                PrivateCrossSigningIdentity::from_pickle(source.decode_msgpack(&data)?)
+                    .await
+                    .map_err(|e| SQLStoreError::Sign(Box::new(e)))?,
+            ),
+            None => None,
+        };
+
+        // Olm sessions belong to the account, they cannot be restored without it
+        let mut sessions = Vec::new();
+        if let Some(account) = &account {
+            let rows = sqlx::query("SELECT data FROM session")
+                .fetch_all(&source.pool)
+                .await?;
+            for row in rows {
+                let data: Vec<u8> = row.try_get("data")?;
+                sessions.push(Session::from_pickle(
+                    Arc::clone(&account.user_id),
+                    Arc::clone(&account.device_id),
+                    Arc::clone(&account.identity_keys),
+                    source.decode_msgpack(&data)?,
+                ));
+            }
+        }
+        counts.olm_sessions = sessions.len();
+
+        let rows = sqlx::query("SELECT data FROM device")
+            .fetch_all(&source.pool)
+            .await?;
+        let mut devices = Vec::with_capacity(rows.len());
+        for row in rows {
+            let data: Vec<u8> = row.try_get("data")?;
+            devices.push(source.decode_msgpack::<ReadOnlyDevice>(&data)?);
+        }
+        counts.devices = devices.len();
+
+        let rows = sqlx::query("SELECT data FROM inbound_group_session")
+            .fetch_all(&source.pool)
+            .await?;
+        let mut inbound_group_sessions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let data: Vec<u8> = row.try_get("data")?;
+            inbound_group_sessions.push(InboundGroupSession::from_pickle(
+                source.decode_msgpack(&data)?,
+            )?);
+        }
+        counts.room_keys = inbound_group_sessions.len();
+
+        self.save_changes(Changes {
+            account,
+            private_identity,
+            sessions,
+            inbound_group_sessions,
+            devices: DeviceChanges {
+                new: devices,
+                ..DeviceChanges::default()
+            },
+            ..Changes::default()
+        })
+        .await
+    }
+}
@@ -40,11 +40,19 @@
 //!
 //! The list of trait bounds may seem daunting, however all enabled database backends are supported.
 
-use std::sync::Arc;
+use std::{
+    collections::{btree_map::Entry, hash_map::RandomState, BTreeMap, BTreeSet},
+    hash::{BuildHasher, Hash, Hasher},
+    sync::{Arc, PoisonError},
+    time::Duration,
+};
 
+use batch::WriteQueue;
 // These crate imports are due to bugs, regressions, etc
 use sqlx_core as _;
-use tracing as _;
+// Only enabled to switch the sqlite library bundled by sqlx to SQLCipher
+#[cfg(feature = "sqlcipher")]
+use libsqlite3_sys as _;
 
 #[cfg(feature = "e2e-encryption")]
 use cryptostore::CryptostoreData;
@@ -53,28 +61,81 @@ use matrix_sdk_base::store::StoreConfig;
 #[cfg(feature = "e2e-encryption")]
 use matrix_sdk_store_encryption::StoreCipher;
 
+#[cfg(feature = "e2e-encryption")]
+pub use cryptostore::{
+    AccountState, DehydratedDevice, KeyCounts, RoomSettings, VerificationState, WithheldInfo,
+};
 mod helpers;
-pub use helpers::SupportedDatabase;
-use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo};
+pub use helpers::{
+    apply_migrations, check_schema_compat, prefixed_query, record_schema_compat, SupportedDatabase,
+};
+pub mod queries;
+pub mod rows;
+mod schema;
+use matrix_sdk_base::{
+    locks::{Mutex, MutexGuard},
+    MinimalRoomMemberEvent, RoomInfo,
+};
 use ruma::{
     events::{
         presence::PresenceEvent,
         receipt::Receipt,
         room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
         AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
-        AnySyncStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent,
     },
     serde::Raw,
+    RoomId,
 };
+pub use schema::{MigrationDuration, SchemaChange};
 use sqlx::{
-    database::HasArguments, migrate::Migrate, types::Json, ColumnIndex, Database, Executor,
-    IntoArguments, Pool, Transaction,
+    database::HasArguments, migrate::Migrate, pool::PoolOptions, types::Json, ColumnIndex,
+    Database, Executor, IntoArguments, Pool, Transaction,
+};
+pub use statestore::{
+    GlobalProfile, HealthStatus, IntegrityReport, MemberFilter, MemberHistoryEntry, QuarantinedRow,
+    RoomListEntry, RoomListOrder, RoomStorageStats, StorageStats, StorageUsage, WellKnown,
 };
 use thiserror::Error;
+use tokio::sync::OwnedMutexGuard;
 
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod any;
+mod batch;
+#[cfg(feature = "sqlite")]
+mod copy;
+#[cfg(feature = "sqlite-import")]
+mod import;
+mod maintenance;
+mod sliding_sync;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub use any::{any_store_config, AnyStateStore};
+pub use batch::WriteBatching;
+#[cfg(feature = "sqlite-import")]
+pub use import::SqliteImportCounts;
+pub use maintenance::MaintenanceConfig;
+pub use sliding_sync::{SlidingSyncList, SlidingSyncState};
+#[cfg(feature = "e2e-encryption")]
+mod cipher;
+#[cfg(feature = "e2e-encryption")]
+pub use cipher::{KdfParams, KeyCipherProvider, PassphraseCipherProvider, StoreCipherProvider};
 #[cfg(feature = "e2e-encryption")]
 mod cryptostore;
+mod serializer;
+#[cfg(feature = "cbor")]
+pub use serializer::CborSerializer;
+#[cfg(feature = "msgpack")]
+pub use serializer::MessagePackSerializer;
+pub use serializer::{JsonSerializer, StoreSerializer};
 mod statestore;
+mod telemetry;
+pub use telemetry::{
+    set_slow_query_callback, set_slow_query_threshold, SlowQuery, SlowQueryCallback,
+};
+#[cfg(feature = "test-postgres")]
+pub mod testing;
+mod transaction;
+pub use transaction::StoreTransaction;
 
 /// Errors that can occur in the SQL Store
 #[derive(Debug, Error)]
@@ -115,63 +176,454 @@ pub enum SQLStoreError {
     #[cfg(feature = "e2e-encryption")]
     #[error("Failed to verify data: {0}")]
     Sign(Box<dyn std::error::Error + Send + Sync>),
+    /// The store cipher provider failed
+    #[cfg(feature = "e2e-encryption")]
+    #[error("Store cipher provider failed: {0}")]
+    CipherProvider(Box<dyn std::error::Error + Send + Sync>),
+    /// A room key export could not be read
+    #[cfg(feature = "e2e-encryption")]
+    #[error("Failed to import room keys: {0}")]
+    RoomKeyExport(Box<dyn std::error::Error + Send + Sync>),
+    /// Failed to decode a MessagePack value of an imported store
+    #[cfg(feature = "sqlite-import")]
+    #[error("Failed to decode imported data as MessagePack: {0}")]
+    MessagePack(#[from] rmp_serde::decode::Error),
+    /// A serializer failed
+    #[error("Serializer failed: {0}")]
+    Serializer(Box<dyn std::error::Error + Send + Sync>),
+    /// A value was written with a custom serializer that is not configured
+    #[error("Value was written with a serializer that is not configured")]
+    UnknownSerializer,
+    /// A value is compressed, but the `compression` feature is disabled
+    #[cfg(feature = "e2e-encryption")]
+    #[error("Value is compressed, but the compression feature is disabled")]
+    CompressionDisabled,
     /// Account info was not found
     #[cfg(feature = "e2e-encryption")]
     #[error("Account info was not found")]
     MissingAccountInfo,
+    /// The database has been written by a newer version of this crate that is incompatible with
+    /// this version
+    #[error("Database schema version {database} is incompatible with this version, which supports up to schema version {supported}")]
+    IncompatibleSchema {
+        /// Schema version of the database
+        database: i64,
+        /// Newest schema version supported by this version
+        supported: i64,
+    },
+    /// The table prefix is not a valid SQL identifier
+    #[error("Invalid table prefix: {0:?}")]
+    InvalidTablePrefix(String),
+    /// The database URL does not belong to an enabled database backend
+    #[error("Unsupported database URL scheme: {0}")]
+    UnsupportedDatabaseUrl(String),
+    /// The snapshot is malformed or does not match the database
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    /// The store already contains data
+    #[error("The store already contains data")]
+    StoreNotEmpty,
+    /// A database to import has a schema version that this version cannot import
+    #[cfg(feature = "sqlite-import")]
+    #[error("Imported database schema version {database} is not supported, this version imports up to schema version {supported}")]
+    IncompatibleImportSchema {
+        /// Schema version of the imported database, `0` if it has none
+        database: i64,
+        /// Newest schema version supported by this version
+        supported: i64,
+    },
+    /// I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The embedded postgres instance failed
+    #[cfg(feature = "test-postgres")]
+    #[error("Embedded postgres failed: {0}")]
+    EmbeddedPostgres(#[from] pg_embed::pg_errors::PgEmbedError),
+}
+
+impl SQLStoreError {
+    /// Returns whether the error is a transient conflict with a concurrent transaction
+    ///
+    /// These are postgres serialization failures (SQLSTATE 40001) and deadlocks (SQLSTATE
+    /// 40P01), and sqlite transactions that could not upgrade to a write transaction because
+    /// another connection was writing (`SQLITE_BUSY` and `SQLITE_BUSY_SNAPSHOT`). Repeating the
+    /// transaction usually succeeds.
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Database(sqlx::Error::Database(e)) => {
+                matches!(e.code().as_deref(), Some("40001" | "40P01" | "5" | "517"))
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Result type returned by SQL Store functions
 pub type Result<T, E = SQLStoreError> = std::result::Result<T, E>;
 
+/// Default maximum total size of the media store in bytes
+pub const DEFAULT_MAX_MEDIA_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Default number of times a write transaction is repeated after a transient conflict
+pub const DEFAULT_TRANSACTION_RETRIES: u32 = 5;
+
+/// Default number of sync tokens kept in the sync token history
+pub const DEFAULT_SYNC_TOKEN_HISTORY_LEN: u32 = 10;
+
+/// Number of locks the rooms are distributed over
+const ROOM_LOCK_STRIPES: usize = 64;
+
+/// Per-room write locks
+///
+/// Rooms are distributed over a fixed number of locks by the hash of their ID, so that the number
+/// of locks does not grow with the number of rooms. Writes to rooms sharing a lock are
+/// serialized.
+#[derive(Debug)]
+pub(crate) struct RoomLocks {
+    /// Hasher distributing the rooms over the locks
+    hasher: RandomState,
+    /// The locks
+    stripes: [Arc<Mutex<()>>; ROOM_LOCK_STRIPES],
+}
+
+impl Default for RoomLocks {
+    fn default() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            stripes: [(); ROOM_LOCK_STRIPES].map(|()| Arc::new(Mutex::new(()))),
+        }
+    }
+}
+
+impl RoomLocks {
+    /// Returns the index of the lock of a room
+    fn stripe(&self, room_id: &RoomId) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        room_id.hash(&mut hasher);
+        usize::from(hasher.finish().to_le_bytes()[0]) % ROOM_LOCK_STRIPES
+    }
+
+    /// Returns the write lock of a room
+    pub(crate) fn get(&self, room_id: &RoomId) -> &Mutex<()> {
+        &self.stripes[self.stripe(room_id)]
+    }
+
+    /// Acquires the write locks of all given rooms
+    ///
+    /// The locks are always acquired in the same order, so that concurrent callers cannot
+    /// deadlock.
+    pub(crate) async fn lock_all<'a>(
+        &self,
+        rooms: impl IntoIterator<Item = &'a RoomId>,
+    ) -> Vec<MutexGuard<'_, ()>> {
+        let stripes: BTreeSet<usize> = rooms.into_iter().map(|room| self.stripe(room)).collect();
+        let mut guards = Vec::with_capacity(stripes.len());
+        for stripe in stripes {
+            guards.push(self.stripes[stripe].lock().await);
+        }
+        guards
+    }
+
+    /// Acquires the write locks of all given rooms that are not in `guards` yet
+    ///
+    /// The new guards are added to `guards`, and the locks are held until they are dropped. The
+    /// new locks are acquired in the same order as by [`RoomLocks::lock_all`], but after the
+    /// locks that are already held.
+    pub(crate) async fn lock_owned<'a>(
+        &self,
+        rooms: impl IntoIterator<Item = &'a RoomId>,
+        guards: &mut BTreeMap<usize, OwnedMutexGuard<()>>,
+    ) {
+        let stripes: BTreeSet<usize> = rooms.into_iter().map(|room| self.stripe(room)).collect();
+        for stripe in stripes {
+            if let Entry::Vacant(entry) = guards.entry(stripe) {
+                entry.insert(Arc::clone(&self.stripes[stripe]).lock_owned().await);
+            }
+        }
+    }
+}
+
 /// SQL State Storage for matrix-sdk
 #[allow(single_use_lifetimes)]
 #[derive(Debug)]
 pub struct StateStore<DB: SupportedDatabase> {
     /// The database connection
     db: Arc<Pool<DB>>,
+    /// The database connection used for reads
+    read_db: Arc<Pool<DB>>,
+    /// Prefix prepended to the names of all tables
+    table_prefix: &'static str,
+    /// Write locks for individual rooms
+    room_locks: Arc<RoomLocks>,
+    /// Whether identical media contents are only stored once
+    media_deduplication: bool,
+    /// Maximum total size of the media store in bytes
+    max_media_bytes: u64,
+    /// Whether membership changes are recorded in the member history
+    member_history: bool,
+    /// Number of sync tokens kept in the sync token history
+    sync_token_history_len: u32,
+    /// Number of times a write transaction is repeated after a transient conflict
+    transaction_retries: u32,
+    /// How long removed rooms are kept in the tombstone tables, if at all
+    room_retention: Option<Duration>,
+    /// Queue of state changes that are written in batches, if enabled
+    write_queue: Option<Arc<WriteQueue<DB>>>,
+    /// Whether cryptostore values are compressed
+    #[cfg(feature = "compression")]
+    compression: bool,
+    /// Serialization format of new events and cryptostore values
+    serializer: Arc<dyn StoreSerializer>,
     #[cfg(feature = "e2e-encryption")]
-    /// Extra cryptostore data
-    cryptostore: Option<CryptostoreData>,
+    /// Extra cryptostore data, `None` while the cryptostore is locked
+    cryptostore: std::sync::RwLock<Option<Arc<CryptostoreData>>>,
 }
 
 #[allow(single_use_lifetimes)]
 impl<DB: SupportedDatabase> StateStore<DB> {
     /// Create a new State Store and automtaically performs migrations
     ///
+    /// See [`run_migrations`] for details.
+    ///
     /// # Errors
-    /// This function will return an error if the migration cannot be applied
+    /// This function will return an error if the migration cannot be applied, or if the database
+    /// has been written by a newer, incompatible version of this crate
     pub async fn new(db: &Arc<Pool<DB>>) -> Result<Self>
     where
         <DB as Database>::Connection: Migrate,
     {
+        run_migrations(db).await?;
+        Ok(Self::from_pool(db))
+    }
+
+    /// Create a new State Store whose tables are named with the given prefix, and performs
+    /// migrations
+    ///
+    /// This allows keeping the store inside of an existing application database without name
+    /// collisions, for example with the prefix `myapp_` the rooms are stored in
+    /// `myapp_statestore_rooms`. The migrations are recorded in `myapp_sqlx_migrations`, so that
+    /// stores with different prefixes can share a database. The prefix has to start with a letter
+    /// or an underscore, and may only contain ASCII letters, digits and underscores.
+    ///
+    /// # Errors
+    /// This function will return an error if the prefix is invalid, if the migration cannot be
+    /// applied, or if the database has been written by a newer, incompatible version of this
+    /// crate
+    pub async fn new_with_table_prefix(db: &Arc<Pool<DB>>, table_prefix: &str) -> Result<Self> {
+        run_migrations_with_table_prefix(db, table_prefix).await?;
+        Self::from_pool(db).with_table_prefix(table_prefix)
+    }
+
+    /// Returns the SQL of all migrations newer than `from_version`
+    ///
+    /// This allows reviewing and applying schema changes manually in environments where the
+    /// application cannot run DDL statements. Every migration is wrapped in its own transaction
+    /// and recorded in the bookkeeping table of sqlx, so that [`StateStore::new`] does not try to
+    /// apply it again. The schema version of this crate version is recorded at the end, like
+    /// [`StateStore::new`] does. Pass `0` to get the SQL for an empty database, and the table
+    /// prefix of the store or an empty string.
+    ///
+    /// # Errors
+    /// This function will return an error if the table prefix is invalid
+    pub fn migration_sql(from_version: i64, table_prefix: &str) -> Result<String> {
+        let table_prefix = helpers::table_prefix(table_prefix)?;
+        let mut sql = String::new();
+        sql.push_str(DB::migrations_table_sql());
+        sql.push('\n');
+        for migration in DB::get_migrator().iter() {
+            if migration.version <= from_version || migration.migration_type.is_down_migration() {
+                continue;
+            }
+            sql.push_str(&format!(
+                "\n-- {} {}\nBEGIN;\n{}\n",
+                migration.version,
+                migration.description,
+                migration.sql.trim_end()
+            ));
+            sql.push_str(&format!(
+                "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)\nVALUES ({}, '{}', TRUE, {}, 0);\nCOMMIT;\n",
+                migration.version,
+                migration.description.replace('\'', "''"),
+                DB::bytes_literal(&migration.checksum)
+            ));
+        }
+        sql.push_str(&format!(
+            "\n-- Schema version of this crate version\nINSERT INTO statestore_schema_compat (id, schema_version, min_reader_version)\nVALUES (0, {}, {})\nON CONFLICT (id) DO UPDATE SET schema_version = excluded.schema_version, min_reader_version = excluded.min_reader_version\nWHERE statestore_schema_compat.schema_version < excluded.schema_version;\n",
+            schema::latest_schema_version(),
+            schema::MIN_READER_SCHEMA_VERSION
+        ));
+        Ok(helpers::apply_table_prefix(&sql, table_prefix))
+    }
+
+    /// Create a new State Store for the pool, without migrating or checking the schema
+    #[must_use]
+    pub(crate) fn from_pool(db: &Arc<Pool<DB>>) -> Self {
+        let read_db = Arc::clone(db);
         let db = Arc::clone(db);
-        let migrator = DB::get_migrator();
-        migrator.run(&*db).await?;
         #[cfg(not(feature = "e2e-encryption"))]
         {
-            Ok(Self { db })
+            Self {
+                db,
+                read_db,
+                table_prefix: "",
+                room_locks: Arc::default(),
+                media_deduplication: false,
+                max_media_bytes: DEFAULT_MAX_MEDIA_BYTES,
+                member_history: false,
+                sync_token_history_len: DEFAULT_SYNC_TOKEN_HISTORY_LEN,
+                transaction_retries: DEFAULT_TRANSACTION_RETRIES,
+                room_retention: None,
+                write_queue: None,
+                #[cfg(feature = "compression")]
+                compression: false,
+                serializer: Arc::new(JsonSerializer),
+            }
         }
         #[cfg(feature = "e2e-encryption")]
         {
-            Ok(Self {
+            Self {
                 db,
-                cryptostore: None,
-            })
+                read_db,
+                table_prefix: "",
+                room_locks: Arc::default(),
+                media_deduplication: false,
+                max_media_bytes: DEFAULT_MAX_MEDIA_BYTES,
+                member_history: false,
+                sync_token_history_len: DEFAULT_SYNC_TOKEN_HISTORY_LEN,
+                transaction_retries: DEFAULT_TRANSACTION_RETRIES,
+                room_retention: None,
+                write_queue: None,
+                #[cfg(feature = "compression")]
+                compression: false,
+                serializer: Arc::new(JsonSerializer),
+                cryptostore: std::sync::RwLock::new(None),
+            }
         }
     }
 
+    /// Routes reads of the state store to a separate pool, such as a postgres read replica
+    ///
+    /// All writes, and reads that are part of a write, still use the primary pool. Reads may
+    /// lag behind writes by the replication delay, so this should only be used with
+    /// replicas that are kept closely in sync. The cryptostore always uses the primary pool,
+    /// as stale sessions can make messages undecryptable.
+    #[must_use]
+    pub fn with_read_pool(mut self, read_db: &Arc<Pool<DB>>) -> Self {
+        self.read_db = Arc::clone(read_db);
+        self
+    }
+
+    /// Names the tables of the store with the given prefix
+    ///
+    /// This is meant for stores whose migrations were applied with
+    /// [`run_migrations_with_table_prefix`]. Unlike
+    /// [`StateStore::new_unmigrated_with_table_prefix`], the schema of the prefixed tables is not
+    /// checked for compatibility. See
+    /// [`StateStore::new_with_table_prefix`] for details.
+    ///
+    /// # Errors
+    /// This function will return an error if the prefix is invalid
+    pub fn with_table_prefix(mut self, table_prefix: &str) -> Result<Self> {
+        self.table_prefix = helpers::table_prefix(table_prefix)?;
+        Ok(self)
+    }
+
     /// Returns a reference to the cryptostore specific data if the store has been unlocked
     ///
     /// # Errors
     /// This function will return an error if the database has not been unlocked
     #[cfg(feature = "e2e-encryption")]
-    pub(crate) fn ensure_e2e(&self) -> Result<&CryptostoreData> {
+    pub(crate) fn ensure_e2e(&self) -> Result<Arc<CryptostoreData>> {
         self.cryptostore
-            .as_ref()
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
             .ok_or(SQLStoreError::DatabaseLocked)
     }
 
+    /// Returns the cryptostore specific data for modification if the store has been unlocked
+    ///
+    /// The data is only shared for the duration of calls borrowing the store, so it is never
+    /// shared while the store is borrowed mutably.
+    #[cfg(feature = "e2e-encryption")]
+    pub(crate) fn cryptostore_mut(&mut self) -> Option<&mut CryptostoreData> {
+        self.cryptostore
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+            .and_then(Arc::get_mut)
+    }
+
+    /// Replaces the cryptostore specific data
+    #[cfg(feature = "e2e-encryption")]
+    fn set_cryptostore(&self, cryptostore: Option<CryptostoreData>) {
+        *self
+            .cryptostore
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = cryptostore.map(Arc::new);
+    }
+
+    /// Returns whether the e2e encryption database is unlocked
+    #[cfg(feature = "e2e-encryption")]
+    #[must_use]
+    pub fn is_unlocked(&self) -> bool {
+        self.cryptostore
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Enables or disables compressing cryptostore values
+    ///
+    /// Values are compressed with zstd before they are encrypted. Compressed values are always
+    /// read transparently, so compression can be enabled and disabled at any time. Compression
+    /// is disabled by default.
+    ///
+    /// Only the encrypted values of the cryptostore tables, such as sessions and room keys, are
+    /// compressed. State events, room infos and other state store data are not compressed, see
+    /// [`StateStore::set_serializer`] for a more compact format of events, and media is stored
+    /// as is.
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+        if let Some(cryptostore) = self.cryptostore_mut() {
+            cryptostore.compression = enabled;
+        }
+    }
+
+    /// Sets the serialization format of new state events, member events and cryptostore values
+    ///
+    /// Values are serialized as JSON by default. The format of every value is recorded with it,
+    /// so values written with JSON or any of the other formats of this crate stay readable after
+    /// switching formats, also when the store is opened without setting the format. Values of a
+    /// custom format can only be read while it is configured.
+    ///
+    /// The `m.space.child`, `m.space.parent`, `m.room.create`, `m.room.tombstone` and
+    /// `m.room.canonical_alias` state events are always stored as JSON, as the database looks
+    /// into them to maintain the space hierarchy, room upgrades and aliases.
+    pub fn set_serializer(&mut self, serializer: Arc<dyn StoreSerializer>) {
+        #[cfg(feature = "e2e-encryption")]
+        if let Some(cryptostore) = self.cryptostore_mut() {
+            cryptostore.serializer = Arc::clone(&serializer);
+        }
+        self.serializer = serializer;
+    }
+
+    /// Locks the e2e encryption database
+    ///
+    /// The store cipher and all cached cryptostore data are dropped, and the key material is
+    /// zeroized once cryptostore accesses that are in progress have finished. Until the database
+    /// is unlocked again, cryptostore accesses fail with [`SQLStoreError::DatabaseLocked`]. The
+    /// state store is not affected.
+    ///
+    /// This only needs a shared reference, so that a store that has been handed to the SDK with
+    /// [`store_config`] can still be locked through the `Arc` that is kept by the application.
+    #[cfg(feature = "e2e-encryption")]
+    pub fn lock(&self) {
+        self.set_cryptostore(None);
+    }
+
     /// Unlocks the e2e encryption database
     /// # Errors
     /// This function will fail if the database could not be unlocked
@@ -186,6 +638,8 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Vec<u8>: SqlType<DB>,
         String: SqlType<DB>,
         bool: SqlType<DB>,
+        i64: SqlType<DB>,
+        f64: SqlType<DB>,
         Vec<u8>: SqlType<DB>,
         Option<String>: SqlType<DB>,
         Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
@@ -198,10 +652,16 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Json<Receipt>: SqlType<DB>,
         Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
         Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+        Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
         for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
     {
-        self.cryptostore = Some(CryptostoreData::new_unencrypted());
+        self.set_cryptostore(Some(CryptostoreData::new_unencrypted()));
+        #[cfg(feature = "compression")]
+        self.set_compression(self.compression);
+        self.set_serializer(Arc::clone(&self.serializer));
+        self.load_index_salt().await?;
         self.load_tracked_users().await?;
+        self.backfill_account_state().await?;
         Ok(())
     }
 
@@ -219,6 +679,90 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Vec<u8>: SqlType<DB>,
         String: SqlType<DB>,
         bool: SqlType<DB>,
+        i64: SqlType<DB>,
+        f64: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        Option<String>: SqlType<DB>,
+        Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+        Json<Raw<PresenceEvent>>: SqlType<DB>,
+        Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+        Json<MinimalRoomMemberEvent>: SqlType<DB>,
+        Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+        Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        Json<Receipt>: SqlType<DB>,
+        Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+        Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+        Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        self.unlock_with_cipher_provider(&PassphraseCipherProvider::new(passphrase))
+            .await
+    }
+
+    /// Unlocks the e2e encryption database with a raw 32-byte key
+    ///
+    /// Use this if the application already manages key material, for example in the OS keyring.
+    /// A database created with a passphrase can be moved to a key with
+    /// [`StateStore::rewrap_cipher`].
+    ///
+    /// # Errors
+    /// This function will fail if the key is wrong
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn unlock_with_key(&mut self, key: &[u8; 32]) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'c, 'a> &'a mut Transaction<'c, DB>: Executor<'a, Database = DB>,
+        for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+        for<'a> &'a str: BorrowedSqlType<'a, DB>,
+        Vec<u8>: SqlType<DB>,
+        String: SqlType<DB>,
+        bool: SqlType<DB>,
+        i64: SqlType<DB>,
+        f64: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        Option<String>: SqlType<DB>,
+        Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+        Json<Raw<PresenceEvent>>: SqlType<DB>,
+        Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+        Json<MinimalRoomMemberEvent>: SqlType<DB>,
+        Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+        Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        Json<Receipt>: SqlType<DB>,
+        Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+        Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+        Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        self.unlock_with_cipher_provider(&KeyCipherProvider::new(key))
+            .await
+    }
+
+    /// Unlocks the e2e encryption database with a custom key management backend
+    ///
+    /// If the database does not contain a store cipher yet, a new one is created and exported
+    /// through the provider.
+    ///
+    /// # Errors
+    /// This function will fail if the provider cannot import or export the store cipher
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn unlock_with_cipher_provider(
+        &mut self,
+        provider: &dyn StoreCipherProvider,
+    ) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'c, 'a> &'a mut Transaction<'c, DB>: Executor<'a, Database = DB>,
+        for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+        for<'a> &'a str: BorrowedSqlType<'a, DB>,
+        Vec<u8>: SqlType<DB>,
+        String: SqlType<DB>,
+        bool: SqlType<DB>,
+        i64: SqlType<DB>,
+        f64: SqlType<DB>,
         Vec<u8>: SqlType<DB>,
         Option<String>: SqlType<DB>,
         Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
@@ -231,25 +775,175 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Json<Receipt>: SqlType<DB>,
         Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
         Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+        Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
         for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
     {
         // Try to read the store cipher
 
         let cipher_export = self.get_kv(b"cipher").await?;
-        if let Some(cipher) = cipher_export {
-            self.cryptostore = Some(CryptostoreData::new(StoreCipher::import(
-                passphrase, &cipher,
-            )?));
+        if let Some(cipher_export) = cipher_export {
+            let cipher = provider.import(&cipher_export).await?;
+            if provider.needs_rewrap(&cipher_export) {
+                self.insert_kv(b"cipher", &provider.export(&cipher).await?)
+                    .await?;
+            }
+            self.set_cryptostore(Some(CryptostoreData::new(cipher)));
         } else {
             // Store the cipher in the database
             let cipher = StoreCipher::new()?;
-            self.insert_kv(b"cipher", &cipher.export(passphrase)?)
+            self.insert_kv(b"cipher", &provider.export(&cipher).await?)
                 .await?;
-            self.cryptostore = Some(CryptostoreData::new(cipher));
+            self.set_cryptostore(Some(CryptostoreData::new(cipher)));
         }
+        #[cfg(feature = "compression")]
+        self.set_compression(self.compression);
+        self.set_serializer(Arc::clone(&self.serializer));
+        self.load_index_salt().await?;
         self.load_tracked_users().await?;
+        self.backfill_account_state().await?;
         Ok(())
     }
+
+    /// Re-protects the store cipher with a different key management backend
+    ///
+    /// Use this to change the passphrase, to strengthen the key derivation parameters, or to
+    /// move the key into a different key management system. The encrypted data itself is not
+    /// touched.
+    ///
+    /// # Errors
+    /// This function will fail if the store cipher cannot be imported with `old` or exported with
+    /// `new`
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn rewrap_cipher(
+        &self,
+        old: &dyn StoreCipherProvider,
+        new: &dyn StoreCipherProvider,
+    ) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'c, 'a> &'a mut Transaction<'c, DB>: Executor<'a, Database = DB>,
+        for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+        for<'a> &'a str: BorrowedSqlType<'a, DB>,
+        Vec<u8>: SqlType<DB>,
+        String: SqlType<DB>,
+        bool: SqlType<DB>,
+        i64: SqlType<DB>,
+        f64: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        Option<String>: SqlType<DB>,
+        Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+        Json<Raw<PresenceEvent>>: SqlType<DB>,
+        Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+        Json<MinimalRoomMemberEvent>: SqlType<DB>,
+        Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+        Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        Json<Receipt>: SqlType<DB>,
+        Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+        Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+        Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let cipher_export = self
+            .get_kv(b"cipher")
+            .await?
+            .ok_or(SQLStoreError::DatabaseLocked)?;
+        let cipher = old.import(&cipher_export).await?;
+        self.insert_kv(b"cipher", &new.export(&cipher).await?)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Applies all pending migrations to the database
+///
+/// [`StateStore::new`] does this automatically. On Postgres, the migrations are guarded by an
+/// advisory lock, so that only one of several concurrently starting instances applies them.
+///
+/// # Errors
+/// This function will return an error if the migration cannot be applied, or if the database
+/// has been written by a newer, incompatible version of this crate
+pub async fn run_migrations<DB: SupportedDatabase>(db: &Pool<DB>) -> Result<()> {
+    DB::run_migrations(db, "").await
+}
+
+/// Applies all pending migrations to the tables with the given prefix
+///
+/// See [`StateStore::new_with_table_prefix`] and [`run_migrations`].
+///
+/// # Errors
+/// This function will return an error if the prefix is invalid, if the migration cannot be
+/// applied, or if the database has been written by a newer, incompatible version of this crate
+pub async fn run_migrations_with_table_prefix<DB: SupportedDatabase>(
+    db: &Pool<DB>,
+    table_prefix: &str,
+) -> Result<()> {
+    DB::run_migrations(db, helpers::table_prefix(table_prefix)?).await
+}
+
+/// Sets a timeout for every statement on all connections opened by the pool
+///
+/// On Postgres this sets `statement_timeout`, so that statements running longer than the
+/// timeout are cancelled. On SQLite this sets `busy_timeout`, which limits how long a
+/// statement waits for the database lock. This keeps a single pathological query from
+/// hanging the sync loop indefinitely.
+///
+/// ```rust,ignore
+/// let pool = matrix_sdk_sql::with_statement_timeout(PgPoolOptions::new(), Duration::from_secs(30))
+///     .connect(&database_url)
+///     .await?;
+/// ```
+///
+/// The timeout also applies to migrations, so it should leave room for the migrations marked
+/// as [`MigrationDuration::Linear`].
+///
+/// The timeout is set with the `after_connect` hook of the pool options, which replaces any hook
+/// set before, and is replaced by any hook set afterwards. If you need a hook of your own, set
+/// the timeout in it instead.
+#[must_use]
+pub fn with_statement_timeout<DB: SupportedDatabase>(
+    options: PoolOptions<DB>,
+    timeout: Duration,
+) -> PoolOptions<DB>
+where
+    for<'c> &'c mut <DB as Database>::Connection: Executor<'c, Database = DB>,
+{
+    let sql = DB::statement_timeout_sql(timeout);
+    options.after_connect(move |conn, _| {
+        let sql = sql.clone();
+        Box::pin(async move {
+            conn.execute(sql.as_str()).await?;
+            Ok(())
+        })
+    })
+}
+
+/// Sets the key of a SQLCipher encrypted sqlite database
+///
+/// The whole database file, including the journal, is encrypted with a key derived from the
+/// passphrase. New databases are encrypted when they are created, existing unencrypted
+/// databases cannot be opened with a key. This is meant for mobile-style deployments where the
+/// entire store should be encrypted at rest, rather than only the cryptostore values.
+///
+/// ```rust,ignore
+/// let options = SqliteConnectOptions::new()
+///     .filename(path)
+///     .create_if_missing(true);
+/// let pool = SqlitePool::connect_with(matrix_sdk_sql::with_sqlcipher_key(options, &passphrase))
+///     .await?;
+/// ```
+///
+/// Opening a database with the wrong key only fails on the first query, which is usually the
+/// migration run by [`StateStore::new`].
+#[cfg(feature = "sqlcipher")]
+#[must_use]
+pub fn with_sqlcipher_key(
+    options: sqlx::sqlite::SqliteConnectOptions,
+    passphrase: &str,
+) -> sqlx::sqlite::SqliteConnectOptions {
+    // sqlx sends `key` before all other pragmas, as SQLCipher requires
+    options.pragma("key", format!("'{}'", passphrase.replace('\'', "''")))
 }
 
 /// Creates a new store confiig
@@ -271,6 +965,8 @@ where
     Vec<u8>: SqlType<DB>,
     String: SqlType<DB>,
     bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
     Vec<u8>: SqlType<DB>,
     Option<String>: SqlType<DB>,
     Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
@@ -283,6 +979,7 @@ where
     Json<Receipt>: SqlType<DB>,
     Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
     Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
     for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
 {
     #[cfg(not(feature = "e2e-encryption"))]
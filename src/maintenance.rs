@@ -0,0 +1,285 @@
+//! Periodic maintenance of the state store in the background
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    helpers::{retry_transient, unix_timestamp, BorrowedSqlType, SqlType},
+    telemetry::timed,
+    Result, StateStore, SupportedDatabase,
+};
+use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::Receipt,
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent,
+    },
+    serde::Raw,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments, Pool,
+    Transaction,
+};
+use tokio::{runtime::Handle, task::JoinHandle};
+use tracing::warn;
+
+/// Settings for the background maintenance of the state store
+///
+/// See [`StateStore::spawn_maintenance`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MaintenanceConfig {
+    /// Time between two maintenance runs
+    pub interval: Duration,
+    /// Upper bound of the random delay added to every interval
+    ///
+    /// This keeps several instances that were started at the same time from running their
+    /// maintenance at the same time.
+    pub jitter: Duration,
+    /// Whether media is evicted to enforce the media size budget
+    pub evict_media: bool,
+    /// Whether expired room tombstones are deleted
+    pub prune_tombstones: bool,
+    /// Filters that have not been saved within this time are deleted, if set
+    pub filter_max_age: Option<Duration>,
+    /// Hashes of olm messages that were received longer ago than this are deleted, if set
+    ///
+    /// See [`StateStore::prune_message_hashes`].
+    #[cfg(feature = "e2e-encryption")]
+    pub message_hash_max_age: Option<Duration>,
+    /// Audit log entries older than this are deleted, if set
+    ///
+    /// See [`StateStore::rotate_audit_log`].
+    pub audit_log_max_age: Option<Duration>,
+    /// Whether the statistics of the query planner are refreshed
+    pub optimize: bool,
+}
+
+impl MaintenanceConfig {
+    /// Creates new maintenance settings that run all jobs at the given interval
+    ///
+    /// The jitter defaults to a tenth of the interval, stale filters, message hashes and audit
+    /// log entries are not deleted.
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            jitter: interval / 10,
+            evict_media: true,
+            prune_tombstones: true,
+            filter_max_age: None,
+            #[cfg(feature = "e2e-encryption")]
+            message_hash_max_age: None,
+            audit_log_max_age: None,
+            optimize: true,
+        }
+    }
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60 * 60))
+    }
+}
+
+/// Background task that runs the maintenance jobs
+#[allow(single_use_lifetimes)]
+#[derive(Debug)]
+struct Maintenance<DB: SupportedDatabase> {
+    /// The database connection
+    db: Arc<Pool<DB>>,
+    /// Prefix prepended to the names of all tables
+    table_prefix: &'static str,
+    /// Which jobs are run and how often
+    config: MaintenanceConfig,
+    /// Maximum total size of the media store in bytes
+    max_media_bytes: u64,
+    /// How long removed rooms are kept in the tombstone tables, if at all
+    room_retention: Option<Duration>,
+    /// How often a write transaction is repeated after a transient conflict
+    transaction_retries: u32,
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> Maintenance<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Runs the maintenance jobs at the configured interval until the pool is closed
+    async fn run(self) {
+        loop {
+            tokio::time::sleep(self.config.interval + random_delay(self.config.jitter)).await;
+            if self.db.is_closed() {
+                return;
+            }
+            self.run_once().await;
+        }
+    }
+
+    /// Runs all enabled maintenance jobs once
+    ///
+    /// A failing job is logged and does not keep the other jobs from running.
+    async fn run_once(&self) {
+        if self.config.evict_media {
+            if let Err(error) = self.evict_media().await {
+                warn!(%error, "Failed to evict media");
+            }
+        }
+        if self.config.prune_tombstones {
+            if let Err(error) = StateStore::<DB>::purge_room_tombstones_in(
+                &self.db,
+                self.table_prefix,
+                self.room_retention,
+            )
+            .await
+            {
+                warn!(%error, "Failed to purge room tombstones");
+            }
+        }
+        if let Some(max_age) = self.config.filter_max_age {
+            if let Err(error) = self.delete_stale_filters(max_age).await {
+                warn!(%error, "Failed to delete stale filters");
+            }
+        }
+        #[cfg(feature = "e2e-encryption")]
+        if let Some(max_age) = self.config.message_hash_max_age {
+            if let Err(error) =
+                StateStore::<DB>::prune_message_hashes_in(&self.db, self.table_prefix, max_age)
+                    .await
+            {
+                warn!(%error, "Failed to prune message hashes");
+            }
+        }
+        if let Some(max_age) = self.config.audit_log_max_age {
+            if let Err(error) =
+                StateStore::<DB>::rotate_audit_log_in(&self.db, self.table_prefix, max_age).await
+            {
+                warn!(%error, "Failed to rotate the audit log");
+            }
+        }
+        if self.config.optimize {
+            if let Err(error) = (&*self.db).execute(DB::optimize_sql()).await {
+                warn!(%error, "Failed to optimize the database");
+            }
+        }
+    }
+
+    /// Evicts media until the media store fits into its size budget
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    async fn evict_media(&self) -> Result<()> {
+        retry_transient(self.transaction_retries, || async move {
+            let mut txn = self.db.begin().await?;
+            StateStore::<DB>::enforce_media_budget_txn(
+                &mut txn,
+                self.table_prefix,
+                self.max_media_bytes,
+            )
+            .await?;
+            txn.commit().await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deletes all filters that have not been saved within `max_age`
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    async fn delete_stale_filters(&self, max_age: Duration) -> Result<()> {
+        let cutoff =
+            unix_timestamp().saturating_sub(i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX));
+        DB::filters_delete_stale_query()
+            .bind(cutoff)
+            .execute(timed(&*self.db, self.table_prefix))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Returns a random delay between zero and `max`
+fn random_delay(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let max_millis = u64::try_from(max.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(
+        random
+            .checked_rem(max_millis.saturating_add(1))
+            .unwrap_or(0),
+    )
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> StateStore<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Runs media eviction, tombstone pruning and query planner maintenance in the background
+    ///
+    /// The task runs on the given runtime and uses the media budget and room retention that are
+    /// configured at the time of the call. It keeps running until the database pool is closed,
+    /// or until it is aborted through the returned handle. Failing jobs are logged and retried
+    /// at the next interval.
+    pub fn spawn_maintenance(&self, handle: &Handle, config: MaintenanceConfig) -> JoinHandle<()> {
+        let maintenance = Maintenance {
+            db: Arc::clone(&self.db),
+            table_prefix: self.table_prefix,
+            config,
+            max_media_bytes: self.max_media_bytes,
+            room_retention: self.room_retention,
+            transaction_retries: self.transaction_retries,
+        };
+        handle.spawn(maintenance.run())
+    }
+}
@@ -0,0 +1,28 @@
+//! Query providers for the different parts of the stores
+//!
+//! Every trait covers the tables of one domain and is a supertrait of [`SupportedDatabase`], so
+//! that the queries can be called on any supported database. The default queries are written for
+//! postgres, and a database only needs to override the queries its SQL dialect does not
+//! understand. A new table can get its own trait here without touching the other ones.
+//!
+//! [`SupportedDatabase`]: crate::SupportedDatabase
+
+mod crypto;
+mod kv;
+mod maintenance;
+mod media;
+mod member;
+mod room;
+mod rows;
+mod state;
+mod sync;
+
+pub use crypto::CryptoQueries;
+pub use kv::KvQueries;
+pub use maintenance::MaintenanceQueries;
+pub use media::MediaQueries;
+pub use member::MemberQueries;
+pub use room::RoomQueries;
+pub use rows::RowQueries;
+pub use state::StateQueries;
+pub use sync::SyncQueries;
@@ -0,0 +1,1165 @@
+//! Queries for the crypto store
+
+use sqlx::Database;
+#[cfg(feature = "e2e-encryption")]
+use sqlx::{database::HasArguments, query::Query};
+
+#[cfg(feature = "e2e-encryption")]
+use crate::helpers::prefixed_query;
+
+/// Queries for the crypto store
+///
+/// All queries of this trait are only available with the `e2e-encryption` feature.
+#[allow(single_use_lifetimes)]
+pub trait CryptoQueries: Database {
+    /// Stores a cryptostore session
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed sender key
+    /// * `$2` - The encrypted session data
+    #[cfg(feature = "e2e-encryption")]
+    fn session_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_session (sender_key, session_data)
+                VALUES ($1, $2)
+            "#,
+        )
+    }
+
+    /// Stores an Olm message hash
+    ///
+    /// # Arguments
+    /// * `$1` - The sender key
+    /// * `$2` - The message hash
+    /// * `$3` - The current unix timestamp
+    #[cfg(feature = "e2e-encryption")]
+    fn olm_message_hash_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_message_hash (sender_key, message_hash, created_at)
+                VALUES ($1, $2, $3)
+            "#,
+        )
+    }
+
+    /// Deletes Olm message hashes that were stored before the given time
+    ///
+    /// # Arguments
+    /// * `$1` - The unix timestamp before which message hashes are deleted
+    #[cfg(feature = "e2e-encryption")]
+    fn olm_message_hash_prune_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_message_hash WHERE created_at < $1
+            "#,
+        )
+    }
+
+    /// Upserts an inbound group session
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    /// * `$2` - The hashed sender key
+    /// * `$3` - The hashed session id
+    /// * `$4` - The encrypted session data
+    /// * `$5` - Whether or not the session has been backed up
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_session_upsert_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_inbound_group_session
+                    (room_id, sender_key, session_id, session_data, backed_up)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (room_id, sender_key, session_id)
+                DO UPDATE SET session_data = $4, backed_up = $5
+            "#,
+        )
+    }
+
+    /// Upserts an outbound group session
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room id
+    /// * `$2` - The encrypted session data
+    #[cfg(feature = "e2e-encryption")]
+    fn outbound_group_session_store_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_outbound_group_session (room_id, session_data)
+                VALUES ($1, $2)
+                ON CONFLICT (room_id)
+                DO UPDATE SET session_data = $2
+            "#,
+        )
+    }
+
+    /// Stores a gossip request
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed recipient ID
+    /// * `$2` - The hashed request ID
+    /// * `$3` - The hashed secret request info
+    /// * `$4` - Whether or not the request has been sent
+    /// * `$5` - The encrypted request data
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_gossip_request (recipient_id, request_id, info_key, sent_out, gossip_data)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (request_id)
+                DO UPDATE SET recipient_id = $1, info_key = $3, sent_out = $4, gossip_data = $5
+            "#,
+        )
+    }
+
+    /// Upserts a cryptographic identity
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    /// * `$2` - The encrypted identity data
+    #[cfg(feature = "e2e-encryption")]
+    fn identity_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_identity (user_id, identity_data)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE SET identity_data = $2
+            "#,
+        )
+    }
+
+    /// Upserts the own olm account
+    ///
+    /// # Arguments
+    /// * `$1` - The encrypted account pickle
+    /// * `$2` - Whether the account has been shared with the server
+    /// * `$3` - The JSON of the key counts
+    /// * `$4` - The current unix timestamp
+    #[cfg(feature = "e2e-encryption")]
+    fn account_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_account
+                    (id, account_data, shared, key_counts, updated_at)
+                VALUES (0, $1, $2, $3, $4)
+                ON CONFLICT (id) DO UPDATE
+                SET account_data = $1, shared = $2, key_counts = $3, updated_at = $4
+            "#,
+        )
+    }
+
+    /// Retrieves the own olm account
+    #[cfg(feature = "e2e-encryption")]
+    fn account_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT account_data, shared, key_counts, updated_at
+                FROM cryptostore_account WHERE id = 0
+            "#,
+        )
+    }
+
+    /// Updates the key counts of the own olm account
+    ///
+    /// # Arguments
+    /// * `$1` - The JSON of the key counts
+    #[cfg(feature = "e2e-encryption")]
+    fn account_key_counts_update_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_account SET key_counts = $1 WHERE id = 0
+            "#,
+        )
+    }
+
+    /// Upserts the dehydrated device
+    ///
+    /// # Arguments
+    /// * `$1` - The encrypted device data
+    #[cfg(feature = "e2e-encryption")]
+    fn dehydrated_device_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_dehydrated_device (id, device_data)
+                VALUES (0, $1)
+                ON CONFLICT (id) DO UPDATE SET device_data = $1
+            "#,
+        )
+    }
+
+    /// Retrieves the dehydrated device
+    #[cfg(feature = "e2e-encryption")]
+    fn dehydrated_device_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT device_data FROM cryptostore_dehydrated_device WHERE id = 0
+            "#,
+        )
+    }
+
+    /// Deletes the dehydrated device
+    #[cfg(feature = "e2e-encryption")]
+    fn dehydrated_device_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_dehydrated_device
+            "#,
+        )
+    }
+
+    /// Upserts the own private cross-signing identity
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    /// * `$2` - The encrypted identity data
+    #[cfg(feature = "e2e-encryption")]
+    fn private_identity_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_private_identity (user_id, identity_data)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE SET identity_data = $2
+            "#,
+        )
+    }
+
+    /// Retrieves the own private cross-signing identity
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn private_identity_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT identity_data FROM cryptostore_private_identity
+                WHERE user_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes a private cross-signing identity
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn private_identity_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_private_identity
+                WHERE user_id = $1
+            "#,
+        )
+    }
+
+    /// Upserts a device
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    /// * `$2` - The hashed device ID
+    /// * `$3` - The encrypted device data
+    #[cfg(feature = "e2e-encryption")]
+    fn device_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_device (user_id, device_id, device_info)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (user_id, device_id) DO UPDATE SET device_info = $3
+            "#,
+        )
+    }
+
+    /// Deletes a device
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    /// * `$2` - The hashed device ID
+    #[cfg(feature = "e2e-encryption")]
+    fn device_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_device
+                WHERE user_id = $1 AND device_id = $2
+            "#,
+        )
+    }
+
+    /// Query to get all sessions for a sender key
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed sender key
+    #[cfg(feature = "e2e-encryption")]
+    fn sessions_for_user_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_data FROM cryptostore_session
+                WHERE sender_key = $1
+            "#,
+        )
+    }
+
+    /// Fetch an inbound group session
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    /// * `$2` - The hashed sender key
+    /// * `$3` - The hashed session id
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_session_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_data FROM cryptostore_inbound_group_session
+                WHERE room_id = $1 AND session_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch all inbound group sessions
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_sessions_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_data FROM cryptostore_inbound_group_session
+            "#,
+        )
+    }
+
+    /// Fetch the inbound group sessions of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_sessions_for_room_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_data FROM cryptostore_inbound_group_session
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Count the inbound group sessions of every room, together with one session of the room
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_session_room_counts_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, COUNT(*) AS session_count,
+                    (array_agg(session_data))[1] AS session_data
+                FROM cryptostore_inbound_group_session
+                GROUP BY room_id
+            "#,
+        )
+    }
+
+    /// Fetch inbound group sessions that have not been backed up yet
+    ///
+    /// # Arguments
+    /// * `$1` - The maximum number of sessions to fetch
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_sessions_for_backup_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_data FROM cryptostore_inbound_group_session
+                WHERE backed_up = FALSE
+                LIMIT $1
+            "#,
+        )
+    }
+
+    /// Upserts the withheld info of a room key
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    /// * `$2` - The hashed session ID
+    /// * `$3` - The encrypted withheld info
+    #[cfg(feature = "e2e-encryption")]
+    fn withheld_session_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_withheld_sessions (room_id, session_id, withheld_data)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (room_id, session_id) DO UPDATE SET withheld_data = $3
+            "#,
+        )
+    }
+
+    /// Fetch the withheld info of a room key
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    /// * `$2` - The hashed session ID
+    #[cfg(feature = "e2e-encryption")]
+    fn withheld_session_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT withheld_data FROM cryptostore_withheld_sessions
+                WHERE room_id = $1 AND session_id = $2
+            "#,
+        )
+    }
+
+    /// Deletes the withheld info of a room key
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    /// * `$2` - The hashed session ID
+    #[cfg(feature = "e2e-encryption")]
+    fn withheld_session_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_withheld_sessions
+                WHERE room_id = $1 AND session_id = $2
+            "#,
+        )
+    }
+
+    /// Stores a secret in the secret inbox
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed secret name
+    /// * `$2` - The encrypted secret
+    #[cfg(feature = "e2e-encryption")]
+    fn secret_store_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_secrets (secret_name, secret_data)
+                VALUES ($1, $2)
+            "#,
+        )
+    }
+
+    /// Fetch all secrets with a given name from the secret inbox
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed secret name
+    #[cfg(feature = "e2e-encryption")]
+    fn secrets_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT secret_data FROM cryptostore_secrets
+                WHERE secret_name = $1
+                ORDER BY secret_id
+            "#,
+        )
+    }
+
+    /// Delete all secrets with a given name from the secret inbox
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed secret name
+    #[cfg(feature = "e2e-encryption")]
+    fn secrets_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_secrets
+                WHERE secret_name = $1
+            "#,
+        )
+    }
+
+    /// Load the outbound group session for a room
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    #[cfg(feature = "e2e-encryption")]
+    fn outbound_group_session_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_data FROM cryptostore_outbound_group_session
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Upserts the crypto settings of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    /// * `$2` - The encrypted room settings
+    #[cfg(feature = "e2e-encryption")]
+    fn room_settings_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_room_settings (room_id, settings_data)
+                VALUES ($1, $2)
+                ON CONFLICT (room_id) DO UPDATE SET settings_data = $2
+            "#,
+        )
+    }
+
+    /// Load the crypto settings of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    #[cfg(feature = "e2e-encryption")]
+    fn room_settings_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT settings_data FROM cryptostore_room_settings
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes the crypto settings of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed room ID
+    #[cfg(feature = "e2e-encryption")]
+    fn room_settings_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_room_settings
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Fetch all olm sessions for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn session_rehash_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT session_id, session_data FROM cryptostore_session
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of an olm session
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed sender key
+    /// * `$2` - The session ID
+    #[cfg(feature = "e2e-encryption")]
+    fn session_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_session SET sender_key = $1
+                WHERE session_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch all inbound group sessions for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_session_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, sender_key, session_id, session_data
+                FROM cryptostore_inbound_group_session
+            "#,
+        )
+    }
+
+    /// Replaces the hashed keys of an inbound group session
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed room ID
+    /// * `$2` - The new hashed sender key
+    /// * `$3` - The new hashed session ID
+    /// * `$4` - The old hashed room ID
+    /// * `$5` - The old hashed sender key
+    /// * `$6` - The old hashed session ID
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_session_rehash_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_inbound_group_session
+                SET room_id = $1, sender_key = $2, session_id = $3
+                WHERE room_id = $4 AND sender_key = $5 AND session_id = $6
+            "#,
+        )
+    }
+
+    /// Fetch all outbound group sessions for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn outbound_group_session_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, session_data FROM cryptostore_outbound_group_session
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of an outbound group session
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed room ID
+    /// * `$2` - The old hashed room ID
+    #[cfg(feature = "e2e-encryption")]
+    fn outbound_group_session_rehash_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_outbound_group_session SET room_id = $1
+                WHERE room_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch the keys of all withheld room keys for re-hashing them
+    #[cfg(feature = "e2e-encryption")]
+    fn withheld_session_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, session_id FROM cryptostore_withheld_sessions
+            "#,
+        )
+    }
+
+    /// Replaces the hashed keys of a withheld room key
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed room ID
+    /// * `$2` - The new hashed session ID
+    /// * `$3` - The old hashed room ID
+    /// * `$4` - The old hashed session ID
+    #[cfg(feature = "e2e-encryption")]
+    fn withheld_session_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_withheld_sessions SET room_id = $1, session_id = $2
+                WHERE room_id = $3 AND session_id = $4
+            "#,
+        )
+    }
+
+    /// Fetch all gossip requests for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT request_id, gossip_data FROM cryptostore_gossip_request
+            "#,
+        )
+    }
+
+    /// Replaces the hashed keys of a gossip request
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed recipient ID
+    /// * `$2` - The new hashed request ID
+    /// * `$3` - The new hashed info key
+    /// * `$4` - The old hashed request ID
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_gossip_request
+                SET recipient_id = $1, request_id = $2, info_key = $3
+                WHERE request_id = $4
+            "#,
+        )
+    }
+
+    /// Fetch all user identities for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn identity_rehash_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, identity_data FROM cryptostore_identity
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of a user identity
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed user ID
+    /// * `$2` - The old hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn identity_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_identity SET user_id = $1
+                WHERE user_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch all private cross-signing identities for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn private_identity_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, identity_data FROM cryptostore_private_identity
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of a private cross-signing identity
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed user ID
+    /// * `$2` - The old hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn private_identity_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_private_identity SET user_id = $1
+                WHERE user_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch all devices for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn device_rehash_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, device_id, device_info FROM cryptostore_device
+            "#,
+        )
+    }
+
+    /// Replaces the hashed keys of a device
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed user ID
+    /// * `$2` - The new hashed device ID
+    /// * `$3` - The old hashed user ID
+    /// * `$4` - The old hashed device ID
+    #[cfg(feature = "e2e-encryption")]
+    fn device_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_device SET user_id = $1, device_id = $2
+                WHERE user_id = $3 AND device_id = $4
+            "#,
+        )
+    }
+
+    /// Fetch all tracked users for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn tracked_user_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, tracked_user_data FROM cryptostore_tracked_user
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of a tracked user
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed user ID
+    /// * `$2` - The old hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn tracked_user_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_tracked_user SET user_id = $1
+                WHERE user_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch the keys of all secrets in the secret inbox for re-hashing them
+    #[cfg(feature = "e2e-encryption")]
+    fn secret_rehash_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT secret_id, secret_name FROM cryptostore_secrets
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of a secret in the secret inbox
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed secret name
+    /// * `$2` - The secret ID
+    #[cfg(feature = "e2e-encryption")]
+    fn secret_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_secrets SET secret_name = $1
+                WHERE secret_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch the keys of all room settings for re-hashing them
+    #[cfg(feature = "e2e-encryption")]
+    fn room_settings_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id FROM cryptostore_room_settings
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of the crypto settings of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed room ID
+    /// * `$2` - The old hashed room ID
+    #[cfg(feature = "e2e-encryption")]
+    fn room_settings_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_room_settings SET room_id = $1
+                WHERE room_id = $2
+            "#,
+        )
+    }
+
+    /// Upserts the state of a verification request
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed flow ID
+    /// * `$2` - The encrypted verification state
+    /// * `$3` - The current unix timestamp
+    #[cfg(feature = "e2e-encryption")]
+    fn verification_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_verification (flow_id, verification_data, updated_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (flow_id) DO UPDATE SET verification_data = $2, updated_at = $3
+            "#,
+        )
+    }
+
+    /// Retrieves the state of a verification request that has been updated recently
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed flow ID
+    /// * `$2` - The unix timestamp before which verification requests have expired
+    #[cfg(feature = "e2e-encryption")]
+    fn verification_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT verification_data FROM cryptostore_verification
+                WHERE flow_id = $1 AND updated_at >= $2
+            "#,
+        )
+    }
+
+    /// Retrieves the state of all verification requests that have been updated recently
+    ///
+    /// # Arguments
+    /// * `$1` - The unix timestamp before which verification requests have expired
+    #[cfg(feature = "e2e-encryption")]
+    fn verifications_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT verification_data FROM cryptostore_verification
+                WHERE updated_at >= $1
+                ORDER BY updated_at
+            "#,
+        )
+    }
+
+    /// Deletes the state of a verification request
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed flow ID
+    #[cfg(feature = "e2e-encryption")]
+    fn verification_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_verification
+                WHERE flow_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes the state of all verification requests that have expired
+    ///
+    /// # Arguments
+    /// * `$1` - The unix timestamp before which verification requests have expired
+    #[cfg(feature = "e2e-encryption")]
+    fn verification_prune_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_verification
+                WHERE updated_at < $1
+            "#,
+        )
+    }
+
+    /// Fetch all verification requests for re-hashing their keys
+    #[cfg(feature = "e2e-encryption")]
+    fn verification_rehash_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT flow_id, verification_data FROM cryptostore_verification
+            "#,
+        )
+    }
+
+    /// Replaces the hashed key of a verification request
+    ///
+    /// # Arguments
+    /// * `$1` - The new hashed flow ID
+    /// * `$2` - The old hashed flow ID
+    #[cfg(feature = "e2e-encryption")]
+    fn verification_rehash_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE cryptostore_verification SET flow_id = $1
+                WHERE flow_id = $2
+            "#,
+        )
+    }
+
+    /// Tries to take or extend a leased lock
+    ///
+    /// Returns a row if the lock has been taken.
+    ///
+    /// # Arguments
+    /// * `$1` - The lock key
+    /// * `$2` - The holder of the lock
+    /// * `$3` - The unix timestamp in milliseconds at which the lease expires
+    /// * `$4` - The current unix timestamp in milliseconds
+    #[cfg(feature = "e2e-encryption")]
+    fn lease_lock_take_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_lease_locks (lock_key, holder, expiration)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (lock_key) DO UPDATE SET holder = $2, expiration = $3
+                WHERE cryptostore_lease_locks.holder = $2
+                    OR cryptostore_lease_locks.expiration < $4
+                RETURNING holder
+            "#,
+        )
+    }
+
+    /// Upserts a tracked user
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    /// * `$2` - The encrypted tracked user data
+    #[cfg(feature = "e2e-encryption")]
+    fn tracked_user_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO cryptostore_tracked_user (user_id, tracked_user_data)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE SET tracked_user_data = $2
+            "#,
+        )
+    }
+
+    /// Fetch a device
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    /// * `$2` - The hashed device ID
+    #[cfg(feature = "e2e-encryption")]
+    fn device_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT device_info FROM cryptostore_device
+                WHERE user_id = $1 AND device_id = $2
+            "#,
+        )
+    }
+
+    /// Fetch all devices of a user
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn devices_for_user_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT device_info FROM cryptostore_device
+                WHERE user_id = $1
+            "#,
+        )
+    }
+
+    /// Retrieves all tracked users
+    #[cfg(feature = "e2e-encryption")]
+    fn tracked_users_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT tracked_user_data FROM cryptostore_tracked_user
+            "#,
+        )
+    }
+
+    /// Retrieves the cryptographic identity of a user
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed user ID
+    #[cfg(feature = "e2e-encryption")]
+    fn identity_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT identity_data FROM cryptostore_identity
+                WHERE user_id = $1
+            "#,
+        )
+    }
+
+    /// Retrieves the cryptographic identities of all users
+    #[cfg(feature = "e2e-encryption")]
+    fn identities_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT identity_data FROM cryptostore_identity
+            "#,
+        )
+    }
+
+    /// Checks whether a message is known
+    ///
+    /// # Arguments
+    /// * `$1` - The sender key
+    /// * `$2` - The message hash
+    #[cfg(feature = "e2e-encryption")]
+    fn message_known_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT 1 FROM cryptostore_message_hash
+                WHERE sender_key = $1 AND message_hash = $2
+            "#,
+        )
+    }
+
+    /// Retrieves a gossip equest by ID
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed request ID
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_fetch_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT gossip_data FROM cryptostore_gossip_request
+                WHERE request_id = $1
+            "#,
+        )
+    }
+
+    /// Retrieves a gossip equest by info
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed request info
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_info_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT gossip_data FROM cryptostore_gossip_request
+                WHERE info_key = $1
+            "#,
+        )
+    }
+
+    /// Retrieves the unsent gossip request for a secret
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed request info
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_pending_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT gossip_data FROM cryptostore_gossip_request
+                WHERE info_key = $1 AND NOT sent_out
+                ORDER BY request_id
+                LIMIT 1
+            "#,
+        )
+    }
+
+    /// Checks whether another unsent gossip request for the same recipient and secret exists
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed recipient ID
+    /// * `$2` - The hashed request info
+    /// * `$3` - The hashed request ID
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_duplicate_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT request_id FROM cryptostore_gossip_request
+                WHERE recipient_id = $1 AND info_key = $2 AND NOT sent_out AND request_id <> $3
+            "#,
+        )
+    }
+
+    /// Retrieves gossip requests by sent state
+    ///
+    /// # Arguments
+    /// * `$1` - The sent state
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_requests_sent_state_fetch_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT gossip_data FROM cryptostore_gossip_request
+                WHERE sent_out = $1
+            "#,
+        )
+    }
+
+    /// Deletes gossip request by transaction ID
+    ///
+    /// # Arguments
+    /// * `$1` - The hashed transaction ID
+    #[cfg(feature = "e2e-encryption")]
+    fn gossip_request_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM cryptostore_gossip_request
+                WHERE request_id = $1
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl CryptoQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl CryptoQueries for sqlx::sqlite::Sqlite {
+    #[cfg(feature = "e2e-encryption")]
+    fn inbound_group_session_room_counts_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, COUNT(*) AS session_count, MIN(session_data) AS session_data
+                FROM cryptostore_inbound_group_session
+                GROUP BY room_id
+            "#,
+        )
+    }
+}
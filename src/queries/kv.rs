@@ -0,0 +1,217 @@
+//! Queries for the key-value tables, custom values, filters and the server cache
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for the key-value tables, custom values, sync filters and cached server responses
+#[allow(single_use_lifetimes)]
+pub trait KvQueries: Database {
+    /// Returns a query for upserting into the `statestore_kv` table
+    ///
+    /// # Arguments
+    /// * `$1` - The key to insert
+    /// * `$2` - The value to insert
+    fn kv_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_kv (kv_key, kv_value)
+                VALUES ($1, $2)
+                ON CONFLICT (kv_key) DO UPDATE SET kv_value = $2
+            "#,
+        )
+    }
+
+    /// Returns a query for loading from the `statestore_kv` table
+    ///
+    /// # Arguments
+    /// * `$1` - The key to load
+    fn kv_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT kv_value FROM statestore_kv WHERE kv_key = $1
+            "#,
+        )
+    }
+
+    /// Returns a query for upserting into the `statestore_custom_values` table
+    ///
+    /// # Arguments
+    /// * `$1` - The key to insert
+    /// * `$2` - The value to insert
+    fn custom_value_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_custom_values (custom_key, custom_value)
+                VALUES ($1, $2)
+                ON CONFLICT (custom_key) DO UPDATE SET custom_value = $2
+            "#,
+        )
+    }
+
+    /// Returns a query for loading from the `statestore_custom_values` table
+    ///
+    /// # Arguments
+    /// * `$1` - The key to load
+    fn custom_value_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT custom_value FROM statestore_custom_values WHERE custom_key = $1
+            "#,
+        )
+    }
+
+    /// Returns a query for deleting from the `statestore_custom_values` table
+    ///
+    /// The deleted value is returned.
+    ///
+    /// # Arguments
+    /// * `$1` - The key to delete
+    fn custom_value_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_custom_values WHERE custom_key = $1
+                RETURNING custom_value
+            "#,
+        )
+    }
+
+    /// Returns a query for loading the custom values in a key range, ordered by key
+    ///
+    /// # Arguments
+    /// * `$1` - The first key of the range
+    /// * `$2` - The first key after the range
+    fn custom_values_range_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT custom_key, custom_value FROM statestore_custom_values
+                WHERE custom_key >= $1 AND custom_key < $2
+                ORDER BY custom_key
+            "#,
+        )
+    }
+
+    /// Returns a query for loading the custom values starting at a key, ordered by key
+    ///
+    /// # Arguments
+    /// * `$1` - The first key of the range
+    fn custom_values_from_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT custom_key, custom_value FROM statestore_custom_values
+                WHERE custom_key >= $1
+                ORDER BY custom_key
+            "#,
+        )
+    }
+
+    /// Returns a query for upserting into the `statestore_filters` table
+    ///
+    /// # Arguments
+    /// * `$1` - The filter name
+    /// * `$2` - The filter id
+    /// * `$3` - The current unix timestamp
+    fn filter_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_filters (filter_name, filter_id, updated_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (filter_name) DO UPDATE SET filter_id = $2, updated_at = $3
+            "#,
+        )
+    }
+
+    /// Returns a query for loading from the `statestore_filters` table
+    ///
+    /// # Arguments
+    /// * `$1` - The filter name
+    fn filter_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT filter_id FROM statestore_filters WHERE filter_name = $1
+            "#,
+        )
+    }
+
+    /// Returns a query for listing all stored filters
+    fn filters_list_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT filter_name, filter_id, updated_at FROM statestore_filters
+                ORDER BY filter_name
+            "#,
+        )
+    }
+
+    /// Returns a query for deleting a filter
+    ///
+    /// # Arguments
+    /// * `$1` - The filter name
+    fn filter_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_filters WHERE filter_name = $1
+            "#,
+        )
+    }
+
+    /// Returns a query for deleting filters that have not been updated since the given time
+    ///
+    /// # Arguments
+    /// * `$1` - The unix timestamp before which filters are considered stale
+    fn filters_delete_stale_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_filters WHERE updated_at < $1
+            "#,
+        )
+    }
+
+    /// Returns a query for upserting into the `statestore_server_cache` table
+    ///
+    /// # Arguments
+    /// * `$1` - The cache key
+    /// * `$2` - The JSON response
+    /// * `$3` - The unix timestamp after which the response expires
+    fn server_cache_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_server_cache (cache_key, response, expires_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (cache_key) DO UPDATE SET response = $2, expires_at = $3
+            "#,
+        )
+    }
+
+    /// Returns a query for loading an unexpired response from the `statestore_server_cache` table
+    ///
+    /// # Arguments
+    /// * `$1` - The cache key
+    /// * `$2` - The current unix timestamp
+    fn server_cache_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT response FROM statestore_server_cache
+                WHERE cache_key = $1 AND expires_at > $2
+            "#,
+        )
+    }
+
+    /// Returns a query for deleting all expired responses from the `statestore_server_cache` table
+    ///
+    /// # Arguments
+    /// * `$1` - The current unix timestamp
+    fn server_cache_expire_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_server_cache WHERE expires_at <= $1
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl KvQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl KvQueries for sqlx::sqlite::Sqlite {}
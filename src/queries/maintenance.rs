@@ -0,0 +1,300 @@
+//! Queries for integrity checks, storage statistics and the audit log
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for integrity checks, orphan cleanup, quarantined rooms, storage statistics and the
+/// audit log
+#[allow(single_use_lifetimes)]
+pub trait MaintenanceQueries: Database {
+    /// Returns a query counting rows that violate referential consistency
+    fn integrity_check_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT
+                    (SELECT COUNT(*) FROM statestore_members m
+                     WHERE NOT EXISTS (SELECT 1 FROM statestore_rooms r WHERE r.room_id = m.room_id)
+                    ) AS orphaned_members,
+                    (SELECT COUNT(*) FROM statestore_state s
+                     WHERE NOT EXISTS (SELECT 1 FROM statestore_rooms r WHERE r.room_id = s.room_id)
+                    ) AS orphaned_state_events,
+                    (SELECT COUNT(*) FROM statestore_receipts c
+                     WHERE NOT EXISTS (SELECT 1 FROM statestore_rooms r WHERE r.room_id = c.room_id)
+                    ) AS orphaned_receipts,
+                    (SELECT COUNT(*) FROM statestore_accountdata a
+                     WHERE a.room_id <> ''
+                       AND NOT EXISTS (SELECT 1 FROM statestore_rooms r WHERE r.room_id = a.room_id)
+                    ) AS orphaned_account_data,
+                    (SELECT COUNT(*) FROM statestore_members m
+                     JOIN statestore_rooms r ON r.room_id = m.room_id
+                     WHERE m.is_partial <> r.is_partial
+                    ) AS partial_member_mismatches,
+                    (SELECT COUNT(*) FROM statestore_state s
+                     JOIN statestore_rooms r ON r.room_id = s.room_id
+                     WHERE s.is_partial <> r.is_partial
+                    ) AS partial_state_mismatches
+            "#,
+        )
+    }
+
+    /// Returns a query deleting members of unknown rooms
+    fn orphaned_members_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_members
+                WHERE room_id NOT IN (SELECT room_id FROM statestore_rooms)
+            "#,
+        )
+    }
+
+    /// Returns a query deleting display names of unknown rooms
+    fn orphaned_display_names_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_display_names
+                WHERE room_id NOT IN (SELECT room_id FROM statestore_rooms)
+            "#,
+        )
+    }
+
+    /// Returns a query deleting state events of unknown rooms
+    fn orphaned_state_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_state
+                WHERE room_id NOT IN (SELECT room_id FROM statestore_rooms)
+            "#,
+        )
+    }
+
+    /// Returns a query deleting receipts of unknown rooms
+    fn orphaned_receipts_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_receipts
+                WHERE room_id NOT IN (SELECT room_id FROM statestore_rooms)
+            "#,
+        )
+    }
+
+    /// Returns a query deleting room account data of unknown rooms
+    fn orphaned_account_data_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_accountdata
+                WHERE room_id <> '' AND room_id NOT IN (SELECT room_id FROM statestore_rooms)
+            "#,
+        )
+    }
+
+    /// Counts the entries and their serialized size per room and category
+    ///
+    /// The category is one of `state`, `members` and `receipts`.
+    fn room_storage_stats_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, 'state' AS category, COUNT(*) AS entries,
+                    CAST(COALESCE(SUM(
+                        OCTET_LENGTH(COALESCE(CAST(state_event AS TEXT), ''))
+                        + COALESCE(OCTET_LENGTH(state_event_data), 0)
+                    ), 0) AS BIGINT) AS bytes
+                FROM statestore_state GROUP BY room_id
+                UNION ALL
+                SELECT room_id, 'members', COUNT(*),
+                    CAST(COALESCE(SUM(
+                        OCTET_LENGTH(COALESCE(CAST(member_event AS TEXT), ''))
+                        + COALESCE(OCTET_LENGTH(member_event_data), 0)
+                        + OCTET_LENGTH(COALESCE(CAST(user_profile AS TEXT), ''))
+                    ), 0) AS BIGINT)
+                FROM statestore_members GROUP BY room_id
+                UNION ALL
+                SELECT room_id, 'receipts', COUNT(*),
+                    CAST(COALESCE(SUM(OCTET_LENGTH(CAST(receipt AS TEXT))), 0) AS BIGINT)
+                FROM statestore_receipts GROUP BY room_id
+            "#,
+        )
+    }
+
+    /// Returns a trivial query checking that the database is reachable
+    fn health_check_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("SELECT 1")
+    }
+
+    /// Moves a row that failed to deserialize into the quarantine
+    ///
+    /// # Arguments
+    /// * `$1` - The table the row was stored in
+    /// * `$2` - The key of the row
+    /// * `$3` - The stored data of the row
+    /// * `$4` - The deserialization error
+    /// * `$5` - The current unix timestamp
+    fn quarantine_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_quarantine
+                    (source_table, row_key, row_data, error, quarantined_at)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (source_table, row_key)
+                DO UPDATE SET row_data = $3, error = $4, quarantined_at = $5
+            "#,
+        )
+    }
+
+    /// Deletes a room info that was moved into the quarantine
+    ///
+    /// The row is only deleted if it has not been replaced in the meantime.
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The stored data of the row
+    fn room_info_quarantine_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_rooms
+                WHERE room_id = $1 AND CAST(room_info AS TEXT) = $2
+            "#,
+        )
+    }
+
+    /// Lists all rows in the quarantine
+    fn quarantine_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT source_table, row_key, row_data, error, quarantined_at
+                FROM statestore_quarantine
+                ORDER BY quarantined_at, source_table, row_key
+            "#,
+        )
+    }
+
+    /// Returns a query deleting audit log entries that were written before the given time
+    ///
+    /// # Arguments
+    /// * `$1` - The unix timestamp before which entries are deleted
+    fn audit_log_prune_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("DELETE FROM statestore_audit_log WHERE changed_at < $1")
+    }
+
+    /// Returns the queries re-deriving the indexed columns of members and state events
+    ///
+    /// Members that have neither joined nor been invited are removed, the display name, join
+    /// status and stripped flag of the other members are taken from their member event, and
+    /// the stripped flag of state events from whether they have an event ID. Members and state
+    /// events that are stored in another format than JSON keep their columns. The display names
+    /// are recreated from the members afterwards.
+    fn index_rebuild_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query(
+                r#"
+                    DELETE FROM statestore_members
+                    WHERE member_event IS NOT NULL
+                        AND COALESCE(member_event->'content'->>'membership', '')
+                            NOT IN ('join', 'invite')
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    UPDATE statestore_members SET
+                        is_partial = member_event->'event_id' IS NULL,
+                        displayname = CASE
+                            WHEN jsonb_typeof(member_event->'content'->'displayname') = 'string'
+                            THEN member_event->'content'->>'displayname'
+                        END,
+                        joined = member_event->'content'->>'membership' = 'join'
+                    WHERE member_event IS NOT NULL
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    UPDATE statestore_state SET is_partial = state_event->'event_id' IS NULL
+                    WHERE state_event IS NOT NULL
+                "#,
+            ),
+            prefixed_query("DELETE FROM statestore_display_names"),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_display_names (room_id, user_id, display_name)
+                    SELECT room_id, user_id, displayname FROM statestore_members
+                    WHERE displayname IS NOT NULL
+                "#,
+            ),
+        ]
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl MaintenanceQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl MaintenanceQueries for sqlx::sqlite::Sqlite {
+    fn room_storage_stats_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, 'state' AS category, COUNT(*) AS entries,
+                    CAST(COALESCE(SUM(
+                        COALESCE(LENGTH(CAST(state_event AS BLOB)), 0)
+                        + COALESCE(LENGTH(state_event_data), 0)
+                    ), 0) AS BIGINT) AS bytes
+                FROM statestore_state GROUP BY room_id
+                UNION ALL
+                SELECT room_id, 'members', COUNT(*),
+                    CAST(COALESCE(SUM(
+                        COALESCE(LENGTH(CAST(member_event AS BLOB)), 0)
+                        + COALESCE(LENGTH(member_event_data), 0)
+                        + COALESCE(LENGTH(CAST(user_profile AS BLOB)), 0)
+                    ), 0) AS BIGINT)
+                FROM statestore_members GROUP BY room_id
+                UNION ALL
+                SELECT room_id, 'receipts', COUNT(*),
+                    CAST(COALESCE(SUM(LENGTH(CAST(receipt AS BLOB))), 0) AS BIGINT)
+                FROM statestore_receipts GROUP BY room_id
+            "#,
+        )
+    }
+
+    fn index_rebuild_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query(
+                r#"
+                    DELETE FROM statestore_members
+                    WHERE member_event IS NOT NULL
+                        AND COALESCE(json_extract(member_event, '$.content.membership'), '')
+                            NOT IN ('join', 'invite')
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    UPDATE statestore_members SET
+                        is_partial = json_type(member_event, '$.event_id') IS NULL,
+                        displayname = CASE
+                            WHEN json_type(member_event, '$.content.displayname') = 'text'
+                            THEN json_extract(member_event, '$.content.displayname')
+                        END,
+                        joined = json_extract(member_event, '$.content.membership') = 'join'
+                    WHERE member_event IS NOT NULL
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    UPDATE statestore_state
+                    SET is_partial = json_type(state_event, '$.event_id') IS NULL
+                    WHERE state_event IS NOT NULL
+                "#,
+            ),
+            prefixed_query("DELETE FROM statestore_display_names"),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_display_names (room_id, user_id, display_name)
+                    SELECT room_id, user_id, displayname FROM statestore_members
+                    WHERE displayname IS NOT NULL
+                "#,
+            ),
+        ]
+    }
+}
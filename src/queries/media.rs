@@ -0,0 +1,326 @@
+//! Queries for the media store
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for media content, media blobs and the negative media cache
+#[allow(single_use_lifetimes)]
+pub trait MediaQueries: Database {
+    /// Returns a query for loading from the `statestore_media` table
+    ///
+    /// Returns `NULL` if the shared content of the media is missing.
+    ///
+    /// # Arguments
+    /// * `$1` - The mxc URL to load
+    /// * `$2` - The width of the thumbnail, or 0 for the original file
+    /// * `$3` - The height of the thumbnail, or 0 for the original file
+    /// * `$4` - The resizing method of the thumbnail, or an empty string for the original file
+    fn media_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_media
+                SET last_access = NOW()
+                WHERE media_url = $1
+                  AND thumbnail_width = $2
+                  AND thumbnail_height = $3
+                  AND thumbnail_method = $4
+                RETURNING CASE
+                    WHEN content_hash IS NULL THEN media_data
+                    ELSE (SELECT media_data FROM statestore_media_blobs
+                          WHERE statestore_media_blobs.content_hash = statestore_media.content_hash)
+                END AS media_data
+            "#,
+        )
+    }
+
+    /// Returns the first query for storing into the `statestore_media` table
+    ///
+    /// New thumbnails of pinned media are pinned as well.
+    ///
+    /// # Arguments
+    /// * `$1` - The key to insert
+    /// * `$2` - The value to insert, empty if the value is stored in `statestore_media_blobs`
+    /// * `$3` - The content hash of the value in `statestore_media_blobs`, if any
+    /// * `$4` - The size of the value in bytes
+    /// * `$5` - The width of the thumbnail, or 0 for the original file
+    /// * `$6` - The height of the thumbnail, or 0 for the original file
+    /// * `$7` - The resizing method of the thumbnail, or an empty string for the original file
+    fn media_insert_query_1<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_media
+                    (media_url, media_data, content_hash, media_size,
+                     thumbnail_width, thumbnail_height, thumbnail_method, last_access, pinned)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(),
+                        EXISTS (SELECT 1 FROM statestore_media WHERE media_url = $1 AND pinned))
+                ON CONFLICT (media_url, thumbnail_width, thumbnail_height, thumbnail_method) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Evicts the least recently accessed media until the total size fits into the budget
+    ///
+    /// Ties in the access time are broken by the mxc URL and thumbnail size, so that the eviction
+    /// is deterministic.
+    /// Pinned media is neither evicted nor counted against the budget. Returns the content hashes
+    /// of the evicted media.
+    ///
+    /// # Arguments
+    /// * `$1` - The maximum total size of the media in bytes
+    fn media_evict_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_media
+                WHERE (media_url, thumbnail_width, thumbnail_height, thumbnail_method) IN
+                    (SELECT media_url, thumbnail_width, thumbnail_height, thumbnail_method FROM
+                        (SELECT media_url, thumbnail_width, thumbnail_height, thumbnail_method,
+                                SUM(media_size) OVER (
+                                    ORDER BY last_access DESC, media_url DESC, thumbnail_width DESC,
+                                             thumbnail_height DESC, thumbnail_method DESC
+                                ) AS total_size
+                         FROM statestore_media
+                         WHERE NOT pinned) AS sizes
+                     WHERE total_size > $1)
+                RETURNING content_hash
+            "#,
+        )
+    }
+
+    /// Stores deduplicated media contents in the `statestore_media_blobs` table, or adds a
+    /// reference to existing contents
+    ///
+    /// Updating the reference count locks existing contents, so that they cannot be deleted by a
+    /// concurrent transaction before the referencing media is committed.
+    ///
+    /// # Arguments
+    /// * `$1` - The content hash
+    /// * `$2` - The media contents
+    fn media_blob_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_media_blobs (content_hash, media_data, ref_count)
+                VALUES ($1, $2, 1)
+                ON CONFLICT (content_hash) DO UPDATE
+                SET ref_count = statestore_media_blobs.ref_count + 1
+            "#,
+        )
+    }
+
+    /// Removes references to media contents
+    ///
+    /// # Arguments
+    /// * `$1` - The content hash
+    /// * `$2` - The number of removed references
+    fn media_blob_release_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_media_blobs SET ref_count = ref_count - $2
+                WHERE content_hash = $1
+            "#,
+        )
+    }
+
+    /// Deletes media contents if they are no longer referenced by any mxc URL
+    ///
+    /// # Arguments
+    /// * `$1` - The content hash
+    fn media_blob_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_media_blobs
+                WHERE content_hash = $1 AND ref_count <= 0
+            "#,
+        )
+    }
+
+    /// Deletes the media with the mxc URL, including all of its thumbnails
+    ///
+    /// Returns the content hashes of the deleted media.
+    ///
+    /// # Arguments
+    /// * `$1` - The mxc URL
+    fn media_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_media
+                WHERE media_url = $1
+                RETURNING content_hash
+            "#,
+        )
+    }
+
+    /// Pins or unpins the media with the mxc URL, including all of its thumbnails
+    ///
+    /// # Arguments
+    /// * `$1` - The mxc URL
+    /// * `$2` - Whether the media is pinned
+    fn media_pin_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_media SET pinned = $2
+                WHERE media_url = $1
+            "#,
+        )
+    }
+
+    /// Deletes the files and thumbnails of the media with the new mxc URL that also exist for the
+    /// old mxc URL
+    ///
+    /// Returns the content hashes of the deleted media.
+    ///
+    /// # Arguments
+    /// * `$1` - The old mxc URL
+    /// * `$2` - The new mxc URL
+    fn media_remap_conflicts_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_media
+                WHERE media_url = $2
+                  AND (thumbnail_width, thumbnail_height, thumbnail_method) IN
+                    (SELECT thumbnail_width, thumbnail_height, thumbnail_method
+                     FROM statestore_media WHERE media_url = $1)
+                RETURNING content_hash
+            "#,
+        )
+    }
+
+    /// Moves the media with the old mxc URL to the new mxc URL
+    ///
+    /// # Arguments
+    /// * `$1` - The old mxc URL
+    /// * `$2` - The new mxc URL
+    fn media_remap_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_media SET media_url = $2
+                WHERE media_url = $1
+            "#,
+        )
+    }
+
+    /// Deletes a single file or thumbnail of the media with the mxc URL
+    ///
+    /// Returns the content hashes of the deleted media.
+    ///
+    /// # Arguments
+    /// * `$1` - The mxc URL
+    /// * `$2` - The width of the thumbnail, or 0 for the original file
+    /// * `$3` - The height of the thumbnail, or 0 for the original file
+    /// * `$4` - The resizing method of the thumbnail, or an empty string for the original file
+    fn media_format_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_media
+                WHERE media_url = $1
+                  AND thumbnail_width = $2
+                  AND thumbnail_height = $3
+                  AND thumbnail_method = $4
+                RETURNING content_hash
+            "#,
+        )
+    }
+
+    /// Upserts an entry into the negative cache
+    ///
+    /// # Arguments
+    /// * `$1` - The cache key
+    /// * `$2` - The unix timestamp after which the entry expires
+    fn negative_cache_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_negative_cache (cache_key, expires_at)
+                VALUES ($1, $2)
+                ON CONFLICT (cache_key) DO UPDATE SET expires_at = $2
+            "#,
+        )
+    }
+
+    /// Checks whether an unexpired entry exists in the negative cache
+    ///
+    /// # Arguments
+    /// * `$1` - The cache key
+    /// * `$2` - The current unix timestamp
+    fn negative_cache_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT 1 FROM statestore_negative_cache
+                WHERE cache_key = $1 AND expires_at > $2
+            "#,
+        )
+    }
+
+    /// Deletes an entry from the negative cache
+    ///
+    /// # Arguments
+    /// * `$1` - The cache key
+    fn negative_cache_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_negative_cache
+                WHERE cache_key = $1
+            "#,
+        )
+    }
+
+    /// Deletes all expired entries from the negative cache
+    ///
+    /// # Arguments
+    /// * `$1` - The current unix timestamp
+    fn negative_cache_expire_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_negative_cache
+                WHERE expires_at <= $1
+            "#,
+        )
+    }
+
+    /// Counts the entries and the total size of the media store
+    fn media_storage_stats_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT COUNT(*) AS entries, CAST(COALESCE(SUM(media_size), 0) AS BIGINT) AS bytes
+                FROM statestore_media
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl MediaQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl MediaQueries for sqlx::sqlite::Sqlite {
+    fn media_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_media
+                SET last_access = datetime(CURRENT_TIMESTAMP, 'localtime')
+                WHERE media_url = $1
+                  AND thumbnail_width = $2
+                  AND thumbnail_height = $3
+                  AND thumbnail_method = $4
+                RETURNING CASE
+                    WHEN content_hash IS NULL THEN media_data
+                    ELSE (SELECT media_data FROM statestore_media_blobs
+                          WHERE statestore_media_blobs.content_hash = statestore_media.content_hash)
+                END AS media_data
+            "#,
+        )
+    }
+
+    fn media_insert_query_1<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_media
+                    (media_url, media_data, content_hash, media_size,
+                     thumbnail_width, thumbnail_height, thumbnail_method, last_access, pinned)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, datetime(CURRENT_TIMESTAMP, 'localtime'),
+                        EXISTS (SELECT 1 FROM statestore_media WHERE media_url = $1 AND pinned))
+                ON CONFLICT (media_url, thumbnail_width, thumbnail_height, thumbnail_method) DO NOTHING
+            "#,
+        )
+    }
+}
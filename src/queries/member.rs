@@ -0,0 +1,394 @@
+//! Queries for room members and presence
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for room members, profiles, display names, member history and presence
+#[allow(single_use_lifetimes)]
+pub trait MemberQueries: Database {
+    /// Appends a membership change to the member history
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    /// * `$3` - The new membership
+    /// * `$4` - The event ID of the member event
+    /// * `$5` - The current unix timestamp
+    fn member_history_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_member_history
+                    (room_id, user_id, membership, event_id, recorded_at)
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+    }
+
+    /// Retrieves the membership changes of a user in a room, oldest first
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    fn member_history_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT membership, event_id, recorded_at FROM statestore_member_history
+                WHERE room_id = $1 AND user_id = $2
+                ORDER BY id
+            "#,
+        )
+    }
+
+    /// Upserts user presence data
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID
+    /// * `$2` - The presence data
+    fn presence_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_presence
+                    (user_id, presence)
+                VALUES ($1, $2)
+                ON CONFLICT(user_id) DO UPDATE SET presence = $2
+            "#,
+        )
+    }
+
+    /// Retrieves user presence data
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID
+    fn presence_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT presence FROM statestore_presence
+                WHERE user_id = $1
+            "#,
+        )
+    }
+
+    /// Retrieves user presence data for multiple users
+    ///
+    /// # Arguments
+    /// * `$1` - The user IDs as a JSON array
+    fn presence_bulk_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, presence FROM statestore_presence
+                WHERE user_id IN (SELECT jsonb_array_elements_text($1::jsonb))
+            "#,
+        )
+    }
+
+    /// Upserts room membership information
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    /// * `$3` - Whether or not the membership event is stripped
+    /// * `$4` - The membership event as JSON, if it is stored as JSON
+    /// * `$5` - The display name of the user
+    /// * `$6` - Whether or not the user has joined
+    /// * `$7` - The serialized membership event, if it is stored in another format
+    fn member_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_members
+                    (room_id, user_id, is_partial, member_event, displayname, joined, member_event_data)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT(room_id, user_id) DO UPDATE SET is_partial = $3, member_event = $4, displayname = $5, joined = $6, member_event_data = $7
+            "#,
+        )
+    }
+
+    /// Upserts user profile information
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    /// * `$3` - Whether or not the information is partial
+    /// * `$4` - The profile event content
+    fn member_profile_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_members
+                    (room_id, user_id, is_partial, user_profile)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT(room_id, user_id) DO UPDATE SET user_profile = $4
+            "#,
+        )
+    }
+
+    /// Upserts the global profile of a user
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID
+    /// * `$2` - The display name
+    /// * `$3` - The avatar URL
+    /// * `$4` - The current unix timestamp
+    /// * `$5` - The `origin_server_ts` of the member event the profile was taken from
+    ///
+    /// Profiles taken from older member events than the stored one are ignored.
+    fn global_profile_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_profiles
+                    (user_id, displayname, avatar_url, updated_at, origin_server_ts)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT(user_id) DO UPDATE
+                SET displayname = $2, avatar_url = $3, updated_at = $4, origin_server_ts = $5
+                WHERE statestore_profiles.origin_server_ts <= $5
+            "#,
+        )
+    }
+
+    /// Retrieves the user profile event for a user in a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    fn profile_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_profile FROM statestore_members
+                WHERE room_id = $1 AND user_id = $2 AND user_profile IS NOT NULL
+            "#,
+        )
+    }
+
+    /// Retrieves the global profile of a user
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID
+    fn global_profile_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT displayname, avatar_url, updated_at, origin_server_ts
+                FROM statestore_profiles
+                WHERE user_id = $1
+            "#,
+        )
+    }
+
+    /// Removes a member from a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    fn member_remove_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_members
+                WHERE room_id = $1 AND user_id = $2
+            "#,
+        )
+    }
+
+    /// List all users in a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn members_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id FROM statestore_members
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Upserts the display name of a room member
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    /// * `$3` - The display name
+    fn display_name_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_display_names (room_id, user_id, display_name)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (room_id, user_id) DO UPDATE SET display_name = $3
+            "#,
+        )
+    }
+
+    /// Removes the display name of a room member
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    fn display_name_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_display_names
+                WHERE room_id = $1 AND user_id = $2
+            "#,
+        )
+    }
+
+    /// Removes the display names of the members of a room that are only known from stripped
+    /// state
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn stripped_display_names_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_display_names
+                WHERE room_id = $1 AND user_id IN (
+                    SELECT user_id FROM statestore_members
+                    WHERE room_id = $1 AND is_partial = '1'
+                )
+            "#,
+        )
+    }
+
+    /// List all users in a room that are only known from stripped state
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn stripped_members_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id FROM statestore_members
+                WHERE room_id = $1 AND is_partial = '1'
+            "#,
+        )
+    }
+
+    /// Removes the members of a room that are only known from stripped state
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn stripped_members_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_members
+                WHERE room_id = $1 AND is_partial = '1'
+            "#,
+        )
+    }
+
+    /// List all users in a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - Whether or not the user has joined
+    fn members_load_query_with_join_status<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id FROM statestore_members
+                WHERE room_id = $1 AND joined = $2
+            "#,
+        )
+    }
+
+    /// Count the users in a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - Whether or not the user has joined, or `NULL` to count all users
+    fn members_count_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT COUNT(*) AS member_count FROM statestore_members
+                WHERE room_id = $1 AND ($2 IS NULL OR joined = $2)
+            "#,
+        )
+    }
+
+    /// List a page of users in a room, ordered by user ID
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - Whether or not the user has joined, or `NULL` to list all users
+    /// * `$3` - The maximum number of users to return
+    /// * `$4` - The number of users to skip
+    fn members_page_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id FROM statestore_members
+                WHERE room_id = $1 AND ($2 IS NULL OR joined = $2)
+                ORDER BY user_id
+                LIMIT $3 OFFSET $4
+            "#,
+        )
+    }
+
+    /// Get specific member event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The user ID
+    fn member_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT is_partial, member_event, member_event_data FROM statestore_members
+                WHERE room_id = $1 AND user_id = $2
+                    AND (member_event IS NOT NULL OR member_event_data IS NOT NULL)
+            "#,
+        )
+    }
+
+    /// Get users with display name in room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The display name
+    fn users_with_display_name_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id FROM statestore_display_names
+                WHERE room_id = $1 AND display_name = $2
+            "#,
+        )
+    }
+
+    /// Get users with any of the given display names in room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The display names as a JSON array
+    fn users_with_display_names_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT display_name, user_id FROM statestore_display_names
+                WHERE room_id = $1
+                    AND display_name IN (SELECT jsonb_array_elements_text($2::jsonb))
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl MemberQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl MemberQueries for sqlx::sqlite::Sqlite {
+    fn presence_bulk_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, presence FROM statestore_presence
+                WHERE user_id IN (SELECT value FROM json_each($1))
+            "#,
+        )
+    }
+
+    fn users_with_display_names_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT display_name, user_id FROM statestore_display_names
+                WHERE room_id = $1 AND display_name IN (SELECT value FROM json_each($2))
+            "#,
+        )
+    }
+}
@@ -0,0 +1,1016 @@
+//! Queries for rooms and their account data
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for room infos, room tombstones, account data, spaces, latest events, tags, direct chats
+/// and receipts
+#[allow(single_use_lifetimes)]
+pub trait RoomQueries: Database {
+    /// Copies the data of a room into the tombstone tables
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    #[must_use]
+    fn room_tombstone_copy_queries<'q>(
+    ) -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_rooms_tombstone
+                    SELECT * FROM statestore_rooms WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_accountdata_tombstone
+                    SELECT * FROM statestore_accountdata WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_members_tombstone
+                    SELECT * FROM statestore_members WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_display_names_tombstone
+                    SELECT * FROM statestore_display_names WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_state_tombstone
+                    SELECT * FROM statestore_state WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_receipts_tombstone
+                    SELECT * FROM statestore_receipts WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_room_tags_tombstone
+                    SELECT * FROM statestore_room_tags WHERE room_id = $1
+                "#,
+            ),
+        ]
+    }
+
+    /// Copies the data of a room from the tombstone tables back into the regular tables
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    #[must_use]
+    fn room_tombstone_restore_queries<'q>(
+    ) -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_rooms
+                    SELECT * FROM statestore_rooms_tombstone WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_accountdata
+                    SELECT * FROM statestore_accountdata_tombstone WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_members
+                    SELECT * FROM statestore_members_tombstone WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_display_names
+                    SELECT * FROM statestore_display_names_tombstone WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_state
+                    SELECT * FROM statestore_state_tombstone WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_receipts
+                    SELECT * FROM statestore_receipts_tombstone WHERE room_id = $1
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_room_tags
+                    SELECT * FROM statestore_room_tags_tombstone WHERE room_id = $1
+                "#,
+            ),
+        ]
+    }
+
+    /// Deletes the tombstone of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    #[must_use]
+    fn room_tombstone_delete_queries<'q>(
+    ) -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query("DELETE FROM statestore_room_tombstones WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_rooms_tombstone WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_accountdata_tombstone WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_members_tombstone WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_display_names_tombstone WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_state_tombstone WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_receipts_tombstone WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_room_tags_tombstone WHERE room_id = $1"),
+        ]
+    }
+
+    /// Records the tombstone of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The current unix timestamp
+    fn room_tombstone_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_room_tombstones (room_id, removed_at)
+                VALUES ($1, $2)
+            "#,
+        )
+    }
+
+    /// Lists the rooms whose tombstones are older than the given time
+    ///
+    /// # Arguments
+    /// * `$1` - The unix timestamp before which tombstones are expired
+    fn expired_room_tombstones_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT room_id FROM statestore_room_tombstones WHERE removed_at < $1
+            "#,
+        )
+    }
+
+    /// Checks whether a room has a tombstone
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn room_tombstone_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT removed_at FROM statestore_room_tombstones WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes a room given its ID
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    #[must_use]
+    fn room_remove_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>> {
+        vec![
+            prefixed_query("DELETE FROM statestore_rooms WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_accountdata WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_members WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_display_names WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_state WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_receipts WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_room_tags WHERE room_id = $1"),
+            prefixed_query("DELETE FROM statestore_aliases WHERE room_id = $1"),
+            prefixed_query(
+                r#"
+                    DELETE FROM statestore_room_upgrades
+                    WHERE (event_type = 'm.room.tombstone' AND old_room_id = $1)
+                        OR (event_type = 'm.room.create' AND new_room_id = $1)
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    DELETE FROM statestore_space_edges
+                    WHERE (event_type = 'm.space.child' AND parent_id = $1)
+                        OR (event_type = 'm.space.parent' AND child_id = $1)
+                "#,
+            ),
+        ]
+    }
+
+    /// Upserts account data
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID for the account data
+    /// * `$2` - The account data event type
+    /// * `$3` - The account data event content
+    fn account_data_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_accountdata
+                    (room_id, event_type, account_data)
+                VALUES ($1, $2, $3)
+                ON CONFLICT(room_id, event_type) DO UPDATE SET account_data = $3
+            "#,
+        )
+    }
+
+    /// Deletes account data
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID for the account data
+    /// * `$2` - The account data event type
+    fn account_data_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_accountdata
+                WHERE room_id = $1 AND event_type = $2
+            "#,
+        )
+    }
+
+    /// Removes an edge of the space hierarchy
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID of the parent
+    /// * `$2` - The room ID of the child
+    /// * `$3` - The type of the state event defining the edge
+    fn space_edge_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_space_edges
+                WHERE parent_id = $1 AND child_id = $2 AND event_type = $3
+            "#,
+        )
+    }
+
+    /// Inserts an edge of the space hierarchy
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID of the parent
+    /// * `$2` - The room ID of the child
+    /// * `$3` - The type of the state event defining the edge
+    fn space_edge_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_space_edges (parent_id, child_id, event_type)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (parent_id, child_id, event_type) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Recreates the edges of the space hierarchy defined by the state of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn space_edges_rebuild_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_space_edges (parent_id, child_id, event_type)
+                SELECT
+                    CASE WHEN event_type = 'm.space.child' THEN room_id ELSE state_key END,
+                    CASE WHEN event_type = 'm.space.child' THEN state_key ELSE room_id END,
+                    event_type
+                FROM statestore_state
+                WHERE room_id = $1
+                    AND event_type IN ('m.space.child', 'm.space.parent')
+                    AND is_partial = '0'
+                    AND CASE
+                        WHEN jsonb_typeof(state_event->'content'->'via') = 'array'
+                        THEN jsonb_array_length(state_event->'content'->'via') > 0
+                        ELSE false
+                    END
+                ON CONFLICT (parent_id, child_id, event_type) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Lists the children of a space
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID of the space
+    fn space_children_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT child_id FROM statestore_space_edges
+                WHERE parent_id = $1 AND event_type = 'm.space.child'
+            "#,
+        )
+    }
+
+    /// Lists the parents of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn space_parents_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT DISTINCT parent_id FROM statestore_space_edges
+                WHERE child_id = $1
+            "#,
+        )
+    }
+
+    /// Removes the room upgrade defined by a state event of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The type of the state event, `m.room.tombstone` or `m.room.create`
+    fn room_upgrade_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_room_upgrades
+                WHERE event_type = $2
+                    AND CASE WHEN $2 = 'm.room.tombstone' THEN old_room_id ELSE new_room_id END = $1
+            "#,
+        )
+    }
+
+    /// Inserts a room upgrade
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID of the upgraded room
+    /// * `$2` - The room ID of the replacement room
+    /// * `$3` - The type of the state event defining the upgrade
+    fn room_upgrade_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_room_upgrades (old_room_id, new_room_id, event_type)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (old_room_id, new_room_id, event_type) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Recreates the room upgrades defined by the state of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn room_upgrades_rebuild_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_room_upgrades (old_room_id, new_room_id, event_type)
+                SELECT
+                    CASE WHEN event_type = 'm.room.tombstone' THEN room_id
+                        ELSE state_event->'content'->'predecessor'->>'room_id' END,
+                    CASE WHEN event_type = 'm.room.tombstone'
+                        THEN state_event->'content'->>'replacement_room' ELSE room_id END,
+                    event_type
+                FROM statestore_state
+                WHERE room_id = $1 AND state_key = '' AND is_partial = '0'
+                    AND CASE
+                        WHEN event_type = 'm.room.tombstone'
+                        THEN jsonb_typeof(state_event->'content'->'replacement_room') = 'string'
+                        WHEN event_type = 'm.room.create'
+                        THEN jsonb_typeof(state_event->'content'->'predecessor'->'room_id') = 'string'
+                        ELSE false
+                    END
+                ON CONFLICT (old_room_id, new_room_id, event_type) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Finds the room that a room replaced, preferring its `m.room.create` event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn room_predecessor_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT old_room_id FROM statestore_room_upgrades
+                WHERE new_room_id = $1
+                ORDER BY event_type, old_room_id
+                LIMIT 1
+            "#,
+        )
+    }
+
+    /// Finds the room that replaced a room, preferring its `m.room.tombstone` event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn room_successor_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT new_room_id FROM statestore_room_upgrades
+                WHERE old_room_id = $1
+                ORDER BY event_type DESC, new_room_id
+                LIMIT 1
+            "#,
+        )
+    }
+
+    /// Removes the aliases of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn aliases_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("DELETE FROM statestore_aliases WHERE room_id = $1")
+    }
+
+    /// Inserts an alias of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The alias
+    /// * `$2` - The room ID
+    /// * `$3` - Whether the alias is the canonical alias of the room
+    fn alias_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (alias, room_id) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Recreates the aliases defined by the state of a room
+    ///
+    /// The canonical alias is inserted first, so it takes precedence over alternative aliases.
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn aliases_rebuild_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>>
+    {
+        vec![
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+                    SELECT state_event->'content'->>'alias', room_id, TRUE
+                    FROM statestore_state
+                    WHERE room_id = $1
+                        AND event_type = 'm.room.canonical_alias' AND state_key = ''
+                        AND is_partial = '0'
+                        AND jsonb_typeof(state_event->'content'->'alias') = 'string'
+                    ON CONFLICT (alias, room_id) DO NOTHING
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+                    SELECT a.value #>> '{}', s.room_id, FALSE
+                    FROM statestore_state s,
+                        jsonb_array_elements(CASE
+                            WHEN jsonb_typeof(s.state_event->'content'->'alt_aliases') = 'array'
+                            THEN s.state_event->'content'->'alt_aliases'
+                            ELSE '[]'::jsonb
+                        END) a
+                    WHERE s.room_id = $1
+                        AND s.event_type = 'm.room.canonical_alias' AND s.state_key = ''
+                        AND s.is_partial = '0'
+                        AND jsonb_typeof(a.value) = 'string'
+                    ON CONFLICT (alias, room_id) DO NOTHING
+                "#,
+            ),
+        ]
+    }
+
+    /// Looks up the room of an alias, preferring rooms that use it as their canonical alias
+    ///
+    /// # Arguments
+    /// * `$1` - The alias
+    fn alias_resolve_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id FROM statestore_aliases
+                WHERE alias = $1
+                ORDER BY is_canonical DESC, room_id
+                LIMIT 1
+            "#,
+        )
+    }
+
+    /// Updates the latest event of a room unless a newer one is already stored
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event
+    /// * `$3` - The origin server timestamp of the event
+    fn latest_event_update_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_rooms SET latest_event = $2, latest_event_ts = $3
+                WHERE room_id = $1 AND (latest_event_ts IS NULL OR latest_event_ts <= $3)
+            "#,
+        )
+    }
+
+    /// Get the latest event of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn latest_event_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT latest_event FROM statestore_rooms
+                WHERE room_id = $1 AND latest_event IS NOT NULL
+            "#,
+        )
+    }
+
+    /// Get the latest events of all rooms
+    fn latest_events_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, latest_event FROM statestore_rooms
+                WHERE latest_event IS NOT NULL
+            "#,
+        )
+    }
+
+    /// Removes all tags of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn room_tags_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_room_tags WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Inserts a tag of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The tag name
+    /// * `$3` - The order of the room within the tag
+    fn room_tag_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_room_tags (room_id, tag, tag_order)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (room_id, tag) DO UPDATE SET tag_order = $3
+            "#,
+        )
+    }
+
+    /// Lists the rooms with a tag, in the order of the tag
+    ///
+    /// # Arguments
+    /// * `$1` - The tag name
+    fn rooms_with_tag_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, tag_order FROM statestore_room_tags
+                WHERE tag = $1
+                ORDER BY tag_order IS NULL, tag_order, room_id
+            "#,
+        )
+    }
+
+    /// Lists the rooms ordered by name
+    ///
+    /// Rooms without a name come last.
+    fn room_list_by_name_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT room_id, room_name, is_encrypted, room_state, latest_event_ts FROM statestore_rooms
+                ORDER BY room_name IS NULL, room_name, room_id
+            "#,
+        )
+    }
+
+    /// Lists the rooms ordered by the timestamp of their latest event, newest first
+    ///
+    /// Rooms without a latest event come last.
+    fn room_list_by_activity_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, room_name, is_encrypted, room_state, latest_event_ts FROM statestore_rooms
+                ORDER BY latest_event_ts IS NULL, latest_event_ts DESC, room_id
+            "#,
+        )
+    }
+
+    /// Removes all direct chats
+    fn direct_chats_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("DELETE FROM statestore_direct_chats")
+    }
+
+    /// Inserts a direct chat
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID of the chat partner
+    /// * `$2` - The room ID
+    fn direct_chat_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_direct_chats (user_id, room_id)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id, room_id) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Lists all direct chats
+    fn direct_chats_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, room_id FROM statestore_direct_chats
+                ORDER BY user_id, room_id
+            "#,
+        )
+    }
+
+    /// Removes all ignored users
+    fn ignored_users_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("DELETE FROM statestore_ignored_users")
+    }
+
+    /// Inserts an ignored user
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID of the ignored user
+    fn ignored_user_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_ignored_users (user_id)
+                VALUES ($1)
+                ON CONFLICT (user_id) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Checks whether a user is ignored
+    ///
+    /// # Arguments
+    /// * `$1` - The user ID
+    fn ignored_user_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("SELECT user_id FROM statestore_ignored_users WHERE user_id = $1")
+    }
+
+    /// Removes all push rules
+    fn push_rules_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query("DELETE FROM statestore_push_rules")
+    }
+
+    /// Inserts a push rule
+    ///
+    /// # Arguments
+    /// * `$1` - The kind of the rule, such as `override`
+    /// * `$2` - The ID of the rule
+    /// * `$3` - The position of the rule within its kind
+    /// * `$4` - Whether the rule is enabled
+    /// * `$5` - Whether the rule is a server default rule
+    /// * `$6` - The rule as JSON
+    fn push_rule_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_push_rules (kind, rule_id, rule_position, enabled, is_default, rule)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (kind, rule_id) DO NOTHING
+            "#,
+        )
+    }
+
+    /// Loads all push rules in order of evaluation within their kind
+    fn push_rules_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT kind, rule FROM statestore_push_rules
+                ORDER BY kind, rule_position
+            "#,
+        )
+    }
+
+    /// Retrieves account data
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID for the account data
+    /// * `$2` - The account data event type
+    fn account_data_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT account_data FROM statestore_accountdata
+                WHERE room_id = $1 AND event_type = $2
+            "#,
+        )
+    }
+
+    /// Retrieves all account data of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID for the account data
+    fn room_account_data_load_all_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT account_data FROM statestore_accountdata
+                WHERE room_id = $1
+                ORDER BY event_type
+            "#,
+        )
+    }
+
+    /// Upserts room information
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - Whether or not the state is partial
+    /// * `$3` - The room info
+    /// * `$4` - The name of the room
+    /// * `$5` - Whether or not the room is encrypted
+    /// * `$6` - The membership state of the room
+    /// * `$7` - Whether or not the room info marks the member list as complete
+    fn room_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_rooms
+                    (room_id, is_partial, room_info, room_name, is_encrypted, room_state, members_synced)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT(room_id) DO UPDATE SET is_partial = $2, room_info = $3, room_name = $4, is_encrypted = $5, room_state = $6, members_synced = $7
+            "#,
+        )
+    }
+
+    /// Checks whether a room is stored with stripped state
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn room_is_partial_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT is_partial FROM statestore_rooms
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Marks the member list of a room as complete
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn members_synced_update_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                UPDATE statestore_rooms SET members_synced = TRUE
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Checks whether the member list of a room is complete
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn members_synced_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT members_synced FROM statestore_rooms
+                WHERE room_id = $1
+            "#,
+        )
+    }
+
+    /// Upserts an event receipt
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event ID
+    /// * `$3` - The receipt type
+    /// * `$4` - The user id
+    /// * `$5` - The receipt content
+    /// * `$6` - The thread ID, or an empty string for unthreaded receipts
+    fn receipt_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_receipts
+                    (room_id, event_id, receipt_type, user_id, receipt, thread_id)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT(room_id, receipt_type, user_id, thread_id) DO UPDATE SET event_id = $2, receipt = $5
+            "#,
+        )
+    }
+
+    /// Get room infos
+    ///
+    /// # Arguments
+    /// * `$1` - Whether or not the info is partial
+    fn room_info_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, CAST(room_info AS TEXT) AS room_info FROM statestore_rooms
+                WHERE is_partial = $1
+            "#,
+        )
+    }
+
+    /// Get a page of room infos, ordered by room name
+    ///
+    /// Rooms without a name come last.
+    ///
+    /// # Arguments
+    /// * `$1` - Whether or not the info is partial
+    /// * `$2` - The maximum number of room infos to return
+    /// * `$3` - The number of room infos to skip
+    fn room_infos_by_name_page_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT room_id, CAST(room_info AS TEXT) AS room_info FROM statestore_rooms
+                WHERE is_partial = $1
+                ORDER BY room_name, room_id
+                LIMIT $2 OFFSET $3
+            "#,
+        )
+    }
+
+    /// Get a page of room infos, most recently active first
+    ///
+    /// Rooms without a latest event come last.
+    ///
+    /// # Arguments
+    /// * `$1` - Whether or not the info is partial
+    /// * `$2` - The maximum number of room infos to return
+    /// * `$3` - The number of room infos to skip
+    fn room_infos_by_activity_page_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, CAST(room_info AS TEXT) AS room_info FROM statestore_rooms
+                WHERE is_partial = $1
+                ORDER BY latest_event_ts DESC NULLS LAST, room_id
+                LIMIT $2 OFFSET $3
+            "#,
+        )
+    }
+
+    /// Get latest receipt for user in room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The receipt type
+    /// * `$3` - The user ID
+    /// * `$4` - The thread ID, or an empty string for unthreaded receipts
+    fn receipt_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT event_id, receipt FROM statestore_receipts
+                WHERE room_id = $1 AND receipt_type = $2 AND user_id = $3 AND thread_id = $4
+            "#,
+        )
+    }
+
+    /// Get all receipts for event in room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The receipt type
+    /// * `$3` - The event ID
+    fn event_receipt_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, receipt FROM statestore_receipts
+                WHERE room_id = $1 AND receipt_type = $2 AND event_id = $3
+            "#,
+        )
+    }
+
+    /// Count the receipts for event in room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The receipt type
+    /// * `$3` - The event ID
+    fn event_receipt_count_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT COUNT(*) AS receipt_count FROM statestore_receipts
+                WHERE room_id = $1 AND receipt_type = $2 AND event_id = $3
+            "#,
+        )
+    }
+
+    /// List a page of receipts for event in room, ordered by user ID
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The receipt type
+    /// * `$3` - The event ID
+    /// * `$4` - The maximum number of receipts to return
+    /// * `$5` - The number of receipts to skip
+    fn event_receipt_page_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, receipt FROM statestore_receipts
+                WHERE room_id = $1 AND receipt_type = $2 AND event_id = $3
+                ORDER BY user_id, thread_id
+                LIMIT $4 OFFSET $5
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl RoomQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl RoomQueries for sqlx::sqlite::Sqlite {
+    fn space_edges_rebuild_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_space_edges (parent_id, child_id, event_type)
+                SELECT
+                    CASE WHEN event_type = 'm.space.child' THEN room_id ELSE state_key END,
+                    CASE WHEN event_type = 'm.space.child' THEN state_key ELSE room_id END,
+                    event_type
+                FROM statestore_state
+                WHERE room_id = $1
+                    AND event_type IN ('m.space.child', 'm.space.parent')
+                    AND is_partial = '0'
+                    AND json_array_length(state_event, '$.content.via') > 0
+                ON CONFLICT (parent_id, child_id, event_type) DO NOTHING
+            "#,
+        )
+    }
+
+    fn room_upgrades_rebuild_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_room_upgrades (old_room_id, new_room_id, event_type)
+                SELECT
+                    CASE WHEN event_type = 'm.room.tombstone' THEN room_id
+                        ELSE json_extract(state_event, '$.content.predecessor.room_id') END,
+                    CASE WHEN event_type = 'm.room.tombstone'
+                        THEN json_extract(state_event, '$.content.replacement_room') ELSE room_id END,
+                    event_type
+                FROM statestore_state
+                WHERE room_id = $1 AND state_key = '' AND is_partial = '0'
+                    AND CASE
+                        WHEN event_type = 'm.room.tombstone'
+                        THEN json_type(state_event, '$.content.replacement_room') = 'text'
+                        WHEN event_type = 'm.room.create'
+                        THEN json_type(state_event, '$.content.predecessor.room_id') = 'text'
+                        ELSE 0
+                    END
+                ON CONFLICT (old_room_id, new_room_id, event_type) DO NOTHING
+            "#,
+        )
+    }
+
+    fn aliases_rebuild_queries<'q>() -> Vec<Query<'q, Self, <Self as HasArguments<'q>>::Arguments>>
+    {
+        vec![
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+                    SELECT json_extract(state_event, '$.content.alias'), room_id, 1
+                    FROM statestore_state
+                    WHERE room_id = $1
+                        AND event_type = 'm.room.canonical_alias' AND state_key = ''
+                        AND is_partial = '0'
+                        AND json_type(state_event, '$.content.alias') = 'text'
+                    ON CONFLICT (alias, room_id) DO NOTHING
+                "#,
+            ),
+            prefixed_query(
+                r#"
+                    INSERT INTO statestore_aliases (alias, room_id, is_canonical)
+                    SELECT a.value, s.room_id, 0
+                    FROM statestore_state s, json_each(s.state_event, '$.content.alt_aliases') a
+                    WHERE s.room_id = $1
+                        AND s.event_type = 'm.room.canonical_alias' AND s.state_key = ''
+                        AND s.is_partial = '0'
+                        AND json_type(s.state_event, '$.content.alt_aliases') = 'array'
+                        AND a.type = 'text'
+                    ON CONFLICT (alias, room_id) DO NOTHING
+                "#,
+            ),
+        ]
+    }
+
+    fn room_infos_by_name_page_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT room_id, CAST(room_info AS TEXT) AS room_info FROM statestore_rooms
+                WHERE is_partial = $1
+                ORDER BY room_name IS NULL, room_name, room_id
+                LIMIT $2 OFFSET $3
+            "#,
+        )
+    }
+
+    fn room_infos_by_activity_page_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, CAST(room_info AS TEXT) AS room_info FROM statestore_rooms
+                WHERE is_partial = $1
+                ORDER BY latest_event_ts IS NULL, latest_event_ts DESC, room_id
+                LIMIT $2 OFFSET $3
+            "#,
+        )
+    }
+}
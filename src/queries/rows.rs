@@ -0,0 +1,336 @@
+//! Queries for reading the raw rows of the state store tables
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries loading all rows of a state store table, used by [`StateStore::raw`]
+///
+/// JSON columns are cast to text, so that they decode the same way on every database.
+///
+/// [`StateStore::raw`]: crate::StateStore::raw
+#[allow(single_use_lifetimes)]
+pub trait RowQueries: Database {
+    /// Loads all room infos
+    fn raw_rooms_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, is_partial, CAST(room_info AS TEXT) AS room_info, CAST(latest_event AS TEXT) AS latest_event, latest_event_ts, room_name, is_encrypted, room_state, members_synced
+                FROM statestore_rooms
+                ORDER BY room_id
+            "#,
+        )
+    }
+
+    /// Loads all state events
+    fn raw_state_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, event_type, state_key, is_partial, CAST(state_event AS TEXT) AS state_event, event_id, state_event_data
+                FROM statestore_state
+                ORDER BY room_id, event_type, state_key
+            "#,
+        )
+    }
+
+    /// Loads all room members
+    fn raw_members_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, user_id, is_partial, CAST(member_event AS TEXT) AS member_event, CAST(user_profile AS TEXT) AS user_profile, displayname, joined, member_event_data
+                FROM statestore_members
+                ORDER BY room_id, user_id
+            "#,
+        )
+    }
+
+    /// Loads all display names of room members
+    fn raw_display_names_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT room_id, user_id, display_name
+                FROM statestore_display_names
+                ORDER BY room_id, display_name, user_id
+            "#,
+        )
+    }
+
+    /// Loads all membership history
+    fn raw_member_history_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT id, room_id, user_id, membership, event_id, recorded_at
+                FROM statestore_member_history
+                ORDER BY id
+            "#,
+        )
+    }
+
+    /// Loads all receipts
+    fn raw_receipts_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, event_id, receipt_type, user_id, thread_id, CAST(receipt AS TEXT) AS receipt
+                FROM statestore_receipts
+                ORDER BY room_id, receipt_type, user_id, thread_id
+            "#,
+        )
+    }
+
+    /// Loads all global and room account data
+    fn raw_account_data_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, event_type, CAST(account_data AS TEXT) AS account_data
+                FROM statestore_accountdata
+                ORDER BY room_id, event_type
+            "#,
+        )
+    }
+
+    /// Loads all room tags
+    fn raw_room_tags_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, tag, tag_order
+                FROM statestore_room_tags
+                ORDER BY room_id, tag
+            "#,
+        )
+    }
+
+    /// Loads all direct chats
+    fn raw_direct_chats_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, room_id
+                FROM statestore_direct_chats
+                ORDER BY user_id, room_id
+            "#,
+        )
+    }
+
+    /// Loads all ignored users
+    fn raw_ignored_users_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT user_id
+                FROM statestore_ignored_users
+                ORDER BY user_id
+            "#,
+        )
+    }
+
+    /// Loads all push rules
+    fn raw_push_rules_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT kind, rule_id, rule_position, enabled, is_default, rule
+                FROM statestore_push_rules
+                ORDER BY kind, rule_position
+            "#,
+        )
+    }
+
+    /// Loads all room aliases
+    fn raw_aliases_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT alias, room_id, is_canonical
+                FROM statestore_aliases
+                ORDER BY alias, room_id
+            "#,
+        )
+    }
+
+    /// Loads all audit log entries
+    fn raw_audit_log_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT id, table_name, operation, row_key, changed_at, old_hash, new_hash
+                FROM statestore_audit_log
+                ORDER BY id
+            "#,
+        )
+    }
+
+    /// Loads all room upgrades
+    fn raw_room_upgrades_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT old_room_id, new_room_id, event_type
+                FROM statestore_room_upgrades
+                ORDER BY old_room_id, new_room_id, event_type
+            "#,
+        )
+    }
+
+    /// Loads all space hierarchy edges
+    fn raw_space_edges_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT parent_id, child_id, event_type
+                FROM statestore_space_edges
+                ORDER BY parent_id, child_id, event_type
+            "#,
+        )
+    }
+
+    /// Loads all tombstones of removed rooms
+    fn raw_room_tombstones_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT room_id, removed_at
+                FROM statestore_room_tombstones
+                ORDER BY room_id
+            "#,
+        )
+    }
+
+    /// Loads all presence events
+    fn raw_presence_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, CAST(presence AS TEXT) AS presence
+                FROM statestore_presence
+                ORDER BY user_id
+            "#,
+        )
+    }
+
+    /// Loads all global profiles
+    fn raw_profiles_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT user_id, displayname, avatar_url, updated_at
+                FROM statestore_profiles
+                ORDER BY user_id
+            "#,
+        )
+    }
+
+    /// Loads all media files, without their contents
+    fn raw_media_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT media_url, thumbnail_width, thumbnail_height, thumbnail_method, content_hash, media_size, pinned
+                FROM statestore_media
+                ORDER BY media_url, thumbnail_width, thumbnail_height, thumbnail_method
+            "#,
+        )
+    }
+
+    /// Loads all negative cache entries
+    fn raw_negative_cache_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT cache_key, expires_at
+                FROM statestore_negative_cache
+                ORDER BY cache_key
+            "#,
+        )
+    }
+
+    /// Loads all key-value entries
+    fn raw_kv_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT kv_key, kv_value
+                FROM statestore_kv
+                ORDER BY kv_key
+            "#,
+        )
+    }
+
+    /// Loads all custom values
+    fn raw_custom_values_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT custom_key, custom_value
+                FROM statestore_custom_values
+                ORDER BY custom_key
+            "#,
+        )
+    }
+
+    /// Loads all sync filters
+    fn raw_filters_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT filter_name, filter_id, updated_at
+                FROM statestore_filters
+                ORDER BY filter_name
+            "#,
+        )
+    }
+
+    /// Loads all cached server responses
+    fn raw_server_cache_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT cache_key, response, expires_at
+                FROM statestore_server_cache
+                ORDER BY cache_key
+            "#,
+        )
+    }
+
+    /// Loads all sync token history
+    fn raw_sync_tokens_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT id, sync_token, created_at
+                FROM statestore_sync_tokens
+                ORDER BY id
+            "#,
+        )
+    }
+
+    /// Loads all sliding sync connections
+    fn raw_sliding_sync_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT conn_id, pos, updated_at
+                FROM statestore_sliding_sync
+                ORDER BY conn_id
+            "#,
+        )
+    }
+
+    /// Loads all sliding sync lists
+    fn raw_sliding_sync_lists_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT conn_id, list_name, ranges, room_count, room_list
+                FROM statestore_sliding_sync_lists
+                ORDER BY conn_id, list_name
+            "#,
+        )
+    }
+
+    /// Loads all sliding sync room subscriptions
+    fn raw_sliding_sync_subscriptions_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT conn_id, room_id, subscription
+                FROM statestore_sliding_sync_subscriptions
+                ORDER BY conn_id, room_id
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl RowQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl RowQueries for sqlx::sqlite::Sqlite {}
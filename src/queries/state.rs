@@ -0,0 +1,134 @@
+//! Queries for room state
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for the state events and stripped state events of rooms
+#[allow(single_use_lifetimes)]
+pub trait StateQueries: Database {
+    /// Upserts a state event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event type
+    /// * `$3` - The state key
+    /// * `$4` - Whether or not the state is partial
+    /// * `$5` - The event as JSON, if it is stored as JSON
+    /// * `$6` - The event ID
+    /// * `$7` - The serialized event, if it is stored in another format
+    fn state_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_state
+                    (room_id, event_type, state_key, is_partial, state_event, event_id, state_event_data)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT(room_id, event_type, state_key) DO UPDATE SET is_partial = $4, state_event = $5, event_id = $6, state_event_data = $7
+            "#,
+        )
+    }
+
+    /// Redacts a state event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The state event ID
+    fn state_redact_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_state
+                WHERE room_id = $1 AND event_id = $2
+            "#,
+        )
+    }
+
+    /// Retrieves a state event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event type
+    /// * `$3` - The state key
+    fn state_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT state_event, state_event_data FROM statestore_state
+                WHERE room_id = $1 AND event_type = $2 AND state_key = $3 AND is_partial = '0'
+            "#,
+        )
+    }
+
+    /// Retrieves a stripped state event
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event type
+    /// * `$3` - The state key
+    fn stripped_state_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT state_event, state_event_data FROM statestore_state
+                WHERE room_id = $1 AND event_type = $2 AND state_key = $3 AND is_partial = '1'
+            "#,
+        )
+    }
+
+    /// Removes the stripped state events of a room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    fn stripped_state_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_state
+                WHERE room_id = $1 AND is_partial = '1'
+            "#,
+        )
+    }
+
+    /// Retrieves all state events by type in room
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event type
+    /// * `$3` - Whether the state is partial
+    fn states_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT state_event, state_event_data FROM statestore_state
+                WHERE room_id = $1 AND event_type = $2 AND is_partial = $3
+            "#,
+        )
+    }
+
+    /// Retrieves the state events of a type in room with any of the given state keys
+    ///
+    /// # Arguments
+    /// * `$1` - The room ID
+    /// * `$2` - The event type
+    /// * `$3` - The state keys as a JSON array
+    fn states_for_keys_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT state_event, state_event_data FROM statestore_state
+                WHERE room_id = $1 AND event_type = $2 AND is_partial = '0'
+                    AND state_key IN (SELECT jsonb_array_elements_text($3::jsonb))
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl StateQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl StateQueries for sqlx::sqlite::Sqlite {
+    fn states_for_keys_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT state_event, state_event_data FROM statestore_state
+                WHERE room_id = $1 AND event_type = $2 AND is_partial = '0'
+                    AND state_key IN (SELECT value FROM json_each($3))
+            "#,
+        )
+    }
+}
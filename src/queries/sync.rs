@@ -0,0 +1,206 @@
+//! Queries for sync tokens and sliding sync connections
+
+use sqlx::{database::HasArguments, query::Query, Database};
+
+use crate::helpers::prefixed_query;
+
+/// Queries for the sync token history and the window state of sliding sync connections
+#[allow(single_use_lifetimes)]
+pub trait SyncQueries: Database {
+    /// Upserts the `pos` token of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    /// * `$2` - The `pos` token, if any
+    /// * `$3` - The current unix timestamp
+    fn sliding_sync_upsert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_sliding_sync (conn_id, pos, updated_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (conn_id) DO UPDATE SET pos = $2, updated_at = $3
+            "#,
+        )
+    }
+
+    /// Loads the `pos` token of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    fn sliding_sync_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT pos FROM statestore_sliding_sync WHERE conn_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes a sliding sync connection, without its lists and room subscriptions
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    fn sliding_sync_delete_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_sliding_sync WHERE conn_id = $1
+            "#,
+        )
+    }
+
+    /// Inserts a list of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    /// * `$2` - The name of the list
+    /// * `$3` - The JSON encoded ranges
+    /// * `$4` - The number of rooms in the list, if known
+    /// * `$5` - The JSON encoded room list
+    fn sliding_sync_list_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_sliding_sync_lists
+                    (conn_id, list_name, ranges, room_count, room_list)
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+    }
+
+    /// Loads the lists of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    fn sliding_sync_lists_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                SELECT list_name, ranges, room_count, room_list FROM statestore_sliding_sync_lists
+                WHERE conn_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes the lists of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    fn sliding_sync_lists_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_sliding_sync_lists WHERE conn_id = $1
+            "#,
+        )
+    }
+
+    /// Inserts a room subscription of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    /// * `$2` - The room ID
+    /// * `$3` - The JSON encoded room subscription
+    fn sliding_sync_subscription_insert_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_sliding_sync_subscriptions (conn_id, room_id, subscription)
+                VALUES ($1, $2, $3)
+            "#,
+        )
+    }
+
+    /// Loads the room subscriptions of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    fn sliding_sync_subscriptions_load_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT room_id, subscription FROM statestore_sliding_sync_subscriptions
+                WHERE conn_id = $1
+            "#,
+        )
+    }
+
+    /// Deletes the room subscriptions of a sliding sync connection
+    ///
+    /// # Arguments
+    /// * `$1` - The connection ID
+    fn sliding_sync_subscriptions_delete_query<'q>(
+    ) -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_sliding_sync_subscriptions WHERE conn_id = $1
+            "#,
+        )
+    }
+
+    /// Returns a query for appending a sync token to the sync token history
+    ///
+    /// # Arguments
+    /// * `$1` - The sync token
+    /// * `$2` - The current unix timestamp
+    fn sync_token_insert_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                INSERT INTO statestore_sync_tokens (sync_token, created_at)
+                VALUES ($1, $2)
+            "#,
+        )
+    }
+
+    /// Returns a query for removing all but the newest sync tokens from the history
+    ///
+    /// # Arguments
+    /// * `$1` - The number of sync tokens to keep
+    fn sync_token_prune_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_sync_tokens
+                WHERE id NOT IN
+                    (SELECT id FROM statestore_sync_tokens
+                     ORDER BY id DESC
+                     LIMIT $1)
+            "#,
+        )
+    }
+
+    /// Returns a query for loading the sync token history, newest first
+    fn sync_token_history_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT sync_token, created_at FROM statestore_sync_tokens
+                ORDER BY id DESC
+            "#,
+        )
+    }
+
+    /// Returns a query for loading the newest sync token
+    fn sync_token_load_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments> {
+        prefixed_query(
+            r#"
+                SELECT sync_token FROM statestore_sync_tokens
+                ORDER BY id DESC
+                LIMIT 1
+            "#,
+        )
+    }
+
+    /// Returns a query for removing the newest sync token
+    fn sync_token_delete_latest_query<'q>() -> Query<'q, Self, <Self as HasArguments<'q>>::Arguments>
+    {
+        prefixed_query(
+            r#"
+                DELETE FROM statestore_sync_tokens
+                WHERE id = (SELECT MAX(id) FROM statestore_sync_tokens)
+            "#,
+        )
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl SyncQueries for sqlx::postgres::Postgres {}
+
+#[cfg(feature = "sqlite")]
+impl SyncQueries for sqlx::sqlite::Sqlite {}
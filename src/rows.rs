@@ -0,0 +1,711 @@
+//! Typed rows of the state store tables
+//!
+//! [`StateStore::raw`] loads the rows of a table as they are stored, for reports and queries
+//! that the store API does not cover. JSON columns are returned as text, and timestamps as unix
+//! timestamps in seconds unless noted otherwise. The rows follow the current schema, so new
+//! fields are added whenever a migration adds a column.
+
+use crate::{
+    helpers::{BorrowedSqlType, SqlType},
+    telemetry::timed,
+    Result, StateStore, SupportedDatabase,
+};
+use futures::TryStreamExt;
+use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::Receipt,
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent,
+    },
+    serde::Raw,
+};
+use sqlx::{
+    database::HasArguments, query::Query, types::Json, ColumnIndex, Database, Executor, FromRow,
+    IntoArguments, Transaction,
+};
+
+/// A row of the `statestore_rooms` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct RoomRow {
+    /// The ID of the room
+    pub room_id: String,
+    /// Whether or not this is the stripped room info of an invite
+    pub is_partial: bool,
+    /// The room info as JSON
+    pub room_info: String,
+    /// The latest event suitable for a room list preview as JSON
+    pub latest_event: Option<String>,
+    /// Origin server timestamp of the latest event in milliseconds
+    pub latest_event_ts: Option<i64>,
+    /// The name of the room
+    pub room_name: Option<String>,
+    /// Whether or not encryption is enabled in the room
+    pub is_encrypted: bool,
+    /// The membership state of the own user in the room, such as `Joined`
+    pub room_state: Option<String>,
+    /// Whether or not the full member list of the room has been loaded
+    pub members_synced: bool,
+}
+
+/// A row of the `statestore_state` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct StateRow {
+    /// The ID of the room
+    pub room_id: String,
+    /// The type of the state event
+    pub event_type: String,
+    /// The state key of the state event
+    pub state_key: String,
+    /// Whether or not this is stripped state of an invite
+    pub is_partial: bool,
+    /// The state event as JSON, missing if it is stored in another format
+    pub state_event: Option<String>,
+    /// The ID of the state event, missing for stripped state
+    pub event_id: Option<String>,
+    /// The state event serialized with another format than JSON, see
+    /// [`StateStore::set_serializer`](crate::StateStore::set_serializer)
+    pub state_event_data: Option<Vec<u8>>,
+}
+
+/// A row of the `statestore_members` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct MemberRow {
+    /// The ID of the room
+    pub room_id: String,
+    /// The ID of the member
+    pub user_id: String,
+    /// Whether or not this is a stripped member of an invite
+    pub is_partial: bool,
+    /// The membership event as JSON
+    pub member_event: Option<String>,
+    /// The profile of the member in the room as JSON
+    pub user_profile: Option<String>,
+    /// The display name of the member
+    pub displayname: Option<String>,
+    /// Whether the member joined the room, as opposed to only being invited
+    pub joined: bool,
+    /// The membership event serialized with another format than JSON, see
+    /// [`StateStore::set_serializer`](crate::StateStore::set_serializer)
+    pub member_event_data: Option<Vec<u8>>,
+}
+
+/// A row of the `statestore_display_names` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct DisplayNameRow {
+    /// The ID of the room
+    pub room_id: String,
+    /// The ID of the member
+    pub user_id: String,
+    /// The display name of the member
+    pub display_name: String,
+}
+
+/// A row of the `statestore_member_history` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct MemberHistoryRow {
+    /// The ID of the row, increasing with every stored change
+    pub id: i64,
+    /// The ID of the room
+    pub room_id: String,
+    /// The ID of the member
+    pub user_id: String,
+    /// The new membership state
+    pub membership: String,
+    /// The ID of the membership event
+    pub event_id: String,
+    /// Unix timestamp in seconds at which the change was stored
+    pub recorded_at: i64,
+}
+
+/// A row of the `statestore_receipts` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct ReceiptRow {
+    /// The ID of the room
+    pub room_id: String,
+    /// The ID of the event the receipt is for
+    pub event_id: String,
+    /// The type of the receipt
+    pub receipt_type: String,
+    /// The ID of the user that sent the receipt
+    pub user_id: String,
+    /// The ID of the thread, empty for unthreaded receipts
+    pub thread_id: String,
+    /// The receipt as JSON
+    pub receipt: String,
+}
+
+/// A row of the `statestore_accountdata` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct AccountDataRow {
+    /// The ID of the room, empty for global account data
+    pub room_id: String,
+    /// The type of the account data event
+    pub event_type: String,
+    /// The account data event as JSON
+    pub account_data: String,
+}
+
+/// A row of the `statestore_room_tags` table
+#[derive(Clone, Debug, PartialEq, FromRow)]
+#[non_exhaustive]
+pub struct RoomTagRow {
+    /// The ID of the room
+    pub room_id: String,
+    /// The name of the tag
+    pub tag: String,
+    /// The order of the room within the tag
+    pub tag_order: Option<f64>,
+}
+
+/// A row of the `statestore_direct_chats` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct DirectChatRow {
+    /// The ID of the chat partner
+    pub user_id: String,
+    /// The ID of the room
+    pub room_id: String,
+}
+
+/// A row of the `statestore_ignored_users` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct IgnoredUserRow {
+    /// The ID of the ignored user
+    pub user_id: String,
+}
+
+/// A row of the `statestore_push_rules` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct PushRuleRow {
+    /// The kind of the rule, such as `override` or `underride`
+    pub kind: String,
+    /// The ID of the rule
+    pub rule_id: String,
+    /// The position of the rule within its kind, in order of evaluation
+    pub rule_position: i64,
+    /// Whether the rule is enabled
+    pub enabled: bool,
+    /// Whether the rule is a server default rule
+    pub is_default: bool,
+    /// The rule as JSON
+    pub rule: String,
+}
+
+/// A row of the `statestore_aliases` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct AliasRow {
+    /// The alias
+    pub alias: String,
+    /// The ID of the room
+    pub room_id: String,
+    /// Whether the alias is the canonical alias of the room
+    pub is_canonical: bool,
+}
+
+/// A row of the `statestore_audit_log` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct AuditLogRow {
+    /// Sequence number of the entry
+    pub id: i64,
+    /// The name of the mutated table
+    pub table_name: String,
+    /// The kind of mutation, `INSERT`, `UPDATE` or `DELETE`
+    pub operation: String,
+    /// The primary key of the mutated row as a JSON array, if the table has one
+    pub row_key: Option<String>,
+    /// Unix timestamp in seconds of the mutation
+    pub changed_at: i64,
+    /// SHA-256 hash of the row before the mutation, only recorded by PostgreSQL
+    pub old_hash: Option<String>,
+    /// SHA-256 hash of the row after the mutation, only recorded by PostgreSQL
+    pub new_hash: Option<String>,
+}
+
+/// A row of the `statestore_room_upgrades` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct RoomUpgradeRow {
+    /// The ID of the upgraded room
+    pub old_room_id: String,
+    /// The ID of the replacement room
+    pub new_room_id: String,
+    /// The type of the state event defining the upgrade
+    pub event_type: String,
+}
+
+/// A row of the `statestore_space_edges` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct SpaceEdgeRow {
+    /// The ID of the space
+    pub parent_id: String,
+    /// The ID of the room in the space
+    pub child_id: String,
+    /// The type of the state event the edge was taken from
+    pub event_type: String,
+}
+
+/// A row of the `statestore_room_tombstones` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct RoomTombstoneRow {
+    /// The ID of the removed room
+    pub room_id: String,
+    /// Unix timestamp in seconds at which the room was removed
+    pub removed_at: i64,
+}
+
+/// A row of the `statestore_presence` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct PresenceRow {
+    /// The ID of the user
+    pub user_id: String,
+    /// The presence event as JSON
+    pub presence: String,
+}
+
+/// A row of the `statestore_profiles` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct ProfileRow {
+    /// The ID of the user
+    pub user_id: String,
+    /// The display name of the user
+    pub displayname: Option<String>,
+    /// The avatar of the user
+    pub avatar_url: Option<String>,
+    /// Unix timestamp in seconds at which the profile was stored
+    pub updated_at: i64,
+}
+
+/// A row of the `statestore_media` table, without the media contents
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct MediaRow {
+    /// The MXC URI of the media file
+    pub media_url: String,
+    /// The width of the thumbnail, `0` for the original file
+    pub thumbnail_width: i64,
+    /// The height of the thumbnail, `0` for the original file
+    pub thumbnail_height: i64,
+    /// The resizing method of the thumbnail, empty for the original file
+    pub thumbnail_method: String,
+    /// The SHA-256 hash of the contents if they are deduplicated
+    pub content_hash: Option<Vec<u8>>,
+    /// The size of the contents in bytes
+    pub media_size: i64,
+    /// Whether or not the media file is exempt from eviction
+    pub pinned: bool,
+}
+
+/// A row of the `statestore_negative_cache` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct NegativeCacheRow {
+    /// The key of the missing resource
+    pub cache_key: String,
+    /// Unix timestamp in seconds at which the entry expires
+    pub expires_at: i64,
+}
+
+/// A row of the `statestore_kv` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct KvRow {
+    /// The key
+    pub kv_key: Vec<u8>,
+    /// The value
+    pub kv_value: Vec<u8>,
+}
+
+/// A row of the `statestore_custom_values` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct CustomValueRow {
+    /// The key
+    pub custom_key: Vec<u8>,
+    /// The value
+    pub custom_value: Vec<u8>,
+}
+
+/// A row of the `statestore_filters` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct FilterRow {
+    /// The name of the filter
+    pub filter_name: String,
+    /// The ID of the filter on the homeserver
+    pub filter_id: String,
+    /// Unix timestamp in seconds at which the filter was stored
+    pub updated_at: i64,
+}
+
+/// A row of the `statestore_server_cache` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct ServerCacheRow {
+    /// The key of the response, such as `capabilities`
+    pub cache_key: String,
+    /// The response as JSON
+    pub response: String,
+    /// Unix timestamp in seconds at which the response expires
+    pub expires_at: i64,
+}
+
+/// A row of the `statestore_sync_tokens` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct SyncTokenRow {
+    /// The ID of the row, increasing with every stored token
+    pub id: i64,
+    /// The sync token
+    pub sync_token: String,
+    /// Unix timestamp in seconds at which the token was stored
+    pub created_at: i64,
+}
+
+/// A row of the `statestore_sliding_sync` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct SlidingSyncRow {
+    /// The ID of the connection
+    pub conn_id: String,
+    /// The position of the connection
+    pub pos: Option<String>,
+    /// Unix timestamp in seconds at which the state was stored
+    pub updated_at: i64,
+}
+
+/// A row of the `statestore_sliding_sync_lists` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct SlidingSyncListRow {
+    /// The ID of the connection
+    pub conn_id: String,
+    /// The name of the list
+    pub list_name: String,
+    /// The ranges of the list as a JSON array of `[start, end]` pairs
+    pub ranges: String,
+    /// The number of rooms in the list
+    pub room_count: Option<i64>,
+    /// The rooms of the list as a JSON array of room IDs or nulls
+    pub room_list: String,
+}
+
+/// A row of the `statestore_sliding_sync_subscriptions` table
+#[derive(Clone, Debug, PartialEq, Eq, FromRow)]
+#[non_exhaustive]
+pub struct SlidingSyncSubscriptionRow {
+    /// The ID of the connection
+    pub conn_id: String,
+    /// The ID of the subscribed room
+    pub room_id: String,
+    /// The room subscription as JSON
+    pub subscription: String,
+}
+
+/// Read-only access to the rows of the state store tables
+///
+/// See [`StateStore::raw`].
+#[derive(Debug)]
+pub struct RawTables<'a, DB: SupportedDatabase> {
+    /// The store to read from
+    store: &'a StateStore<DB>,
+}
+
+impl<DB: SupportedDatabase> StateStore<DB> {
+    /// Returns read-only access to the rows of the state store tables
+    ///
+    /// The rows are read from the read pool. Cryptostore tables are not included, as their
+    /// values are encrypted.
+    pub const fn raw(&self) -> RawTables<'_, DB> {
+        RawTables { store: self }
+    }
+}
+
+#[allow(single_use_lifetimes)]
+impl<'s, DB: SupportedDatabase> RawTables<'s, DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Loads all rows of a table
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails or a row cannot be decoded
+    async fn load<T>(
+        &self,
+        query: Query<'static, DB, <DB as HasArguments<'static>>::Arguments>,
+    ) -> Result<Vec<T>>
+    where
+        T: for<'r> FromRow<'r, <DB as Database>::Row> + Send + Unpin,
+    {
+        let mut rows = query.fetch(timed(&*self.store.read_db, self.store.table_prefix));
+        let mut result = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            result.push(T::from_row(&row)?);
+        }
+        Ok(result)
+    }
+
+    /// Loads all room infos, ordered by room ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn rooms(&self) -> Result<Vec<RoomRow>> {
+        self.load(DB::raw_rooms_load_query()).await
+    }
+
+    /// Loads all state events, ordered by room ID, event type and state key
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn state(&self) -> Result<Vec<StateRow>> {
+        self.load(DB::raw_state_load_query()).await
+    }
+
+    /// Loads all room members, ordered by room ID and user ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn members(&self) -> Result<Vec<MemberRow>> {
+        self.load(DB::raw_members_load_query()).await
+    }
+
+    /// Loads the display names of all room members, ordered by room ID and display name
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn display_names(&self) -> Result<Vec<DisplayNameRow>> {
+        self.load(DB::raw_display_names_load_query()).await
+    }
+
+    /// Loads the membership history, oldest change first
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn member_history(&self) -> Result<Vec<MemberHistoryRow>> {
+        self.load(DB::raw_member_history_load_query()).await
+    }
+
+    /// Loads all receipts, ordered by room ID, receipt type, user ID and thread ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn receipts(&self) -> Result<Vec<ReceiptRow>> {
+        self.load(DB::raw_receipts_load_query()).await
+    }
+
+    /// Loads all global and room account data, ordered by room ID and event type
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn account_data(&self) -> Result<Vec<AccountDataRow>> {
+        self.load(DB::raw_account_data_load_query()).await
+    }
+
+    /// Loads all room tags, ordered by room ID and tag
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn room_tags(&self) -> Result<Vec<RoomTagRow>> {
+        self.load(DB::raw_room_tags_load_query()).await
+    }
+
+    /// Loads all direct chats, ordered by user ID and room ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn direct_chats(&self) -> Result<Vec<DirectChatRow>> {
+        self.load(DB::raw_direct_chats_load_query()).await
+    }
+
+    /// Loads all ignored users, ordered by user ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn ignored_users(&self) -> Result<Vec<IgnoredUserRow>> {
+        self.load(DB::raw_ignored_users_load_query()).await
+    }
+
+    /// Loads all push rules, ordered by kind and position
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn push_rules(&self) -> Result<Vec<PushRuleRow>> {
+        self.load(DB::raw_push_rules_load_query()).await
+    }
+
+    /// Loads all room aliases, ordered by alias and room ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn aliases(&self) -> Result<Vec<AliasRow>> {
+        self.load(DB::raw_aliases_load_query()).await
+    }
+
+    /// Loads all audit log entries, oldest first
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn audit_log(&self) -> Result<Vec<AuditLogRow>> {
+        self.load(DB::raw_audit_log_load_query()).await
+    }
+
+    /// Loads all room upgrades, ordered by the old and the new room ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn room_upgrades(&self) -> Result<Vec<RoomUpgradeRow>> {
+        self.load(DB::raw_room_upgrades_load_query()).await
+    }
+
+    /// Loads all edges of the space hierarchy, ordered by parent ID and child ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn space_edges(&self) -> Result<Vec<SpaceEdgeRow>> {
+        self.load(DB::raw_space_edges_load_query()).await
+    }
+
+    /// Loads the tombstones of all removed rooms, ordered by room ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn room_tombstones(&self) -> Result<Vec<RoomTombstoneRow>> {
+        self.load(DB::raw_room_tombstones_load_query()).await
+    }
+
+    /// Loads all presence events, ordered by user ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn presence(&self) -> Result<Vec<PresenceRow>> {
+        self.load(DB::raw_presence_load_query()).await
+    }
+
+    /// Loads all global profiles, ordered by user ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn profiles(&self) -> Result<Vec<ProfileRow>> {
+        self.load(DB::raw_profiles_load_query()).await
+    }
+
+    /// Loads all media files without their contents, ordered by URL and thumbnail size
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn media(&self) -> Result<Vec<MediaRow>> {
+        self.load(DB::raw_media_load_query()).await
+    }
+
+    /// Loads all entries of the negative cache, ordered by key
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn negative_cache(&self) -> Result<Vec<NegativeCacheRow>> {
+        self.load(DB::raw_negative_cache_load_query()).await
+    }
+
+    /// Loads all key-value entries, ordered by key
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn kv(&self) -> Result<Vec<KvRow>> {
+        self.load(DB::raw_kv_load_query()).await
+    }
+
+    /// Loads all custom values, ordered by key
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn custom_values(&self) -> Result<Vec<CustomValueRow>> {
+        self.load(DB::raw_custom_values_load_query()).await
+    }
+
+    /// Loads all sync filters, ordered by name
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn filters(&self) -> Result<Vec<FilterRow>> {
+        self.load(DB::raw_filters_load_query()).await
+    }
+
+    /// Loads all cached server responses, ordered by key
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn server_cache(&self) -> Result<Vec<ServerCacheRow>> {
+        self.load(DB::raw_server_cache_load_query()).await
+    }
+
+    /// Loads the sync token history, oldest token first
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn sync_tokens(&self) -> Result<Vec<SyncTokenRow>> {
+        self.load(DB::raw_sync_tokens_load_query()).await
+    }
+
+    /// Loads all sliding sync connections, ordered by connection ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn sliding_sync(&self) -> Result<Vec<SlidingSyncRow>> {
+        self.load(DB::raw_sliding_sync_load_query()).await
+    }
+
+    /// Loads all sliding sync lists, ordered by connection ID and list name
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn sliding_sync_lists(&self) -> Result<Vec<SlidingSyncListRow>> {
+        self.load(DB::raw_sliding_sync_lists_load_query()).await
+    }
+
+    /// Loads all sliding sync room subscriptions, ordered by connection ID and room ID
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn sliding_sync_subscriptions(&self) -> Result<Vec<SlidingSyncSubscriptionRow>> {
+        self.load(DB::raw_sliding_sync_subscriptions_load_query())
+            .await
+    }
+}
@@ -0,0 +1,408 @@
+//! Machine-readable descriptions of the database schema changes
+
+/// Rough estimate of how long a schema change takes to apply
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum MigrationDuration {
+    /// The change only touches the schema, and completes instantly regardless of the amount of
+    /// stored data
+    Instant,
+    /// The change rewrites or indexes existing rows, and takes time proportional to the size of
+    /// the affected tables
+    Linear,
+}
+
+/// Description of a single schema change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SchemaChange {
+    /// The version of the migration applying the change
+    pub version: i64,
+    /// Human-readable summary of the change
+    pub description: &'static str,
+    /// Tables created by the change
+    pub tables_added: &'static [&'static str],
+    /// Columns added, altered or removed by the change, as `table.column`
+    pub columns_altered: &'static [&'static str],
+    /// How long the change is expected to take to apply
+    pub duration: MigrationDuration,
+}
+
+/// All schema changes, ordered by version
+///
+/// Every migration in `migrations/` needs an entry here.
+pub(crate) static SCHEMA_CHANGES: &[SchemaChange] = &[
+    SchemaChange {
+        version: 20_220_510_121_100,
+        description: "Initial state store schema",
+        tables_added: &[
+            "statestore_kv",
+            "statestore_media",
+            "statestore_rooms",
+            "statestore_accountdata",
+            "statestore_presence",
+            "statestore_members",
+            "statestore_state",
+            "statestore_receipts",
+        ],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_220_514_084_500,
+        description: "Initial crypto store schema",
+        tables_added: &[
+            "cryptostore_session",
+            "cryptostore_message_hash",
+            "cryptostore_inbound_group_session",
+            "cryptostore_outbound_group_session",
+            "cryptostore_gossip_request",
+            "cryptostore_identity",
+            "cryptostore_device",
+            "cryptostore_tracked_user",
+        ],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_114_111_000,
+        description: "Store the event ID of state events",
+        tables_added: &[],
+        columns_altered: &["statestore_state.event_id"],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_201_120_000,
+        description: "Track the backup state of inbound group sessions",
+        tables_added: &[],
+        columns_altered: &["cryptostore_inbound_group_session.backed_up"],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_202_120_000,
+        description: "Negative cache for missing media and profiles",
+        tables_added: &["statestore_negative_cache"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_203_120_000,
+        description: "Withheld room key info",
+        tables_added: &["cryptostore_withheld_sessions"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_204_120_000,
+        description: "Secret inbox",
+        tables_added: &["cryptostore_secrets"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_205_120_000,
+        description: "Dedicated table for own cross-signing key pairs",
+        tables_added: &["cryptostore_private_identity"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_206_120_000,
+        description: "Crypto room settings",
+        tables_added: &["cryptostore_room_settings"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_207_120_000,
+        description: "Leased locks for multi-process crypto store access",
+        tables_added: &["cryptostore_lease_locks"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_208_120_000,
+        description: "Deduplicate media by content hash",
+        tables_added: &["statestore_media_blobs"],
+        columns_altered: &["statestore_media.content_hash"],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_209_120_000,
+        description: "Track the size of stored media",
+        tables_added: &[],
+        columns_altered: &["statestore_media.media_size"],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_210_120_000,
+        description: "Store media thumbnails as separate entries",
+        tables_added: &[],
+        columns_altered: &[
+            "statestore_media.thumbnail_width",
+            "statestore_media.thumbnail_height",
+            "statestore_media.thumbnail_method",
+        ],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_211_120_000,
+        description: "Schema compatibility information",
+        tables_added: &["statestore_schema_compat"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_212_120_000,
+        description: "Dedicated table for sync filters",
+        tables_added: &["statestore_filters"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_213_120_000,
+        description: "Threaded read receipts",
+        tables_added: &[],
+        columns_altered: &["statestore_receipts.thread_id"],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_214_120_000,
+        description: "Sync token history",
+        tables_added: &["statestore_sync_tokens"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_215_120_000,
+        description: "Membership change history",
+        tables_added: &["statestore_member_history"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_216_120_000,
+        description: "Dedicated table for custom values",
+        tables_added: &["statestore_custom_values"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_217_120_000,
+        description: "Display name map",
+        tables_added: &["statestore_display_names"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_218_120_000,
+        description: "Global profiles",
+        tables_added: &["statestore_profiles"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_219_120_000,
+        description: "Room tag and direct chat index",
+        tables_added: &["statestore_room_tags", "statestore_direct_chats"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_220_120_000,
+        description: "Room tombstones",
+        tables_added: &[
+            "statestore_room_tombstones",
+            "statestore_rooms_tombstone",
+            "statestore_accountdata_tombstone",
+            "statestore_members_tombstone",
+            "statestore_display_names_tombstone",
+            "statestore_state_tombstone",
+            "statestore_receipts_tombstone",
+            "statestore_room_tags_tombstone",
+        ],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_221_120_000,
+        description: "Space hierarchy edges",
+        tables_added: &["statestore_space_edges"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_222_120_000,
+        description: "Latest event per room",
+        tables_added: &[],
+        columns_altered: &[
+            "statestore_rooms.latest_event",
+            "statestore_rooms.latest_event_ts",
+        ],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_223_120_000,
+        description: "Media pinning",
+        tables_added: &[],
+        columns_altered: &["statestore_media.pinned"],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_224_120_000,
+        description: "Add a table for rows that failed to deserialize",
+        tables_added: &["statestore_quarantine"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_225_120_000,
+        description: "Move the olm account out of the key-value table",
+        tables_added: &["cryptostore_account"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_226_120_000,
+        description: "Record when message hashes were stored",
+        tables_added: &[],
+        columns_altered: &["cryptostore_message_hash.created_at"],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_227_120_000,
+        description: "Deduplicate unsent key requests",
+        tables_added: &[],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_221_228_120_000,
+        description: "Add a table for the dehydrated device",
+        tables_added: &["cryptostore_dehydrated_device"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_229_120_000,
+        description: "Add a table for verification requests",
+        tables_added: &["cryptostore_verification"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_230_120_000,
+        description: "Add tables for sliding sync connections",
+        tables_added: &[
+            "statestore_sliding_sync",
+            "statestore_sliding_sync_lists",
+            "statestore_sliding_sync_subscriptions",
+        ],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_221_231_120_000,
+        description: "Room list columns of room infos",
+        tables_added: &[],
+        columns_altered: &[
+            "statestore_rooms.room_name",
+            "statestore_rooms.is_encrypted",
+            "statestore_rooms.room_state",
+        ],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_101_120_000,
+        description: "Track whether the member list of a room is complete",
+        tables_added: &[],
+        columns_altered: &["statestore_rooms.members_synced"],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_102_120_000,
+        description: "Cache of server capabilities and well-known responses",
+        tables_added: &["statestore_server_cache"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_230_103_120_000,
+        description: "Index over the ignored user list",
+        tables_added: &["statestore_ignored_users"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_230_104_120_000,
+        description: "Normalized push rules",
+        tables_added: &["statestore_push_rules"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_230_105_120_000,
+        description: "Index over the aliases of rooms",
+        tables_added: &["statestore_aliases"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_106_120_000,
+        description: "Audit log of mutations",
+        tables_added: &["statestore_audit_log"],
+        columns_altered: &[],
+        duration: MigrationDuration::Instant,
+    },
+    SchemaChange {
+        version: 20_230_107_120_000,
+        description: "Room upgrade chains",
+        tables_added: &["statestore_room_upgrades"],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_108_120_000,
+        description: "Order global profiles by the timestamp of the member event",
+        tables_added: &[],
+        columns_altered: &["statestore_profiles.origin_server_ts"],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_109_120_000,
+        description: "Events serialized with another format than JSON",
+        tables_added: &[],
+        columns_altered: &[
+            "statestore_state.state_event",
+            "statestore_state.state_event_data",
+            "statestore_members.member_event_data",
+        ],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_110_120_000,
+        description: "Indexes for the pages of room infos",
+        tables_added: &[],
+        columns_altered: &[],
+        duration: MigrationDuration::Linear,
+    },
+    SchemaChange {
+        version: 20_230_111_120_000,
+        description: "Reference counts of shared media contents",
+        tables_added: &[],
+        columns_altered: &["statestore_media_blobs.ref_count"],
+        duration: MigrationDuration::Linear,
+    },
+];
+
+/// The oldest schema version that a crate version needs to know about to be able to use a
+/// database written by this crate version
+///
+/// This needs to be bumped to the latest version whenever a schema change breaks older crate
+/// versions.
+pub(crate) const MIN_READER_SCHEMA_VERSION: i64 = 20_230_109_120_000;
+
+/// Returns the newest schema version known to this crate
+pub(crate) fn latest_schema_version() -> i64 {
+    SCHEMA_CHANGES.last().map_or(0, |change| change.version)
+}
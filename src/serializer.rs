@@ -0,0 +1,195 @@
+//! Serialization formats of events and cryptostore values
+
+use std::fmt::Debug;
+
+use ruma::serde::Raw;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Result, SQLStoreError};
+
+/// Serialization format of events and cryptostore values
+///
+/// Cryptostore values are serialized before they are compressed and encrypted. JSON is used by
+/// default, see [`StateStore::set_serializer`](crate::StateStore::set_serializer). Values of
+/// other formats are marked with a prefix, so that values written with any of the formats
+/// compiled into this crate stay readable after the format was changed.
+pub trait StoreSerializer: Debug + Send + Sync {
+    /// Returns the prefix that marks values of this format
+    ///
+    /// Prefixes have to start with a NUL byte, which serialized JSON never does, and must not
+    /// start with the prefix of another format. JSON itself has an empty prefix.
+    fn prefix(&self) -> &'static [u8];
+
+    /// Serializes a value, without the prefix
+    ///
+    /// The formats of this crate serialize values directly, this is only called for custom
+    /// formats.
+    ///
+    /// # Errors
+    /// This function will return an error if the value cannot be serialized
+    fn serialize(&self, value: &serde_json::Value) -> Result<Vec<u8>>;
+
+    /// Deserializes a value written by [`StoreSerializer::serialize`]
+    ///
+    /// The formats of this crate deserialize values directly, this is only called for custom
+    /// formats.
+    ///
+    /// # Errors
+    /// This function will return an error if the data cannot be deserialized
+    fn deserialize(&self, data: &[u8]) -> Result<serde_json::Value>;
+}
+
+/// Prefix of values serialized with [`MessagePackSerializer`]
+#[cfg(feature = "msgpack")]
+const MESSAGE_PACK_PREFIX: &[u8] = b"\0rmp";
+
+/// Prefix of values serialized with [`CborSerializer`]
+#[cfg(feature = "cbor")]
+const CBOR_PREFIX: &[u8] = b"\0cbor";
+
+/// Stores values as JSON, the default format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JsonSerializer;
+
+impl StoreSerializer for JsonSerializer {
+    fn prefix(&self) -> &'static [u8] {
+        b""
+    }
+
+    fn serialize(&self, value: &serde_json::Value) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<serde_json::Value> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// Stores values as MessagePack, which is smaller and faster to parse than JSON
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessagePackSerializer;
+
+#[cfg(feature = "msgpack")]
+impl StoreSerializer for MessagePackSerializer {
+    fn prefix(&self) -> &'static [u8] {
+        MESSAGE_PACK_PREFIX
+    }
+
+    fn serialize(&self, value: &serde_json::Value) -> Result<Vec<u8>> {
+        rmp_serde::to_vec_named(value).map_err(|e| SQLStoreError::Serializer(Box::new(e)))
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<serde_json::Value> {
+        rmp_serde::from_slice(data).map_err(|e| SQLStoreError::Serializer(Box::new(e)))
+    }
+}
+
+/// Stores values as CBOR, which is smaller and faster to parse than JSON
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CborSerializer;
+
+#[cfg(feature = "cbor")]
+impl StoreSerializer for CborSerializer {
+    fn prefix(&self) -> &'static [u8] {
+        CBOR_PREFIX
+    }
+
+    fn serialize(&self, value: &serde_json::Value) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(value, &mut data)
+            .map_err(|e| SQLStoreError::Serializer(Box::new(e)))?;
+        Ok(data)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<serde_json::Value> {
+        ciborium::de::from_reader(data).map_err(|e| SQLStoreError::Serializer(Box::new(e)))
+    }
+}
+
+/// Serializes a value with the given format, including its prefix
+///
+/// # Errors
+/// This function returns an error if serialization fails.
+pub(crate) fn serialize<T: Serialize + ?Sized>(
+    serializer: &dyn StoreSerializer,
+    value: &T,
+) -> Result<Vec<u8>> {
+    let prefix = serializer.prefix();
+    let mut serialized = prefix.to_vec();
+    match prefix {
+        b"" => serde_json::to_writer(&mut serialized, value)?,
+        #[cfg(feature = "msgpack")]
+        MESSAGE_PACK_PREFIX => rmp_serde::encode::write_named(&mut serialized, value)
+            .map_err(|e| SQLStoreError::Serializer(Box::new(e)))?,
+        #[cfg(feature = "cbor")]
+        CBOR_PREFIX => ciborium::ser::into_writer(value, &mut serialized)
+            .map_err(|e| SQLStoreError::Serializer(Box::new(e)))?,
+        _ => serialized.extend(serializer.serialize(&serde_json::to_value(value)?)?),
+    }
+    Ok(serialized)
+}
+
+/// Deserializes a value written with any of the formats of this crate, or the given format
+///
+/// The format is taken from the prefix of the value, so values stay readable after the
+/// configured format was changed.
+///
+/// # Errors
+/// This function returns an error if deserialization fails, or if the value was written with a
+/// custom format other than the given one.
+pub(crate) fn deserialize<T: DeserializeOwned>(
+    serializer: &dyn StoreSerializer,
+    value: &[u8],
+) -> Result<T> {
+    if value.first() != Some(&0) {
+        return Ok(serde_json::from_slice(value)?);
+    }
+    #[cfg(feature = "msgpack")]
+    if let Some(data) = value.strip_prefix(MESSAGE_PACK_PREFIX) {
+        return rmp_serde::from_slice(data).map_err(|e| SQLStoreError::Serializer(Box::new(e)));
+    }
+    #[cfg(feature = "cbor")]
+    if let Some(data) = value.strip_prefix(CBOR_PREFIX) {
+        return ciborium::de::from_reader(data)
+            .map_err(|e| SQLStoreError::Serializer(Box::new(e)));
+    }
+    let prefix = serializer.prefix();
+    match value.strip_prefix(prefix) {
+        Some(data) if !prefix.is_empty() => {
+            Ok(serde_json::from_value(serializer.deserialize(data)?)?)
+        }
+        _ => Err(SQLStoreError::UnknownSerializer),
+    }
+}
+
+/// Serializes an event for its binary column, or returns `None` to store it as JSON
+///
+/// Events are kept in their JSON column with the JSON format, so that the database can look
+/// into them.
+///
+/// # Errors
+/// This function returns an error if the event is not valid JSON or if serialization fails.
+pub(crate) fn serialize_event<T>(
+    serializer: &dyn StoreSerializer,
+    event: &Raw<T>,
+) -> Result<Option<Vec<u8>>> {
+    if serializer.prefix().is_empty() {
+        return Ok(None);
+    }
+    let event: serde_json::Value = event.deserialize_as()?;
+    serialize(serializer, &event).map(Some)
+}
+
+/// Deserializes an event that was stored in its binary column
+///
+/// # Errors
+/// This function returns an error if deserialization fails.
+pub(crate) fn deserialize_event<T>(
+    serializer: &dyn StoreSerializer,
+    data: &[u8],
+) -> Result<Raw<T>> {
+    let event: serde_json::Value = deserialize(serializer, data)?;
+    Ok(Raw::from_json(serde_json::value::to_raw_value(&event)?))
+}
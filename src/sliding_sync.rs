@@ -0,0 +1,214 @@
+//! Storage of the window state of sliding sync connections
+
+use std::collections::BTreeMap;
+
+use crate::{
+    helpers::{unix_timestamp, BorrowedSqlType, SqlType},
+    telemetry::timed,
+    Result, StateStore, SupportedDatabase,
+};
+use futures::TryStreamExt;
+use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::Receipt,
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent,
+    },
+    serde::Raw,
+    OwnedRoomId, UInt,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments, Row,
+    Transaction,
+};
+
+/// Window state of a sliding sync connection
+///
+/// See [`StateStore::save_sliding_sync`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlidingSyncState {
+    /// The `pos` token of the last response
+    pub pos: Option<String>,
+    /// The lists of the connection, by name
+    pub lists: BTreeMap<String, SlidingSyncList>,
+    /// The room subscriptions of the connection, as sent to the server
+    pub room_subscriptions: BTreeMap<OwnedRoomId, serde_json::Value>,
+}
+
+/// Window state of a single sliding sync list
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlidingSyncList {
+    /// The ranges of the list that are being synced, with inclusive bounds
+    pub ranges: Vec<(UInt, UInt)>,
+    /// The number of rooms in the list on the server, if known
+    pub room_count: Option<u64>,
+    /// The rooms of the list in order, `None` for entries that are not known
+    pub room_list: Vec<Option<OwnedRoomId>>,
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> StateStore<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Stores the window state of a sliding sync connection, replacing the previous state
+    ///
+    /// The connection ID distinguishes several sliding sync connections of the same client.
+    /// Use [`StateStore::save_sliding_sync_pos`] if only the `pos` token has changed.
+    ///
+    /// # Errors
+    /// This function will return an error if the state cannot be encoded or if the query fails
+    pub async fn save_sliding_sync(&self, conn_id: &str, state: &SlidingSyncState) -> Result<()> {
+        let mut txn = self.db.begin().await?;
+        DB::sliding_sync_upsert_query()
+            .bind(conn_id)
+            .bind(state.pos.clone())
+            .bind(unix_timestamp())
+            .execute(timed(&mut txn, self.table_prefix))
+            .await?;
+        DB::sliding_sync_lists_delete_query()
+            .bind(conn_id)
+            .execute(timed(&mut txn, self.table_prefix))
+            .await?;
+        for (name, list) in &state.lists {
+            DB::sliding_sync_list_insert_query()
+                .bind(conn_id)
+                .bind(name.as_str())
+                .bind(serde_json::to_string(&list.ranges)?)
+                .bind(
+                    list.room_count
+                        .map(|count| i64::try_from(count).unwrap_or(i64::MAX)),
+                )
+                .bind(serde_json::to_string(&list.room_list)?)
+                .execute(timed(&mut txn, self.table_prefix))
+                .await?;
+        }
+        DB::sliding_sync_subscriptions_delete_query()
+            .bind(conn_id)
+            .execute(timed(&mut txn, self.table_prefix))
+            .await?;
+        for (room_id, subscription) in &state.room_subscriptions {
+            DB::sliding_sync_subscription_insert_query()
+                .bind(conn_id)
+                .bind(room_id.as_str())
+                .bind(serde_json::to_string(subscription)?)
+                .execute(timed(&mut txn, self.table_prefix))
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Stores the `pos` token of a sliding sync connection, keeping its lists and subscriptions
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn save_sliding_sync_pos(&self, conn_id: &str, pos: Option<&str>) -> Result<()> {
+        DB::sliding_sync_upsert_query()
+            .bind(conn_id)
+            .bind(pos.map(ToOwned::to_owned))
+            .bind(unix_timestamp())
+            .execute(timed(&*self.db, self.table_prefix))
+            .await?;
+        Ok(())
+    }
+
+    /// Loads the window state of a sliding sync connection
+    ///
+    /// Returns `None` if no state has been stored for the connection.
+    ///
+    /// # Errors
+    /// This function will return an error if the stored state cannot be decoded or if the query
+    /// fails
+    pub async fn load_sliding_sync(&self, conn_id: &str) -> Result<Option<SlidingSyncState>> {
+        let row = DB::sliding_sync_load_query()
+            .bind(conn_id)
+            .fetch_optional(timed(&*self.read_db, self.table_prefix))
+            .await?;
+        let pos = match row {
+            Some(row) => row.try_get("pos")?,
+            None => return Ok(None),
+        };
+        let mut state = SlidingSyncState {
+            pos,
+            ..SlidingSyncState::default()
+        };
+
+        let mut rows = DB::sliding_sync_lists_load_query()
+            .bind(conn_id)
+            .fetch(timed(&*self.read_db, self.table_prefix));
+        while let Some(row) = rows.try_next().await? {
+            let ranges: String = row.try_get("ranges")?;
+            let room_count: Option<i64> = row.try_get("room_count")?;
+            let room_list: String = row.try_get("room_list")?;
+            state.lists.insert(
+                row.try_get("list_name")?,
+                SlidingSyncList {
+                    ranges: serde_json::from_str(&ranges)?,
+                    room_count: room_count.map(|count| u64::try_from(count).unwrap_or_default()),
+                    room_list: serde_json::from_str(&room_list)?,
+                },
+            );
+        }
+
+        let mut rows = DB::sliding_sync_subscriptions_load_query()
+            .bind(conn_id)
+            .fetch(timed(&*self.read_db, self.table_prefix));
+        while let Some(row) = rows.try_next().await? {
+            let room_id: String = row.try_get("room_id")?;
+            let subscription: String = row.try_get("subscription")?;
+            state.room_subscriptions.insert(
+                OwnedRoomId::try_from(room_id)?,
+                serde_json::from_str(&subscription)?,
+            );
+        }
+        Ok(Some(state))
+    }
+
+    /// Deletes the window state of a sliding sync connection
+    ///
+    /// # Errors
+    /// This function will return an error if the query fails
+    pub async fn delete_sliding_sync(&self, conn_id: &str) -> Result<()> {
+        let mut txn = self.db.begin().await?;
+        DB::sliding_sync_delete_query()
+            .bind(conn_id)
+            .execute(timed(&mut txn, self.table_prefix))
+            .await?;
+        DB::sliding_sync_lists_delete_query()
+            .bind(conn_id)
+            .execute(timed(&mut txn, self.table_prefix))
+            .await?;
+        DB::sliding_sync_subscriptions_delete_query()
+            .bind(conn_id)
+            .execute(timed(&mut txn, self.table_prefix))
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+//! Logging of slow queries and table prefixes of executed queries
+
+use std::{
+    fmt::{self, Debug},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, PoisonError, RwLock,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Stream};
+use once_cell::sync::Lazy;
+use sqlx::{
+    database::{HasArguments, HasStatement},
+    Database, Either, Execute, Executor,
+};
+use sqlx_core::describe::Describe;
+use tracing::warn;
+
+use crate::helpers::prefixed_sql;
+
+/// A query that took longer than the slow query threshold
+///
+/// See [`set_slow_query_threshold`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SlowQuery {
+    /// The SQL statement with normalized whitespace
+    ///
+    /// Bound parameters only appear as placeholders such as `$1`, their values are never
+    /// included.
+    pub sql: String,
+    /// How long the query took, including the time spent waiting for a connection or a lock
+    pub duration: Duration,
+}
+
+/// Callback receiving every slow query, see [`set_slow_query_callback`]
+pub type SlowQueryCallback = Arc<dyn Fn(&SlowQuery) + Send + Sync>;
+
+/// The slow query threshold in nanoseconds, `0` if slow queries are not logged
+static SLOW_QUERY_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+/// The callback receiving slow queries
+static SLOW_QUERY_CALLBACK: Lazy<RwLock<Option<SlowQueryCallback>>> =
+    Lazy::new(|| RwLock::new(None));
+
+/// Sets the threshold above which queries are considered slow
+///
+/// Slow queries are logged as warnings with the target `matrix_sdk_sql::slow_query` and passed
+/// to the callback set with [`set_slow_query_callback`]. The duration is measured from starting
+/// the query until its last row has been read, so it includes waiting for a pool connection and,
+/// on SQLite, for the database lock. This helps diagnosing lock contention. The threshold
+/// applies to all stores of the process. Pass `None` to stop logging slow queries, which is the
+/// default.
+pub fn set_slow_query_threshold(threshold: Option<Duration>) {
+    let nanos = threshold.map_or(0, |threshold| {
+        u64::try_from(threshold.as_nanos())
+            .unwrap_or(u64::MAX)
+            .max(1)
+    });
+    SLOW_QUERY_THRESHOLD.store(nanos, Ordering::Relaxed);
+}
+
+/// Sets a callback that receives every slow query
+///
+/// The callback is called from the task that ran the query, so it should return quickly, for
+/// example by recording a metric. It is only called once a threshold has been set with
+/// [`set_slow_query_threshold`].
+pub fn set_slow_query_callback(callback: Option<SlowQueryCallback>) {
+    *SLOW_QUERY_CALLBACK
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = callback;
+}
+
+/// Logs the query if it took longer than the slow query threshold
+fn record_query(sql: &str, duration: Duration) {
+    let threshold = SLOW_QUERY_THRESHOLD.load(Ordering::Relaxed);
+    if threshold == 0 || duration < Duration::from_nanos(threshold) {
+        return;
+    }
+    let query = SlowQuery {
+        sql: sql.split_whitespace().collect::<Vec<_>>().join(" "),
+        duration,
+    };
+    warn!(
+        target: "matrix_sdk_sql::slow_query",
+        sql = %query.sql,
+        duration_ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        "Slow query"
+    );
+    let callback = SLOW_QUERY_CALLBACK
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    if let Some(callback) = callback {
+        callback(&query);
+    }
+}
+
+/// Executor that measures the duration of the queries it runs and applies the table prefix
+///
+/// See [`timed`].
+#[derive(Debug)]
+pub(crate) struct Timed<E> {
+    /// The wrapped executor
+    executor: E,
+    /// The table prefix of the store running the queries
+    table_prefix: &'static str,
+}
+
+/// Wraps an executor, so that its queries are logged if they are slow
+///
+/// The table names of the queries are prefixed with `table_prefix`, see
+/// [`StateStore::with_table_prefix`](crate::StateStore::with_table_prefix).
+pub(crate) const fn timed<E>(executor: E, table_prefix: &'static str) -> Timed<E> {
+    Timed {
+        executor,
+        table_prefix,
+    }
+}
+
+/// Query with the table prefix applied to its SQL statement
+struct Prefixed<Q> {
+    /// The original query
+    query: Q,
+    /// The prefixed SQL statement, `None` if the query is run unchanged
+    sql: Option<&'static str>,
+}
+
+impl<'q, DB: Database, Q: Execute<'q, DB>> Execute<'q, DB> for Prefixed<Q> {
+    fn sql(&self) -> &'q str {
+        self.sql.unwrap_or_else(|| self.query.sql())
+    }
+
+    fn statement(&self) -> Option<&<DB as HasStatement<'q>>::Statement> {
+        // A statement prepared by the caller belongs to the unprefixed SQL
+        if self.sql.is_some() {
+            None
+        } else {
+            self.query.statement()
+        }
+    }
+
+    fn take_arguments(&mut self) -> Option<<DB as HasArguments<'q>>::Arguments> {
+        self.query.take_arguments()
+    }
+
+    fn persistent(&self) -> bool {
+        self.query.persistent()
+    }
+}
+
+/// Stream of query results that records the duration of the query when it is dropped
+struct TimedStream<'e, T> {
+    /// The results of the query
+    inner: BoxStream<'e, T>,
+    /// The SQL statement of the query
+    sql: &'e str,
+    /// When the query was started
+    start: Instant,
+}
+
+impl<T> Stream for TimedStream<'_, T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().inner.as_mut().poll_next(cx)
+    }
+}
+
+impl<T> Drop for TimedStream<'_, T> {
+    fn drop(&mut self) {
+        record_query(self.sql, self.start.elapsed());
+    }
+}
+
+impl<T> Debug for TimedStream<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimedStream")
+            .field("sql", &self.sql)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'c, E: Executor<'c>> Executor<'c> for Timed<E> {
+    type Database = E::Database;
+
+    fn fetch_many<'e, 'q: 'e, Q: 'q>(
+        self,
+        query: Q,
+    ) -> BoxStream<
+        'e,
+        sqlx::Result<
+            Either<<Self::Database as Database>::QueryResult, <Self::Database as Database>::Row>,
+        >,
+    >
+    where
+        'c: 'e,
+        Q: Execute<'q, Self::Database>,
+    {
+        let sql = query.sql();
+        let query = Prefixed {
+            sql: prefixed_sql(sql, self.table_prefix),
+            query,
+        };
+        let start = Instant::now();
+        Box::pin(TimedStream {
+            inner: self.executor.fetch_many(query),
+            sql,
+            start,
+        })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, Q: 'q>(
+        self,
+        query: Q,
+    ) -> BoxFuture<'e, sqlx::Result<Option<<Self::Database as Database>::Row>>>
+    where
+        'c: 'e,
+        Q: Execute<'q, Self::Database>,
+    {
+        let sql = query.sql();
+        let query = Prefixed {
+            sql: prefixed_sql(sql, self.table_prefix),
+            query,
+        };
+        let start = Instant::now();
+        let result = self.executor.fetch_optional(query);
+        async move {
+            let result = result.await;
+            record_query(sql, start.elapsed());
+            result
+        }
+        .boxed()
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [<Self::Database as Database>::TypeInfo],
+    ) -> BoxFuture<'e, sqlx::Result<<Self::Database as HasStatement<'q>>::Statement>>
+    where
+        'c: 'e,
+    {
+        let sql = prefixed_sql(sql, self.table_prefix).unwrap_or(sql);
+        self.executor.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, sqlx::Result<Describe<Self::Database>>>
+    where
+        'c: 'e,
+    {
+        let sql = prefixed_sql(sql, self.table_prefix).unwrap_or(sql);
+        self.executor.describe(sql)
+    }
+}
@@ -0,0 +1,79 @@
+//! Disposable postgres instances for tests
+
+use std::{net::TcpListener, sync::Arc, time::Duration};
+
+use pg_embed::{
+    pg_enums::PgAuthMethod,
+    pg_fetch::{PgFetchSettings, PG_V13},
+    postgres::{PgEmbed, PgSettings},
+};
+use sqlx::{migrate::MigrateDatabase, PgPool, Postgres};
+use tempfile::TempDir;
+
+use crate::Result;
+
+/// A disposable postgres instance
+///
+/// The postgres binaries are downloaded on first use and cached by `pg-embed`. The database
+/// cluster lives in a temporary directory and is deleted when the instance is dropped.
+#[allow(missing_debug_implementations)]
+pub struct TestPostgres {
+    /// The running postgres server
+    pg: PgEmbed,
+    /// The directory holding the database cluster
+    _data_dir: TempDir,
+}
+
+impl TestPostgres {
+    /// Starts a new postgres instance on a free local port
+    ///
+    /// # Errors
+    /// This function will return an error if postgres cannot be downloaded, set up or started
+    pub async fn start() -> Result<Self> {
+        let data_dir = tempfile::tempdir()?;
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let pg_settings = PgSettings {
+            database_dir: data_dir.path().join("db"),
+            port,
+            user: "postgres".to_owned(),
+            password: "postgres".to_owned(),
+            auth_method: PgAuthMethod::Plain,
+            persistent: false,
+            timeout: Some(Duration::from_secs(30)),
+            migration_dir: None,
+        };
+        let fetch_settings = PgFetchSettings {
+            version: PG_V13,
+            ..PgFetchSettings::default()
+        };
+        let mut pg = PgEmbed::new(pg_settings, fetch_settings).await?;
+        pg.setup().await?;
+        pg.start_db().await?;
+        Ok(Self {
+            pg,
+            _data_dir: data_dir,
+        })
+    }
+
+    /// Returns the URL of a database on this instance, creating the database if it does not exist
+    ///
+    /// # Errors
+    /// This function will return an error if the database cannot be created
+    pub async fn database_url(&self, name: &str) -> Result<String> {
+        let db_url = self.pg.full_db_uri(name);
+        if !Postgres::database_exists(&db_url).await? {
+            Postgres::create_database(&db_url).await?;
+        }
+        Ok(db_url)
+    }
+
+    /// Returns a connection pool for a database on this instance, creating the database if it does
+    /// not exist
+    ///
+    /// # Errors
+    /// This function will return an error if the database cannot be created or connected to
+    pub async fn pool(&self, name: &str) -> Result<Arc<PgPool>> {
+        let db_url = self.database_url(name).await?;
+        Ok(Arc::new(PgPool::connect(&db_url).await?))
+    }
+}
@@ -0,0 +1,517 @@
+//! Unit-of-work API for making application changes atomically with SDK state changes
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    helpers::{unix_timestamp, BorrowedSqlType, SqlType},
+    telemetry::timed,
+    Result, RoomLocks, SQLStoreError, StateStore, StoreSerializer, SupportedDatabase,
+};
+use futures::{future::BoxFuture, TryStreamExt};
+use matrix_sdk_base::{
+    deserialized_responses::RawMemberEvent, MinimalRoomMemberEvent, RoomInfo, StateChanges,
+};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::{Receipt, ReceiptType},
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, AnySyncTimelineEvent, GlobalAccountDataEventType,
+        RoomAccountDataEventType, StateEventType,
+    },
+    serde::Raw,
+    OwnedEventId, RoomId, UserId,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments, Row,
+    Transaction,
+};
+use tokio::sync::OwnedMutexGuard;
+
+/// Transactional handle to the state store
+///
+/// Obtained through [`StateStore::transaction`]. All reads and writes made through this handle
+/// are part of the same database transaction, which is committed once the callback returns
+/// successfully. Reads see the changes made earlier in the transaction.
+#[allow(single_use_lifetimes)]
+#[derive(Debug)]
+pub struct StoreTransaction<DB: SupportedDatabase> {
+    /// The underlying database transaction
+    txn: Transaction<'static, DB>,
+    /// Prefix prepended to the names of all tables
+    table_prefix: &'static str,
+    /// Whether membership changes are recorded in the member history
+    member_history: bool,
+    /// Number of sync tokens kept in the sync token history
+    sync_token_history_len: u32,
+    /// Serialization format of new events
+    serializer: Arc<dyn StoreSerializer>,
+    /// Per-room write locks of the store
+    room_locks: Arc<RoomLocks>,
+    /// Write locks of the rooms changed in the transaction, held until it ends
+    room_guards: BTreeMap<usize, OwnedMutexGuard<()>>,
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> StoreTransaction<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Returns the underlying database transaction
+    ///
+    /// Use this to run the application's own queries as part of the transaction.
+    pub fn connection(&mut self) -> &mut Transaction<'static, DB> {
+        &mut self.txn
+    }
+
+    /// Save the set of state changes in the store
+    ///
+    /// Takes the per-room write locks of the changed rooms and holds them until the transaction
+    /// ends, so that concurrent syncs of the same rooms wait for it. Rooms that are changed by
+    /// several calls are locked by the first one, so save the changes of all rooms with a
+    /// single call where possible, to avoid acquiring the locks out of order.
+    ///
+    /// # Errors
+    /// This function will return an error if the database query fails
+    pub async fn save_changes(&mut self, state_changes: &StateChanges) -> Result<()> {
+        self.room_locks
+            .lock_owned(
+                StateStore::<DB>::changed_rooms(state_changes),
+                &mut self.room_guards,
+            )
+            .await;
+        StateStore::<DB>::save_state_changes_txn(
+            &mut self.txn,
+            self.table_prefix,
+            state_changes,
+            self.sync_token_history_len,
+            &*self.serializer,
+        )
+        .await?;
+        if self.member_history {
+            for room_id in state_changes.members.keys() {
+                StateStore::<DB>::record_member_history_txn(
+                    &mut self.txn,
+                    self.table_prefix,
+                    room_id,
+                    state_changes,
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the last stored sync token
+    ///
+    /// # Errors
+    /// This function will return an error if the database query fails
+    pub async fn get_sync_token(&mut self) -> Result<Option<String>> {
+        let row = DB::sync_token_load_query()
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => Ok(Some(row.try_get("sync_token")?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Put arbitrary data into the custom store
+    ///
+    /// # Errors
+    /// This function will return an error if the upsert cannot be performed
+    pub async fn set_custom_value(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        DB::custom_value_upsert_query()
+            .bind(key)
+            .bind(value)
+            .execute(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        Ok(())
+    }
+
+    /// Get arbitrary data from the custom store
+    ///
+    /// # Errors
+    /// This function will return an error if the database query fails
+    pub async fn get_custom_value(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let row = DB::custom_value_load_query()
+            .bind(key)
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => Ok(Some(row.try_get("custom_value")?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save the given filter id under the given name
+    ///
+    /// # Errors
+    /// This function will return an error if the upsert cannot be performed
+    pub async fn save_filter(&mut self, name: &str, filter_id: &str) -> Result<()> {
+        DB::filter_upsert_query()
+            .bind(name)
+            .bind(filter_id)
+            .bind(unix_timestamp())
+            .execute(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        Ok(())
+    }
+
+    /// Get the filter id that was stored under the given filter name
+    ///
+    /// # Errors
+    /// This function will return an error if the database query fails
+    pub async fn get_filter(&mut self, name: &str) -> Result<Option<String>> {
+        let row = DB::filter_load_query()
+            .bind(name)
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => Ok(Some(row.try_get("filter_id")?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieves a state event in room by event type and state key
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_state_event(
+        &mut self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+        state_key: &str,
+    ) -> Result<Option<Raw<AnySyncStateEvent>>> {
+        let row = DB::state_load_query()
+            .bind(room_id.as_str())
+            .bind(event_type.to_string())
+            .bind(state_key)
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => StateStore::<DB>::decode_event(
+                &*self.serializer,
+                &row,
+                "state_event",
+                "state_event_data",
+            )
+            .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieves all state events of a given type in a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_state_events(
+        &mut self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+    ) -> Result<Vec<Raw<AnySyncStateEvent>>> {
+        let mut rows = DB::states_load_query()
+            .bind(room_id.as_str())
+            .bind(event_type.to_string())
+            .bind(false)
+            .fetch(timed(&mut self.txn, self.table_prefix));
+        let mut result = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            result.push(StateStore::<DB>::decode_event(
+                &*self.serializer,
+                &row,
+                "state_event",
+                "state_event_data",
+            )?);
+        }
+        Ok(result)
+    }
+
+    /// Retrieves a member event for a user in a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_member_event(
+        &mut self,
+        room_id: &RoomId,
+        user_id: &UserId,
+    ) -> Result<Option<RawMemberEvent>> {
+        let row = DB::member_load_query()
+            .bind(room_id.as_str())
+            .bind(user_id.as_str())
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        if row.try_get::<'_, bool, _>("is_partial")? {
+            let event = StateStore::<DB>::decode_event(
+                &*self.serializer,
+                &row,
+                "member_event",
+                "member_event_data",
+            )?;
+            Ok(Some(RawMemberEvent::Stripped(event)))
+        } else {
+            let event = StateStore::<DB>::decode_event(
+                &*self.serializer,
+                &row,
+                "member_event",
+                "member_event_data",
+            )?;
+            Ok(Some(RawMemberEvent::Sync(event)))
+        }
+    }
+
+    /// Get room infos
+    ///
+    /// Unlike [`matrix_sdk_base::StateStore::get_room_infos`], room infos that fail to
+    /// deserialize are returned as errors instead of being quarantined.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_room_infos(&mut self) -> Result<Vec<RoomInfo>> {
+        self.get_room_infos_internal(false).await
+    }
+
+    /// Get partial room infos
+    ///
+    /// Unlike [`matrix_sdk_base::StateStore::get_stripped_room_infos`], room infos that fail to
+    /// deserialize are returned as errors instead of being quarantined.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_stripped_room_infos(&mut self) -> Result<Vec<RoomInfo>> {
+        self.get_room_infos_internal(true).await
+    }
+
+    /// Get room infos
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    async fn get_room_infos_internal(&mut self, partial: bool) -> Result<Vec<RoomInfo>> {
+        let mut rows = DB::room_info_load_query()
+            .bind(partial)
+            .fetch(timed(&mut self.txn, self.table_prefix));
+        let mut result = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let room_info: String = row.try_get("room_info")?;
+            result.push(serde_json::from_str(&room_info)?);
+        }
+        Ok(result)
+    }
+
+    /// Get global account data for an account data event type
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_account_data_event(
+        &mut self,
+        event_type: GlobalAccountDataEventType,
+    ) -> Result<Option<Raw<AnyGlobalAccountDataEvent>>> {
+        let row = DB::account_data_load_query()
+            .bind("")
+            .bind(event_type.to_string())
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => Ok(Some(
+                row.try_get::<'_, Json<Raw<AnyGlobalAccountDataEvent>>, _>("account_data")?
+                    .0,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Get room account data for an account data event type
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_room_account_data_event(
+        &mut self,
+        room_id: &RoomId,
+        event_type: RoomAccountDataEventType,
+    ) -> Result<Option<Raw<AnyRoomAccountDataEvent>>> {
+        let row = DB::account_data_load_query()
+            .bind(room_id.as_str())
+            .bind(event_type.to_string())
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => Ok(Some(
+                row.try_get::<'_, Json<Raw<AnyRoomAccountDataEvent>>, _>("account_data")?
+                    .0,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the latest receipt of a user in a thread of a room
+    ///
+    /// Pass `None` as the thread ID to get the unthreaded receipt of the user.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_user_room_thread_receipt_event(
+        &mut self,
+        room_id: &RoomId,
+        receipt_type: ReceiptType,
+        thread_id: Option<&str>,
+        user_id: &UserId,
+    ) -> Result<Option<(OwnedEventId, Receipt)>> {
+        let row = DB::receipt_load_query()
+            .bind(room_id.as_str())
+            .bind(receipt_type.as_str())
+            .bind(user_id.as_str())
+            .bind(thread_id.unwrap_or(""))
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        let event_id = row.try_get::<'_, String, _>("event_id")?.try_into()?;
+        let receipt = row.try_get::<'_, Json<Receipt>, _>("receipt")?.0;
+        Ok(Some((event_id, receipt)))
+    }
+
+    /// Get latest receipt for user in room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_user_room_receipt_event(
+        &mut self,
+        room_id: &RoomId,
+        receipt_type: ReceiptType,
+        user_id: &UserId,
+    ) -> Result<Option<(OwnedEventId, Receipt)>> {
+        self.get_user_room_thread_receipt_event(room_id, receipt_type, None, user_id)
+            .await
+    }
+
+    /// Gets presence for a user
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_presence_event(
+        &mut self,
+        user_id: &UserId,
+    ) -> Result<Option<Raw<PresenceEvent>>> {
+        let row = DB::presence_load_query()
+            .bind(user_id.as_str())
+            .fetch_optional(timed(&mut self.txn, self.table_prefix))
+            .await?;
+        match row {
+            Some(row) => Ok(Some(
+                row.try_get::<'_, Json<Raw<PresenceEvent>>, _>("presence")?
+                    .0,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[allow(single_use_lifetimes)]
+impl<DB: SupportedDatabase> StateStore<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'a, 'c> &'c mut Transaction<'a, DB>: Executor<'c, Database = DB>,
+    for<'a> &'a [u8]: BorrowedSqlType<'a, DB>,
+    for<'a> &'a str: BorrowedSqlType<'a, DB>,
+    Vec<u8>: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    String: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<SyncRoomMemberEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    bool: SqlType<DB>,
+    i64: SqlType<DB>,
+    f64: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<StrippedRoomMemberEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncTimelineEvent>>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    /// Runs the callback inside of a database transaction
+    ///
+    /// The transaction is committed if the callback returns `Ok`, and rolled back otherwise.
+    /// State changes queued by write batching are written before the transaction starts, so
+    /// that they are visible to it.
+    ///
+    /// ```rust,ignore
+    /// store
+    ///     .transaction(|txn| {
+    ///         Box::pin(async move {
+    ///             txn.save_changes(&changes).await?;
+    ///             sqlx::query("UPDATE app_state SET synced = TRUE")
+    ///                 .execute(txn.connection())
+    ///                 .await?;
+    ///             Ok::<_, SQLStoreError>(())
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    ///
+    /// # Errors
+    /// This function will return an error if the transaction cannot be started or committed,
+    /// or if the callback fails
+    pub async fn transaction<F, T, E>(&self, callback: F) -> Result<T, E>
+    where
+        F: for<'t> FnOnce(&'t mut StoreTransaction<DB>) -> BoxFuture<'t, Result<T, E>> + Send,
+        T: Send,
+        E: From<SQLStoreError> + Send,
+    {
+        self.flush().await?;
+        let mut handle = StoreTransaction {
+            txn: self.db.begin().await.map_err(SQLStoreError::from)?,
+            table_prefix: self.table_prefix,
+            member_history: self.member_history,
+            sync_token_history_len: self.sync_token_history_len,
+            serializer: Arc::clone(&self.serializer),
+            room_locks: Arc::clone(&self.room_locks),
+            room_guards: BTreeMap::new(),
+        };
+        match callback(&mut handle).await {
+            Ok(value) => {
+                handle.txn.commit().await.map_err(SQLStoreError::from)?;
+                Ok(value)
+            }
+            Err(error) => {
+                handle.txn.rollback().await.map_err(SQLStoreError::from)?;
+                Err(error)
+            }
+        }
+    }
+}